@@ -0,0 +1,56 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Field of View Benchmarks
+//!
+
+#[macro_use]
+extern crate criterion;
+extern crate oxide_rs;
+
+use criterion::Criterion;
+use oxide_rs::rand::{Rng, SeedableRng};
+use oxide_rs::{Algorithm, GameRng, Grid, Point, Radius, Visibility};
+
+const ALGORITHMS: [Algorithm; 3] = [Algorithm::Shadowcasting, Algorithm::Permissive, Algorithm::Symmetric];
+
+/// 200x200 map with roughly one tile in eight opaque
+fn scattered_map() -> Grid<bool> {
+    let mut rng = GameRng::from_seed(0xF0F0);
+    Grid::from_fn(200, 200, |_| rng.gen_range(0, 8) != 0)
+}
+
+fn bench_fov(c: &mut Criterion) {
+    let open = Grid::new(200, 200, true);
+    let scattered = scattered_map();
+    let center = Point::new(100, 100);
+    let mut group = c.benchmark_group("fov_200x200");
+    for &algorithm in ALGORITHMS.iter() {
+        let mut visibility = Visibility::new();
+        for &(name, map) in [("open", &open), ("scattered", &scattered)].iter() {
+            group.bench_function(format!("{:?}/{}/unlimited", algorithm, name), |b| {
+                b.iter(|| visibility.compute(map, center, Radius::Unlimited, algorithm))
+            });
+            group.bench_function(format!("{:?}/{}/circle_12", algorithm, name), |b| {
+                b.iter(|| visibility.compute(map, center, Radius::Circle(12), algorithm))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_fov);
+criterion_main!(benches);
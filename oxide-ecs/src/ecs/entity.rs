@@ -17,7 +17,204 @@
 //! Entity Component System
 //!
 
-use std::fmt::{Debug, Error, Formatter};
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Error, Formatter};
 
 /// Entity ID
-type EntityID = usize;
+pub type EntityID = u32;
+
+/// Entity Handle
+///
+/// An entity is an index paired with the generation of that index at the time it was created.
+/// When an entity is deleted its index may be reused, but the generation is advanced so stale
+/// handles can be detected.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Entity {
+    id: EntityID,
+    generation: u32,
+}
+
+impl Entity {
+    /// Handle which never refers to a living Entity
+    pub const DEAD: Entity = Entity {
+        id: EntityID::MAX,
+        generation: u32::MAX,
+    };
+
+    pub(crate) fn new(id: EntityID, generation: u32) -> Entity {
+        Entity { id, generation }
+    }
+    /// Index of this Entity
+    pub fn id(&self) -> EntityID {
+        self.id
+    }
+    /// Generation of this Entity's index
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl Display for Entity {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}v{}", self.id, self.generation)
+    }
+}
+
+impl Debug for Entity {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "Entity({}v{})", self.id, self.generation)
+    }
+}
+
+/// Entity Allocator
+///
+/// Deserializing checks that the allocator's tables agree, so a damaged snapshot is reported
+/// instead of panicking on a later allocation.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(try_from = "ManagerData")]
+pub struct EntityManager {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<EntityID>,
+}
+
+/// Serialized form of an EntityManager, checked before it becomes one
+#[derive(Deserialize)]
+struct ManagerData {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<EntityID>,
+}
+
+impl TryFrom<ManagerData> for EntityManager {
+    type Error = String;
+    fn try_from(data: ManagerData) -> Result<EntityManager, String> {
+        if data.generations.len() != data.alive.len() {
+            return Err(format!("{} generations for {} entities", data.generations.len(), data.alive.len()));
+        }
+        let mut seen = HashSet::new();
+        for &id in &data.free {
+            match data.alive.get(id as usize) {
+                Some(false) if seen.insert(id) => (),
+                Some(false) => return Err(format!("free entity {} is listed twice", id)),
+                Some(true) => return Err(format!("free entity {} is alive", id)),
+                None => return Err(format!("free entity {} is out of range", id)),
+            }
+        }
+        Ok(EntityManager {
+            generations: data.generations,
+            alive: data.alive,
+            free: data.free,
+        })
+    }
+}
+
+impl EntityManager {
+    /// Create a new, empty Entity Manager
+    pub fn new() -> EntityManager {
+        EntityManager::default()
+    }
+    /// Allocate a new Entity, reusing a free index if one is available.
+    pub fn create(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(id) => {
+                self.alive[id as usize] = true;
+                Entity::new(id, self.generations[id as usize])
+            }
+            None => {
+                let id = self.generations.len() as EntityID;
+                self.generations.push(0);
+                self.alive.push(true);
+                Entity::new(id, 0)
+            }
+        }
+    }
+    /// Delete an Entity, returning false if it was not alive.
+    pub fn delete(&mut self, entity: Entity) -> bool {
+        if self.is_alive(entity) {
+            let index = entity.id as usize;
+            self.alive[index] = false;
+            self.generations[index] = self.generations[index].wrapping_add(1);
+            self.free.push(entity.id);
+            true
+        } else {
+            false
+        }
+    }
+    /// Check if an Entity handle refers to a living Entity.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.id as usize;
+        index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation
+    }
+    /// Number of living Entities
+    pub fn len(&self) -> usize {
+        self.alive.iter().filter(|alive| **alive).count()
+    }
+    /// Check if there are no living Entities
+    pub fn is_empty(&self) -> bool {
+        self.alive.iter().all(|alive| !*alive)
+    }
+    /// Copy of this allocator in which only the given Entities are alive.
+    ///
+    /// Every other living Entity is deleted in the copy, so handles to it do not resolve.
+    pub fn subset(&self, entities: &[Entity]) -> EntityManager {
+        let kept: HashSet<Entity> = entities.iter().cloned().collect();
+        let mut subset = self.clone();
+        for entity in self.iter() {
+            if !kept.contains(&entity) {
+                subset.delete(entity);
+            }
+        }
+        subset
+    }
+    /// Iterate over all living Entities
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = Entity> + 'a {
+        self.alive
+            .iter()
+            .enumerate()
+            .filter(|&(_, alive)| *alive)
+            .map(move |(id, _)| Entity::new(id as EntityID, self.generations[id]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[test]
+    fn reuse_advances_generation() {
+        let mut entities = EntityManager::new();
+        let first = entities.create();
+        let second = entities.create();
+        assert!(entities.delete(first));
+        assert!(!entities.delete(first));
+        assert!(!entities.is_alive(first));
+
+        let third = entities.create();
+        assert_eq!(third.id(), first.id());
+        assert_eq!(third.generation(), first.generation() + 1);
+        assert!(entities.is_alive(third));
+        assert!(entities.is_alive(second));
+        assert_eq!(entities.len(), 2);
+    }
+
+    #[test]
+    fn deserialize_rejects_corrupt_tables() {
+        let mut entities = EntityManager::new();
+        let first = entities.create();
+        entities.create();
+        entities.delete(first);
+        let json = serde_json::to_string(&entities).unwrap();
+        let restored: EntityManager = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.len(), 1);
+
+        let corrupt = |json: &str| serde_json::from_str::<EntityManager>(json).is_err();
+        assert!(corrupt(r#"{"generations":[0],"alive":[true,true],"free":[]}"#));
+        assert!(corrupt(r#"{"generations":[1,0],"alive":[false,true],"free":[5]}"#));
+        assert!(corrupt(r#"{"generations":[1,0],"alive":[false,true],"free":[1]}"#));
+        assert!(corrupt(r#"{"generations":[1,0],"alive":[false,true],"free":[0,0]}"#));
+    }
+}
@@ -16,29 +16,17 @@
 //!
 //! Entity Component System
 //!
-use std::fmt::{Debug, Error, Formatter};
 
-/// Entity ID
-type EntityID = usize;
-
-pub struct EntityManager {
-    entities: Vec<EntityID>
-}
-
-/// Component
-pub trait Component: Clone + Copy + Send + Sync + Debug {}
-
-/// System
-pub trait System {
-    /// Initialize System
-    fn initialize();
-    ///
-    fn cleanup();
-    ///
-    fn suspend();
-    ///
-    fn resume();
-    ///
-    fn update(delta: f64);
-}
+mod component;
+mod entity;
+mod serialize;
+pub mod storage;
+mod system;
+mod world;
 
+pub use self::component::Component;
+pub use self::entity::{Entity, EntityID, EntityManager};
+pub use self::serialize::{EntityMap, Persistent, SnapshotError, WorldSnapshot};
+pub use self::storage::ComponentEvent;
+pub use self::system::System;
+pub use self::world::World;
@@ -0,0 +1,496 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! World Serialization
+//!
+//! Components and Resources opt into snapshots by implementing [`Persistent`] and being
+//! registered under a stable name with [`World::register_persistent`] or
+//! [`World::persist_resource`]. Everything else is skipped.
+//!
+//! [`Persistent`]: trait.Persistent.html
+//! [`World::register_persistent`]: struct.World.html#method.register_persistent
+//! [`World::persist_resource`]: struct.World.html#method.persist_resource
+//!
+
+use super::{Component, Entity, EntityManager, World};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_value::{to_value, DeserializerError, SerializerError, Value};
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Marker for Components and Resources which are written into World snapshots.
+pub trait Persistent: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Rewrite any Entity references after the value has been restored from a snapshot.
+    fn map_entities(&mut self, _map: &EntityMap) {}
+}
+
+/// Mapping of snapshot Entities to the Entities they were restored as.
+#[derive(Clone, Debug, Default)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    /// Create an empty mapping
+    pub fn new() -> EntityMap {
+        EntityMap::default()
+    }
+    /// Record that `from` was restored as `to`.
+    pub fn insert(&mut self, from: Entity, to: Entity) {
+        self.map.insert(from, to);
+    }
+    /// Look up the Entity a snapshot Entity was restored as.
+    pub fn get(&self, entity: Entity) -> Option<Entity> {
+        self.map.get(&entity).cloned()
+    }
+    /// Map an Entity, turning Entities which were not part of the snapshot into [`Entity::DEAD`].
+    ///
+    /// Handles from outside the snapshot may match an unrelated living Entity of the World they
+    /// are restored into, so they are never kept. Use [`get`] to drop them instead.
+    ///
+    /// [`Entity::DEAD`]: struct.Entity.html#associatedconstant.DEAD
+    /// [`get`]: #method.get
+    pub fn map(&self, entity: Entity) -> Entity {
+        self.get(entity).unwrap_or(Entity::DEAD)
+    }
+    /// Number of mapped Entities
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    /// Check if no Entities were mapped
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    /// Iterate over all mapped Entities
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (Entity, Entity)> + 'a {
+        self.map.iter().map(|(from, to)| (*from, *to))
+    }
+}
+
+/// Serializable snapshot of a World's Entities, persistent Components and persistent Resources.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    entities: EntityManager,
+    components: BTreeMap<String, Entries>,
+    resources: BTreeMap<String, Value>,
+}
+
+impl WorldSnapshot {
+    /// Entities captured in the snapshot
+    pub fn entities(&self) -> &EntityManager {
+        &self.entities
+    }
+    /// Drop the captured Resources, leaving only Entities and their Components.
+    pub fn without_resources(mut self) -> WorldSnapshot {
+        self.resources.clear();
+        self
+    }
+}
+
+/// Errors produced while taking or restoring a snapshot
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// A value could not be serialized.
+    Serialize { name: String, message: String },
+    /// A value could not be deserialized.
+    Deserialize { name: String, message: String },
+    /// The snapshot contains a Component name which has not been registered.
+    UnknownComponent(String),
+    /// The snapshot contains a Resource name which has not been registered.
+    UnknownResource(String),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            SnapshotError::Serialize { ref name, ref message } => {
+                write!(f, "Unable to serialize '{}': {}", name, message)
+            }
+            SnapshotError::Deserialize { ref name, ref message } => {
+                write!(f, "Unable to deserialize '{}': {}", name, message)
+            }
+            SnapshotError::UnknownComponent(ref name) => write!(f, "Unknown component '{}'", name),
+            SnapshotError::UnknownResource(ref name) => write!(f, "Unknown resource '{}'", name),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+type Apply = Box<dyn FnOnce(&mut World, &EntityMap)>;
+type Entries = Vec<(Entity, Value)>;
+
+pub(crate) struct ComponentPersister {
+    name: String,
+    type_id: TypeId,
+    save: fn(&World) -> Result<Entries, SerializerError>,
+    load: fn(Entries) -> Result<Apply, DeserializerError>,
+}
+
+pub(crate) struct ResourcePersister {
+    name: String,
+    type_id: TypeId,
+    save: fn(&World) -> Result<Option<Value>, SerializerError>,
+    load: fn(Value) -> Result<Apply, DeserializerError>,
+}
+
+fn save_component<T: Component + Persistent>(world: &World) -> Result<Entries, SerializerError> {
+    match world.storage::<T>() {
+        Some(storage) => storage
+            .iter()
+            .map(|(entity, component)| to_value(component).map(|value| (entity, value)))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+fn load_component<T: Component + Persistent>(entries: Entries) -> Result<Apply, DeserializerError> {
+    let components = entries
+        .into_iter()
+        .map(|(entity, value)| value.deserialize_into::<T>().map(|component| (entity, component)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Box::new(move |world: &mut World, map: &EntityMap| {
+        for (entity, mut component) in components {
+            component.map_entities(map);
+            world.insert(map.map(entity), component);
+        }
+    }))
+}
+
+fn save_resource<R: Persistent>(world: &World) -> Result<Option<Value>, SerializerError> {
+    match world.resources.try_fetch::<R>() {
+        Some(resource) => to_value(&*resource).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn load_resource<R: Persistent>(value: Value) -> Result<Apply, DeserializerError> {
+    let mut resource = value.deserialize_into::<R>()?;
+    Ok(Box::new(move |world: &mut World, map: &EntityMap| {
+        resource.map_entities(map);
+        world.resources.insert(resource);
+    }))
+}
+
+impl World {
+    /// Register a Component type which is included in snapshots under the given name.
+    ///
+    /// # Panics
+    /// If the name is already used by a different Component type.
+    pub fn register_persistent<T: Component + Persistent>(&mut self, name: &str) {
+        self.register::<T>();
+        let type_id = TypeId::of::<T>();
+        if let Some(existing) = self.component_persisters.iter().find(|p| p.name == name) {
+            assert!(existing.type_id == type_id, "Component name '{}' is already registered", name);
+            return;
+        }
+        self.component_persisters.push(ComponentPersister {
+            name: name.to_string(),
+            type_id,
+            save: save_component::<T>,
+            load: load_component::<T>,
+        });
+    }
+    /// Register a Resource type which is included in snapshots under the given name.
+    ///
+    /// # Panics
+    /// If the name is already used by a different Resource type.
+    pub fn persist_resource<R: Persistent>(&mut self, name: &str) {
+        let type_id = TypeId::of::<R>();
+        if let Some(existing) = self.resource_persisters.iter().find(|p| p.name == name) {
+            assert!(existing.type_id == type_id, "Resource name '{}' is already registered", name);
+            return;
+        }
+        self.resource_persisters.push(ResourcePersister {
+            name: name.to_string(),
+            type_id,
+            save: save_resource::<R>,
+            load: load_resource::<R>,
+        });
+    }
+    /// Capture all Entities, persistent Components and persistent Resources.
+    pub fn snapshot(&self) -> Result<WorldSnapshot, SnapshotError> {
+        self.capture(self.entities.clone())
+    }
+    /// Capture only the given Entities, their persistent Components and all persistent Resources.
+    ///
+    /// Dead Entities are ignored. Other Entities are recorded as deleted, so references to them
+    /// from the captured Components do not resolve after a restore.
+    pub fn snapshot_entities(&self, entities: &[Entity]) -> Result<WorldSnapshot, SnapshotError> {
+        self.capture(self.entities.subset(entities))
+    }
+    fn capture(&self, entities: EntityManager) -> Result<WorldSnapshot, SnapshotError> {
+        let mut components = BTreeMap::new();
+        for persister in &self.component_persisters {
+            let mut entries = (persister.save)(self).map_err(|error| SnapshotError::Serialize {
+                name: persister.name.clone(),
+                message: error.to_string(),
+            })?;
+            entries.retain(|&(entity, _)| entities.is_alive(entity));
+            components.insert(persister.name.clone(), entries);
+        }
+        let mut resources = BTreeMap::new();
+        for persister in &self.resource_persisters {
+            let value = (persister.save)(self).map_err(|error| SnapshotError::Serialize {
+                name: persister.name.clone(),
+                message: error.to_string(),
+            })?;
+            if let Some(value) = value {
+                resources.insert(persister.name.clone(), value);
+            }
+        }
+        Ok(WorldSnapshot {
+            entities,
+            components,
+            resources,
+        })
+    }
+    /// Restore a snapshot into this World, returning how snapshot Entities were mapped.
+    ///
+    /// Restoring into a World without living Entities reproduces the snapshot's Entity IDs and
+    /// generations exactly. Otherwise every snapshot Entity is allocated anew and Entity
+    /// references are rewritten through [`Persistent::map_entities`]. The World is left
+    /// untouched if any value fails to deserialize.
+    ///
+    /// [`Persistent::map_entities`]: trait.Persistent.html#method.map_entities
+    pub fn restore(&mut self, snapshot: WorldSnapshot) -> Result<EntityMap, SnapshotError> {
+        let pending = self.prepare(snapshot.components, snapshot.resources)?;
+
+        let mut map = EntityMap::new();
+        if self.entities.is_empty() {
+            self.entities = snapshot.entities;
+            for entity in self.entities.iter() {
+                map.insert(entity, entity);
+            }
+        } else {
+            for entity in snapshot.entities.iter() {
+                let restored = self.entities.create();
+                map.insert(entity, restored);
+            }
+        }
+        for apply in pending {
+            apply(self, &map);
+        }
+        Ok(map)
+    }
+    /// Restore a snapshot onto the same Entities of this World instead of allocating new ones.
+    ///
+    /// Snapshot Entities which are still alive here get their Components back under the same
+    /// handles, and those deleted since are skipped. References to any living Entity of this World
+    /// are kept, so Entities captured at different times can keep referring to each other. The
+    /// World is left untouched if any value fails to deserialize.
+    pub fn reattach(&mut self, snapshot: WorldSnapshot) -> Result<(), SnapshotError> {
+        let pending = self.prepare(snapshot.components, snapshot.resources)?;
+
+        let mut map = EntityMap::new();
+        for entity in self.entities.iter() {
+            map.insert(entity, entity);
+        }
+        for apply in pending {
+            apply(self, &map);
+        }
+        Ok(())
+    }
+    /// Check that a snapshot would restore into this World without changing anything.
+    pub fn check(&self, snapshot: &WorldSnapshot) -> Result<(), SnapshotError> {
+        self.prepare(snapshot.components.clone(), snapshot.resources.clone()).map(|_| ())
+    }
+    /// Deserialize every value of a snapshot, ready to be applied.
+    fn prepare(&self, components: BTreeMap<String, Entries>, resources: BTreeMap<String, Value>) -> Result<Vec<Apply>, SnapshotError> {
+        let mut pending = Vec::new();
+        for (name, entries) in components {
+            let persister = self
+                .component_persisters
+                .iter()
+                .find(|p| p.name == name)
+                .ok_or_else(|| SnapshotError::UnknownComponent(name.clone()))?;
+            let apply = (persister.load)(entries).map_err(|error| SnapshotError::Deserialize {
+                name: name.clone(),
+                message: error.to_string(),
+            })?;
+            pending.push(apply);
+        }
+        for (name, value) in resources {
+            let persister = self
+                .resource_persisters
+                .iter()
+                .find(|p| p.name == name)
+                .ok_or_else(|| SnapshotError::UnknownResource(name.clone()))?;
+            let apply = (persister.load)(value).map_err(|error| SnapshotError::Deserialize {
+                name: name.clone(),
+                message: error.to_string(),
+            })?;
+            pending.push(apply);
+        }
+        Ok(pending)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate serde_json;
+
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+
+    impl Component for Name {}
+
+    impl Persistent for Name {}
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Target(Entity);
+
+    impl Component for Target {}
+
+    impl Persistent for Target {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.0 = map.map(self.0);
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Sprite(u32);
+
+    impl Component for Sprite {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Turn(u64);
+
+    impl Persistent for Turn {}
+
+    fn registered() -> World {
+        let mut world = World::new();
+        world.register_persistent::<Name>("name");
+        world.register_persistent::<Target>("target");
+        world.register::<Sprite>();
+        world.persist_resource::<Turn>("turn");
+        world
+    }
+
+    fn populated() -> (World, Entity, Entity) {
+        let mut world = registered();
+        let dead = world.create_entity();
+        let hunter = world.create_entity();
+        let prey = world.create_entity();
+        world.delete_entity(dead);
+        world.insert(hunter, Name("orc".to_string()));
+        world.insert(hunter, Target(prey));
+        world.insert(hunter, Sprite(7));
+        world.insert(prey, Name("player".to_string()));
+        world.resources_mut().add(Turn(42));
+        (world, hunter, prey)
+    }
+
+    #[test]
+    fn round_trip_preserves_entities() {
+        let (world, hunter, prey) = populated();
+        let json = serde_json::to_string(&world.snapshot().unwrap()).unwrap();
+
+        let mut restored = registered();
+        let map = restored.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(map.get(hunter), Some(hunter));
+        assert_eq!(restored.get::<Name>(hunter), Some(&Name("orc".to_string())));
+        assert_eq!(restored.get::<Target>(hunter), Some(&Target(prey)));
+        assert_eq!(restored.get::<Sprite>(hunter), None);
+        assert_eq!(*restored.resources().fetch::<Turn>(), Turn(42));
+        assert_eq!(restored.create_entity().generation(), 1);
+    }
+
+    #[test]
+    fn restore_into_populated_world_remaps() {
+        let (world, hunter, prey) = populated();
+        let snapshot = world.snapshot().unwrap();
+
+        let mut restored = registered();
+        let existing = restored.create_entity();
+        let map = restored.restore(snapshot).unwrap();
+        let new_hunter = map.get(hunter).unwrap();
+        let new_prey = map.get(prey).unwrap();
+        assert!(new_hunter != existing && new_prey != existing);
+        assert_eq!(restored.get::<Target>(new_hunter), Some(&Target(new_prey)));
+        assert_eq!(restored.get::<Name>(new_prey), Some(&Name("player".to_string())));
+    }
+
+    #[test]
+    fn partial_snapshot_moves_entities() {
+        let (world, hunter, prey) = populated();
+        let snapshot = world.snapshot_entities(&[hunter]).unwrap().without_resources();
+        assert_eq!(snapshot.entities().len(), 1);
+        assert!(!snapshot.entities().is_alive(prey));
+
+        let mut restored = registered();
+        restored.resources_mut().add(Turn(7));
+        let existing = restored.create_entity();
+        let map = restored.restore(snapshot).unwrap();
+        let moved = map.get(hunter).unwrap();
+        assert_eq!(map.len(), 1);
+        assert!(moved != existing);
+        assert_eq!(restored.get::<Name>(moved), Some(&Name("orc".to_string())));
+        assert!(!restored.is_alive(restored.get::<Target>(moved).unwrap().0));
+        assert_eq!(*restored.resources().fetch::<Turn>(), Turn(7));
+    }
+
+    #[test]
+    fn references_outside_snapshot_are_dead() {
+        let (world, hunter, prey) = populated();
+        let snapshot = world.snapshot_entities(&[hunter]).unwrap();
+
+        let mut restored = registered();
+        let reused: Vec<Entity> = (0..3).map(|_| restored.create_entity()).collect();
+        assert!(reused.contains(&prey));
+        let map = restored.restore(snapshot).unwrap();
+        let target = restored.get::<Target>(map.map(hunter)).unwrap().0;
+        assert_eq!(target, Entity::DEAD);
+        assert!(!restored.is_alive(target));
+        assert_eq!(map.get(prey), None);
+    }
+
+    #[test]
+    fn reattach_keeps_handles() {
+        let (mut world, hunter, prey) = populated();
+        let snapshot = world.snapshot_entities(&[hunter]).unwrap().without_resources();
+        world.strip_entity(hunter);
+        assert!(world.is_alive(hunter));
+        assert_eq!(world.get::<Name>(hunter), None);
+        let other = world.create_entity();
+        world.insert(other, Target(hunter));
+
+        world.reattach(snapshot).unwrap();
+        assert_eq!(world.get::<Name>(hunter), Some(&Name("orc".to_string())));
+        assert_eq!(world.get::<Target>(hunter), Some(&Target(prey)));
+        assert_eq!(world.get::<Target>(other), Some(&Target(hunter)));
+        assert_eq!(world.entities().len(), 3);
+    }
+
+    #[test]
+    fn unknown_component_leaves_world_untouched() {
+        let (world, _, _) = populated();
+        let snapshot = world.snapshot().unwrap();
+
+        let mut restored = World::new();
+        restored.register_persistent::<Name>("name");
+        match restored.restore(snapshot) {
+            Err(SnapshotError::UnknownComponent(ref name)) if name == "target" => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(restored.entities().is_empty());
+    }
+}
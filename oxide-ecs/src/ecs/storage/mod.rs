@@ -19,7 +19,35 @@
 
 pub mod vec;
 
-pub trait Storage<Component> {
+pub use self::vec::VecStorage;
 
+use super::Entity;
+
+/// Change made to a tracked Storage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ComponentEvent {
+    /// A component was attached to an Entity which did not have one.
+    Inserted(Entity),
+    /// A component was replaced or borrowed mutably, and may have changed.
+    Modified(Entity),
+    /// A component was detached, including when its Entity was deleted.
+    Removed(Entity),
 }
 
+/// Component Storage
+pub trait Storage<T> {
+    /// Insert a component for an Entity, returning the previous component if there was one.
+    fn insert(&mut self, entity: Entity, component: T) -> Option<T>;
+    /// Remove the component for an Entity.
+    fn remove(&mut self, entity: Entity) -> Option<T>;
+    /// Get the component for an Entity.
+    fn get(&self, entity: Entity) -> Option<&T>;
+    /// Get the component for an Entity mutably.
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T>;
+    /// Check if an Entity has a component in this Storage.
+    fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+    /// Remove all components.
+    fn clear(&mut self);
+}
@@ -1 +1,184 @@
-pub struct
\ No newline at end of file
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Vector backed Component Storage
+//!
+
+use super::super::Entity;
+use super::{ComponentEvent, Storage};
+
+/// Component Storage indexed directly by Entity ID.
+#[derive(Clone, Debug)]
+pub struct VecStorage<T> {
+    data: Vec<Option<(Entity, T)>>,
+    changes: Option<Vec<ComponentEvent>>,
+}
+
+impl<T> Default for VecStorage<T> {
+    fn default() -> VecStorage<T> {
+        VecStorage {
+            data: Vec::new(),
+            changes: None,
+        }
+    }
+}
+
+impl<T> VecStorage<T> {
+    /// Create a new, empty Storage
+    pub fn new() -> VecStorage<T> {
+        VecStorage::default()
+    }
+    /// Number of stored components
+    pub fn len(&self) -> usize {
+        self.data.iter().filter(|slot| slot.is_some()).count()
+    }
+    /// Check if there are no stored components
+    pub fn is_empty(&self) -> bool {
+        self.data.iter().all(|slot| slot.is_none())
+    }
+    /// Iterate over all Entities and their components
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.data
+            .iter()
+            .filter_map(|slot| slot.as_ref().map(|(entity, value)| (*entity, value)))
+    }
+    /// Iterate mutably over all Entities and their components
+    ///
+    /// Every component visited is recorded as modified when tracking.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        let changes = &mut self.changes;
+        self.data.iter_mut().filter_map(move |slot| {
+            slot.as_mut().map(|(entity, value)| {
+                if let Some(ref mut changes) = *changes {
+                    changes.push(ComponentEvent::Modified(*entity));
+                }
+                (*entity, value)
+            })
+        })
+    }
+    /// Start recording changes. Until drained, every change is kept.
+    pub fn track(&mut self) {
+        if self.changes.is_none() {
+            self.changes = Some(Vec::new());
+        }
+    }
+    /// Check if changes are being recorded
+    pub fn is_tracked(&self) -> bool {
+        self.changes.is_some()
+    }
+    /// Take every change recorded since the last drain, oldest first.
+    pub fn drain_changes(&mut self) -> impl Iterator<Item = ComponentEvent> + '_ {
+        self.changes.iter_mut().flat_map(|changes| changes.drain(..))
+    }
+    fn record(&mut self, event: ComponentEvent) {
+        if let Some(ref mut changes) = self.changes {
+            changes.push(event);
+        }
+    }
+}
+
+impl<T> Storage<T> for VecStorage<T> {
+    fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        let index = entity.id() as usize;
+        if self.data.len() <= index {
+            self.data.resize_with(index + 1, || None);
+        }
+        match self.data[index].replace((entity, component)) {
+            Some((previous, value)) if previous == entity => {
+                self.record(ComponentEvent::Modified(entity));
+                Some(value)
+            }
+            _ => {
+                self.record(ComponentEvent::Inserted(entity));
+                None
+            }
+        }
+    }
+    fn remove(&mut self, entity: Entity) -> Option<T> {
+        let removed = match self.data.get_mut(entity.id() as usize) {
+            Some(slot) if slot.as_ref().map(|(owner, _)| *owner) == Some(entity) => {
+                slot.take().map(|(_, value)| value)
+            }
+            _ => None,
+        };
+        if removed.is_some() {
+            self.record(ComponentEvent::Removed(entity));
+        }
+        removed
+    }
+    fn get(&self, entity: Entity) -> Option<&T> {
+        match self.data.get(entity.id() as usize) {
+            Some(Some((owner, value))) if *owner == entity => Some(value),
+            _ => None,
+        }
+    }
+    fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.data.get_mut(entity.id() as usize) {
+            Some(Some((owner, value))) if *owner == entity => {
+                if let Some(ref mut changes) = self.changes {
+                    changes.push(ComponentEvent::Modified(entity));
+                }
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+    fn clear(&mut self) {
+        if let Some(ref mut changes) = self.changes {
+            changes.extend(self.data.iter().flatten().map(|(entity, _)| ComponentEvent::Removed(*entity)));
+        }
+        self.data.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::EntityManager;
+
+    #[test]
+    fn tracked_changes() {
+        let mut entities = EntityManager::new();
+        let (a, b) = (entities.create(), entities.create());
+        let mut storage = VecStorage::new();
+        storage.insert(a, 1);
+        assert_eq!(storage.drain_changes().count(), 0);
+
+        storage.track();
+        storage.insert(b, 2);
+        storage.insert(b, 3);
+        *storage.get_mut(a).unwrap() += 1;
+        storage.remove(a);
+        storage.remove(a);
+        for (_, value) in storage.iter_mut() {
+            *value += 1;
+        }
+        storage.clear();
+        let changes: Vec<ComponentEvent> = storage.drain_changes().collect();
+        assert_eq!(
+            changes,
+            vec![
+                ComponentEvent::Inserted(b),
+                ComponentEvent::Modified(b),
+                ComponentEvent::Modified(a),
+                ComponentEvent::Removed(a),
+                ComponentEvent::Modified(b),
+                ComponentEvent::Removed(b),
+            ]
+        );
+        assert_eq!(storage.drain_changes().count(), 0);
+    }
+}
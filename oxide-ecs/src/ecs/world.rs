@@ -0,0 +1,187 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Entity Component System World
+//!
+
+use super::serialize::{ComponentPersister, ResourcePersister};
+use super::storage::{Storage, VecStorage};
+use super::{Component, Entity, EntityManager};
+use resource::Resources;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Type erased Component Storage
+pub(crate) trait AnyStorage: Any + Send + Sync {
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> AnyStorage for VecStorage<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Container of Entities, their Components and shared Resources
+#[derive(Default)]
+pub struct World {
+    pub(crate) entities: EntityManager,
+    pub(crate) storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    pub(crate) resources: Resources,
+    pub(crate) component_persisters: Vec<ComponentPersister>,
+    pub(crate) resource_persisters: Vec<ResourcePersister>,
+}
+
+impl World {
+    /// Create a new, empty World
+    pub fn new() -> World {
+        World::default()
+    }
+    /// Register a Component type, creating its Storage.
+    pub fn register<T: Component>(&mut self) {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(VecStorage::<T>::new()));
+    }
+    /// Record changes to a Component type, registering it if needed.
+    ///
+    /// Drain them with [`VecStorage::drain_changes`] to keep derived data up to date.
+    ///
+    /// [`VecStorage::drain_changes`]: storage/vec/struct.VecStorage.html#method.drain_changes
+    pub fn track<T: Component>(&mut self) {
+        self.storage_mut::<T>().track();
+    }
+    /// Create a new Entity
+    pub fn create_entity(&mut self) -> Entity {
+        self.entities.create()
+    }
+    /// Delete an Entity and all of its Components, returning false if it was not alive.
+    pub fn delete_entity(&mut self, entity: Entity) -> bool {
+        if self.entities.delete(entity) {
+            for storage in self.storages.values_mut() {
+                storage.remove_entity(entity);
+            }
+            true
+        } else {
+            false
+        }
+    }
+    /// Detach every Component from an Entity, keeping it alive.
+    pub fn strip_entity(&mut self, entity: Entity) {
+        if self.entities.is_alive(entity) {
+            for storage in self.storages.values_mut() {
+                storage.remove_entity(entity);
+            }
+        }
+    }
+    /// Delete every Entity, keeping registered Components and Resources.
+    pub fn clear_entities(&mut self) {
+        let entities: Vec<Entity> = self.entities.iter().collect();
+        for entity in entities {
+            self.delete_entity(entity);
+        }
+    }
+    /// Check if an Entity is alive.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+    /// Entity Allocator
+    pub fn entities(&self) -> &EntityManager {
+        &self.entities
+    }
+    /// Attach a Component to a living Entity, returning the Component it replaced.
+    ///
+    /// Components attached to dead Entities are dropped.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        if self.entities.is_alive(entity) {
+            self.storage_mut::<T>().insert(entity, component)
+        } else {
+            None
+        }
+    }
+    /// Detach a Component from an Entity.
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_internal_mut::<T>().and_then(|storage| storage.remove(entity))
+    }
+    /// Get an Entity's Component.
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>().and_then(|storage| storage.get(entity))
+    }
+    /// Get an Entity's Component mutably.
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_internal_mut::<T>().and_then(|storage| storage.get_mut(entity))
+    }
+    /// Get the Storage of a Component type if it has been registered.
+    pub fn storage<T: Component>(&self) -> Option<&VecStorage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any().downcast_ref::<VecStorage<T>>())
+    }
+    /// Get the Storage of a Component type mutably, registering it if needed.
+    pub fn storage_mut<T: Component>(&mut self) -> &mut VecStorage<T> {
+        self.register::<T>();
+        self.storage_internal_mut::<T>().expect("Component storage was just registered")
+    }
+    /// Shared Resources
+    pub fn resources(&self) -> &Resources {
+        &self.resources
+    }
+    /// Shared Resources, mutably
+    pub fn resources_mut(&mut self) -> &mut Resources {
+        &mut self.resources
+    }
+    fn storage_internal_mut<T: Component>(&mut self) -> Option<&mut VecStorage<T>> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|storage| storage.as_any_mut().downcast_mut::<VecStorage<T>>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Health(i32);
+
+    impl Component for Health {}
+
+    #[test]
+    fn delete_removes_components() {
+        let mut world = World::new();
+        let entity = world.create_entity();
+        assert_eq!(world.insert(entity, Health(10)), None);
+        world.get_mut::<Health>(entity).unwrap().0 -= 3;
+        assert_eq!(world.get::<Health>(entity), Some(&Health(7)));
+
+        assert!(world.delete_entity(entity));
+        assert_eq!(world.get::<Health>(entity), None);
+
+        let reused = world.create_entity();
+        assert_eq!(reused.id(), entity.id());
+        assert_eq!(world.get::<Health>(reused), None);
+        assert_eq!(world.insert(entity, Health(1)), None);
+        assert_eq!(world.storage::<Health>().unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,29 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Oxide Entity Component System
+//!
+
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_value;
+
+pub mod ecs;
+pub mod resource;
+
+pub use ecs::{Component, ComponentEvent, Entity, EntityID, EntityManager, EntityMap, Persistent, SnapshotError, System, World, WorldSnapshot};
+pub use resource::{Fetch, FetchMut, Resource, ResourceId, Resources, Runnable};
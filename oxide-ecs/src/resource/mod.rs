@@ -17,14 +17,15 @@
 //! Resource System
 //!
 
+#[allow(clippy::module_inception)]
 mod resource;
 mod resources;
 
-pub use Resource;
-
+pub use self::resource::{Resource, ResourceId};
+pub use self::resources::{Fetch, FetchMut, Resources};
 
 /// Trait for Runnable Systems
 pub trait Runnable<'a> {
-    ///
-    fn run(&mut self, resources:&'a Resources);
-}
\ No newline at end of file
+    /// Run against the shared Resources
+    fn run(&mut self, resources: &'a Resources);
+}
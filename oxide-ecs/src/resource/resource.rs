@@ -17,7 +17,7 @@
 //! Resource System
 //!
 
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ResourceId(pub TypeId);
@@ -29,5 +29,7 @@ impl ResourceId {
     }
 }
 
+/// Any value shared between Systems
 pub trait Resource: Any + Send + Sync {}
 
+impl<T: Any + Send + Sync> Resource for T {}
@@ -14,15 +14,22 @@
 // limitations under the License.
 //
 //!
+//! Resource Container
 //!
-//!
+
+use super::{Resource, ResourceId};
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+const RESOURCE_NOT_FOUND: &str = "No resource with the given id";
 
 /// Return value of [`Resources::fetch`].
 ///
 /// [`Resources::fetch`]: struct.Resources.html#method.fetch
 pub struct Fetch<'a, T: 'a> {
-    inner: Ref<'a, Box<Resource>>,
-    phantom: PhantomData<&'a T>,
+    inner: Ref<'a, T>,
 }
 
 impl<'a, T> Deref for Fetch<'a, T>
@@ -32,7 +39,7 @@ impl<'a, T> Deref for Fetch<'a, T>
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { self.inner.downcast_ref_unchecked() }
+        &self.inner
     }
 }
 
@@ -40,8 +47,7 @@ impl<'a, T> Deref for Fetch<'a, T>
 ///
 /// [`Resources::fetch_mut`]: struct.Resources.html#method.fetch_mut
 pub struct FetchMut<'a, T: 'a> {
-    inner: RefMut<'a, Box<Resource>>,
-    phantom: PhantomData<&'a mut T>,
+    inner: RefMut<'a, T>,
 }
 
 impl<'a, T> Deref for FetchMut<'a, T>
@@ -51,7 +57,7 @@ impl<'a, T> Deref for FetchMut<'a, T>
     type Target = T;
 
     fn deref(&self) -> &T {
-        unsafe { self.inner.downcast_ref_unchecked() }
+        &self.inner
     }
 }
 
@@ -60,14 +66,14 @@ impl<'a, T> DerefMut for FetchMut<'a, T>
         T: Resource,
 {
     fn deref_mut(&mut self) -> &mut T {
-        unsafe { self.inner.downcast_mut_unchecked() }
+        &mut self.inner
     }
 }
 
-
-///
+/// Container of shared Resources, keyed by type.
+#[derive(Default)]
 pub struct Resources {
-    resources: FxHashMap<ResourceId, TrustCell<Box<Resource>>>,
+    resources: HashMap<ResourceId, RefCell<Box<dyn Any + Send + Sync>>>,
 }
 
 impl Resources {
@@ -76,6 +82,7 @@ impl Resources {
         Default::default()
     }
 
+    /// Add a resource, panicking if one of the same type already exists.
     pub fn add<R>(&mut self, r: R)
         where
             R: Resource,
@@ -85,82 +92,112 @@ impl Resources {
         let entry = self.resources.entry(ResourceId::new::<R>());
 
         if let Entry::Vacant(e) = entry {
-            e.insert(TrustCell::new(Box::new(r)));
+            e.insert(RefCell::new(Box::new(r)));
         } else {
             panic!("Tried to add a resource though \
                     an instance of this type already exists in `Resources`");
         }
     }
 
-
-    pub fn exists(&self, id: ResourceId) -> bool {
-        self.resources.contains_key(&id)
+    /// Insert a resource, returning the previous instance if there was one.
+    pub fn insert<R>(&mut self, r: R) -> Option<R>
+        where
+            R: Resource,
+    {
+        self.resources
+            .insert(ResourceId::new::<R>(), RefCell::new(Box::new(r)))
+            .and_then(|previous| previous.into_inner().downcast::<R>().ok())
+            .map(|previous| *previous)
     }
 
-
-    pub fn entry<R>(&mut self) -> Entry<R>
+    /// Remove a resource, returning it if it existed.
+    pub fn remove<R>(&mut self) -> Option<R>
         where
             R: Resource,
     {
-        create_entry(self.resources.entry(ResourceId::new::<R>()))
+        self.resources
+            .remove(&ResourceId::new::<R>())
+            .and_then(|previous| previous.into_inner().downcast::<R>().ok())
+            .map(|previous| *previous)
+    }
+
+    /// Check if a resource with the given id exists.
+    pub fn exists(&self, id: ResourceId) -> bool {
+        self.resources.contains_key(&id)
     }
 
     /// Like try_fetch except panics if unable to
-    pub fn fetch<T>(&self) -> Fetch<T>
+    pub fn fetch<T>(&self) -> Fetch<'_, T>
         where
             T: Resource,
     {
         self.try_fetch().expect(RESOURCE_NOT_FOUND)
     }
 
-    pub fn fetch_mut<T>(&self) -> FetchMut<T>
+    /// Like try_fetch_mut except panics if unable to
+    pub fn fetch_mut<T>(&self) -> FetchMut<'_, T>
         where
             T: Resource,
     {
         self.try_fetch_mut().expect(RESOURCE_NOT_FOUND)
     }
 
-    pub fn try_fetch<T>(&self) -> Option<Fetch<T>>
+    /// Fetch a resource immutably, returning `None` if it does not exist.
+    pub fn try_fetch<T>(&self) -> Option<Fetch<'_, T>>
         where
             T: Resource,
     {
-        self.try_fetch_internal(TypeId::of::<T>()).map(|r| {
+        self.try_fetch_internal(ResourceId::new::<T>()).map(|r| {
             Fetch {
-                inner: r.borrow(),
-                phantom: PhantomData,
+                inner: Ref::map(r.borrow(), |r| r.downcast_ref::<T>().expect(RESOURCE_NOT_FOUND)),
             }
         })
     }
 
     /// Like `fetch_mut`, but returns an `Option` instead of panicking in the case of the resource
-    /// being accessed mutably.
-    pub fn try_fetch_mut<T>(&self) -> Option<FetchMut<T>>
+    /// not existing.
+    pub fn try_fetch_mut<T>(&self) -> Option<FetchMut<'_, T>>
         where
             T: Resource,
     {
-        self.try_fetch_internal(TypeId::of::<T>()).map(|r| {
+        self.try_fetch_internal(ResourceId::new::<T>()).map(|r| {
             FetchMut {
-                inner: r.borrow_mut(),
-                phantom: PhantomData,
+                inner: RefMut::map(r.borrow_mut(), |r| r.downcast_mut::<T>().expect(RESOURCE_NOT_FOUND)),
             }
         })
     }
 
-    /// Like `fetch_mut`, but returns an `Option` instead of panicking in the case of the resource
-    /// being accessed mutably.
-    pub fn try_fetch_mut_by_id<T>(&self) -> Option<FetchMut<T>>
+    /// Get a resource mutably through exclusive access, without runtime borrow tracking.
+    pub fn get_mut<T>(&mut self) -> Option<&mut T>
         where
             T: Resource,
     {
-        self.try_fetch_internal(TypeId::of::<T>()).map(|r| {
-            FetchMut {
-                inner: r.borrow_mut(),
-                phantom: PhantomData,
-            }
-        })
+        self.resources
+            .get_mut(&ResourceId::new::<T>())
+            .and_then(|r| r.get_mut().downcast_mut::<T>())
+    }
+
+    fn try_fetch_internal(&self, id: ResourceId) -> Option<&RefCell<Box<dyn Any + Send + Sync>>> {
+        self.resources.get(&id)
     }
+}
 
-    fn try_fetch_internal(&self, id: TypeId) -> Option<&TrustCell<Box<Resource>>> {
-        self.resources.get(&ResourceId(id))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Turn(u64);
+
+    #[test]
+    fn fetch_and_replace() {
+        let mut resources = Resources::new();
+        resources.add(Turn(1));
+        assert!(resources.exists(ResourceId::new::<Turn>()));
+        resources.fetch_mut::<Turn>().0 += 1;
+        assert_eq!(*resources.fetch::<Turn>(), Turn(2));
+        assert_eq!(resources.insert(Turn(7)), Some(Turn(2)));
+        assert_eq!(resources.remove::<Turn>(), Some(Turn(7)));
+        assert!(resources.try_fetch::<Turn>().is_none());
     }
-}
\ No newline at end of file
+}
@@ -20,11 +20,14 @@
 use super::data::Data;
 use super::event::Event;
 use super::engine::Engine;
+use super::save::AutosavePolicy;
 use super::state::{State, StateManager};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 use std::time::Instant;
 
 ///
-///
+/// Application
 ///
 pub struct Application<'a, D: Data> {
     states: StateManager<'a, D>,
@@ -37,6 +40,14 @@ impl<'a, D: Data> Application<'a, D> {
         let engine = Engine::new(data);
         Application { states, engine }
     }
+    /// Autosave at the safe points selected by the policy. The game is captured on the game
+    /// thread, then serialized and written to the Engine's save slots in the background.
+    pub fn set_autosave(&mut self, policy: AutosavePolicy)
+        where
+            D: Clone + Serialize + DeserializeOwned,
+    {
+        self.engine.set_autosave(policy);
+    }
     pub fn run(&mut self) {
         let mut last_update = Instant::now();
         let mut accumulator = 0.0;
@@ -55,11 +66,13 @@ impl<'a, D: Data> Application<'a, D> {
                 // Handle Events Here
                 self.states.handle(&mut self.engine, Event::Empty);
                 self.states.update(&mut self.engine, delta);
+                self.engine.maintain();
             }
 
             self.states.render(&mut self.engine);
             accumulator -= frame_time;
         }
+        self.engine.flush_autosaves();
     }
 }
 
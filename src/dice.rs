@@ -0,0 +1,850 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Dice Notation
+//!
+//! Dice expressions are sums of dice groups and constants such as `3d6+2` or `d%-1`. A dice
+//! group may be followed by modifiers:
+//!
+//! * `x` or `!` explodes the dice: every die showing its highest face is rolled again and added.
+//! * `khN`/`keep highest N` and `klN`/`keep lowest N` keep only some of the dice.
+//! * `dhN`/`drop highest N` and `dlN`/`drop lowest N` discard some of the dice.
+//! * `adv`/`advantage` and `dis`/`disadvantage` roll twice as many dice and keep the highest or
+//!   lowest half, so `1d20 advantage` is `2d20kh1`.
+//!
+//! `N` defaults to 1 when omitted, so `4d6 drop lowest` is `4d6kh3`.
+//!
+
+use rand::Rng;
+use serde::de::{Deserialize, Deserializer, Error as DeError};
+use serde::ser::{Serialize, Serializer};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::iter::Peekable;
+use std::str::{CharIndices, FromStr};
+
+const MAX_DICE: u32 = 1000;
+const MAX_SIDES: u32 = 1_000_000;
+const MAX_EXPLOSIONS: usize = 100;
+const MAX_MEAN_WORK: u64 = 1 << 22;
+
+/// Which dice of a group count towards its total
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Keep {
+    All,
+    Highest(u32),
+    Lowest(u32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Term {
+    Roll {
+        negative: bool,
+        count: u32,
+        sides: u32,
+        explode: bool,
+        keep: Keep,
+    },
+    Constant(i64),
+}
+
+/// Dice Expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dice {
+    terms: Vec<Term>,
+}
+
+impl Dice {
+    /// Expression rolling `count` dice with `sides` faces.
+    ///
+    /// # Panics
+    /// If `count` or `sides` is zero or exceeds the supported limits.
+    pub fn new(count: u32, sides: u32) -> Dice {
+        assert!(count > 0 && count <= MAX_DICE, "Dice count out of range");
+        assert!(sides > 0 && sides <= MAX_SIDES, "Dice sides out of range");
+        Dice {
+            terms: vec![Term::Roll {
+                negative: false,
+                count,
+                sides,
+                explode: false,
+                keep: Keep::All,
+            }],
+        }
+    }
+    /// Expression which always totals `value`.
+    ///
+    /// # Panics
+    /// If `value` lies outside `±u32::MAX`, the range the notation can express.
+    pub fn constant(value: i64) -> Dice {
+        assert!(value.unsigned_abs() <= u64::from(u32::MAX), "Constant out of range");
+        Dice {
+            terms: vec![Term::Constant(value)],
+        }
+    }
+    /// Expression adding a constant to this one.
+    ///
+    /// # Panics
+    /// If `constant` lies outside `±u32::MAX`, the range the notation can express.
+    pub fn plus(mut self, constant: i64) -> Dice {
+        assert!(constant.unsigned_abs() <= u64::from(u32::MAX), "Constant out of range");
+        self.terms.push(Term::Constant(constant));
+        self
+    }
+    /// Smallest possible total
+    pub fn min(&self) -> i64 {
+        self.terms.iter().fold(0, |min, term| min.saturating_add(term.bounds().0))
+    }
+    /// Largest possible total, or `None` if exploding dice make it unbounded.
+    pub fn max(&self) -> Option<i64> {
+        self.terms
+            .iter()
+            .try_fold(0i64, |max, term| term.bounds().1.map(|high| max.saturating_add(high)))
+    }
+    /// Expected total
+    pub fn mean(&self) -> f64 {
+        self.terms.iter().map(Term::mean).sum()
+    }
+    /// Roll the expression, keeping every die for display.
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> DiceRoll {
+        let parts: Vec<RollPart> = self.terms.iter().map(|term| term.roll(rng)).collect();
+        let total = parts.iter().map(RollPart::total).sum();
+        DiceRoll { total, parts }
+    }
+    /// Roll the expression, returning only the total.
+    pub fn roll_total<R: Rng>(&self, rng: &mut R) -> i64 {
+        self.roll(rng).total
+    }
+}
+
+impl Term {
+    fn kept(count: u32, keep: Keep) -> u32 {
+        match keep {
+            Keep::All => count,
+            Keep::Highest(kept) | Keep::Lowest(kept) => kept,
+        }
+    }
+    fn bounds(&self) -> (i64, Option<i64>) {
+        match *self {
+            Term::Constant(value) => (value, Some(value)),
+            Term::Roll { negative, count, sides, explode, keep } => {
+                let kept = i64::from(Term::kept(count, keep));
+                let low = kept;
+                let high = if explode { None } else { Some(kept * i64::from(sides)) };
+                if negative {
+                    (-high.unwrap_or(i64::MAX), Some(-low))
+                } else {
+                    (low, high)
+                }
+            }
+        }
+    }
+    fn mean(&self) -> f64 {
+        match *self {
+            Term::Constant(value) => value as f64,
+            Term::Roll { negative, count, sides, explode, keep } => {
+                let mean = match keep {
+                    Keep::All => f64::from(count) * die_mean(sides, explode),
+                    Keep::Highest(kept) => highest_mean(count, kept, sides, explode),
+                    Keep::Lowest(kept) => {
+                        f64::from(count) * die_mean(sides, explode) - highest_mean(count, count - kept, sides, explode)
+                    }
+                };
+                if negative {
+                    -mean
+                } else {
+                    mean
+                }
+            }
+        }
+    }
+    fn roll<R: Rng>(&self, rng: &mut R) -> RollPart {
+        match *self {
+            Term::Constant(value) => RollPart::Constant(value),
+            Term::Roll { negative, count, sides, explode, keep } => {
+                let mut dice: Vec<DieRoll> = (0..count)
+                    .map(|_| {
+                        let mut faces = Vec::new();
+                        loop {
+                            let face = rng.gen_range(1, sides + 1);
+                            faces.push(face);
+                            if !explode || face != sides || faces.len() >= MAX_EXPLOSIONS {
+                                break;
+                            }
+                        }
+                        DieRoll { faces, kept: true }
+                    })
+                    .collect();
+                let mut order: Vec<usize> = (0..dice.len()).collect();
+                order.sort_by_key(|&index| dice[index].value());
+                let discarded: &[usize] = match keep {
+                    Keep::All => &[],
+                    Keep::Highest(kept) => &order[..(count - kept) as usize],
+                    Keep::Lowest(kept) => &order[kept as usize..],
+                };
+                for &index in discarded {
+                    dice[index].kept = false;
+                }
+                RollPart::Dice {
+                    notation: Term::Roll {
+                        negative: false,
+                        count,
+                        sides,
+                        explode,
+                        keep,
+                    }
+                    .to_string(),
+                    negative,
+                    dice,
+                }
+            }
+        }
+    }
+}
+
+/// Probability that a single die shows at least `value`.
+fn die_at_least(value: u64, sides: u32, explode: bool) -> f64 {
+    let sides = u64::from(sides);
+    if value <= 1 {
+        1.0
+    } else if !explode {
+        if value > sides {
+            0.0
+        } else {
+            (sides - value + 1) as f64 / sides as f64
+        }
+    } else {
+        // Reaching `value` takes `q` maximum faces followed by at least `r + 1`.
+        let q = (value - 1) / sides;
+        let r = (value - 1) % sides;
+        (1.0 / sides as f64).powi(q as i32) * (sides - r) as f64 / sides as f64
+    }
+}
+
+fn die_mean(sides: u32, explode: bool) -> f64 {
+    let mean = (f64::from(sides) + 1.0) / 2.0;
+    if explode {
+        mean * f64::from(sides) / (f64::from(sides) - 1.0)
+    } else {
+        mean
+    }
+}
+
+/// Expected sum of the highest `kept` of `count` dice, from `E[sum] = Σv E[min(kept, B(count, P(X ≥ v)))]`.
+///
+/// Faces are summed exactly while that takes fewer than `MAX_MEAN_WORK` binomial terms; larger
+/// dice are estimated by evaluating the middle face of evenly sized bands, which keeps the work
+/// bounded for any valid expression.
+fn highest_mean(count: u32, kept: u32, sides: u32, explode: bool) -> f64 {
+    let n = count as usize;
+    let k = kept as usize;
+    let mut log_factorial = vec![0.0f64; n + 1];
+    for i in 1..=n {
+        log_factorial[i] = log_factorial[i - 1] + (i as f64).ln();
+    }
+    let pmf = |j: usize, p: f64| {
+        (log_factorial[n] - log_factorial[j] - log_factorial[n - j] + j as f64 * p.ln() + (n - j) as f64 * (1.0 - p).ln()).exp()
+    };
+    // Chains of maximum faces long enough to fall below the cut-off, plus the final face.
+    let faces = if explode {
+        let chains = (-1e-15f64.ln() / f64::from(sides).ln()).ceil() as u64;
+        u64::from(sides) * (chains + 1)
+    } else {
+        u64::from(sides)
+    };
+    let terms = k.min(n - k) as u64 + 1;
+    let band = (faces * terms / MAX_MEAN_WORK).max(1);
+    let mut total = 0.0;
+    let mut value = 1u64;
+    while value <= faces {
+        let width = band.min(faces + 1 - value);
+        let p = die_at_least(value + width / 2, sides, explode);
+        if p < 1e-15 {
+            break;
+        }
+        // E[min(k, B)] summed from whichever tail of the binomial is shorter.
+        let expected = if p >= 1.0 {
+            f64::from(kept)
+        } else if k <= n - k {
+            k as f64 - (0..k).map(|j| (k - j) as f64 * pmf(j, p)).sum::<f64>()
+        } else {
+            n as f64 * p - (k + 1..=n).map(|j| (j - k) as f64 * pmf(j, p)).sum::<f64>()
+        };
+        total += expected * width as f64;
+        value += width;
+    }
+    total
+}
+
+impl Display for Keep {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Keep::All => Ok(()),
+            Keep::Highest(kept) => write!(f, "kh{}", kept),
+            Keep::Lowest(kept) => write!(f, "kl{}", kept),
+        }
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Term::Constant(value) => write!(f, "{}", value),
+            Term::Roll { count, sides, explode, keep, .. } => {
+                write!(f, "{}d{}{}{}", count, sides, if explode { "x" } else { "" }, keep)
+            }
+        }
+    }
+}
+
+impl Display for Dice {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for (index, term) in self.terms.iter().enumerate() {
+            let negative = match *term {
+                Term::Constant(value) => value < 0,
+                Term::Roll { negative, .. } => negative,
+            };
+            match (index, negative) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => (),
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            match *term {
+                Term::Constant(value) => write!(f, "{}", value.unsigned_abs())?,
+                _ => write!(f, "{}", term)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Single die of a roll, including any explosions
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DieRoll {
+    faces: Vec<u32>,
+    kept: bool,
+}
+
+impl DieRoll {
+    /// Value of the die, summing every explosion
+    pub fn value(&self) -> i64 {
+        self.faces.iter().map(|face| i64::from(*face)).sum()
+    }
+    /// Faces rolled, more than one if the die exploded
+    pub fn faces(&self) -> &[u32] {
+        &self.faces
+    }
+    /// Check if the die counts towards the total
+    pub fn kept(&self) -> bool {
+        self.kept
+    }
+}
+
+impl Display for DieRoll {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if !self.kept {
+            write!(f, "(")?;
+        }
+        for (index, face) in self.faces.iter().enumerate() {
+            if index > 0 {
+                write!(f, "!+")?;
+            }
+            write!(f, "{}", face)?;
+        }
+        if !self.kept {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+/// Part of a roll, one per term of the expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RollPart {
+    /// Dice group with its notation and every die rolled
+    Dice { notation: String, negative: bool, dice: Vec<DieRoll> },
+    /// Constant modifier
+    Constant(i64),
+}
+
+impl RollPart {
+    /// Signed contribution of this part to the total
+    pub fn total(&self) -> i64 {
+        match *self {
+            RollPart::Constant(value) => value,
+            RollPart::Dice { negative, ref dice, .. } => {
+                let sum: i64 = dice.iter().filter(|die| die.kept).map(DieRoll::value).sum();
+                if negative {
+                    -sum
+                } else {
+                    sum
+                }
+            }
+        }
+    }
+}
+
+/// Result of rolling a Dice expression
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiceRoll {
+    total: i64,
+    parts: Vec<RollPart>,
+}
+
+impl DiceRoll {
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+    /// Breakdown of the roll, one part per term
+    pub fn parts(&self) -> &[RollPart] {
+        &self.parts
+    }
+}
+
+/// Formats the roll for a message log, e.g. `4d6kh3 [6, 5, 3, (1)] + 2 = 16`.
+impl Display for DiceRoll {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for (index, part) in self.parts.iter().enumerate() {
+            let negative = part.total() < 0
+                || match *part {
+                    RollPart::Dice { negative, .. } => negative,
+                    RollPart::Constant(_) => false,
+                };
+            match (index, negative) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => (),
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            match *part {
+                RollPart::Constant(value) => write!(f, "{}", value.unsigned_abs())?,
+                RollPart::Dice { ref notation, ref dice, .. } => {
+                    write!(f, "{} [", notation)?;
+                    for (index, die) in dice.iter().enumerate() {
+                        if index > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{}", die)?;
+                    }
+                    write!(f, "]")?;
+                }
+            }
+        }
+        write!(f, " = {}", self.total)
+    }
+}
+
+/// Kinds of Dice parsing errors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiceErrorKind {
+    /// The expression is empty or ends where a term or number was expected.
+    UnexpectedEnd,
+    /// A character which cannot appear at this position.
+    UnexpectedCharacter(char),
+    /// A modifier word which is not recognised.
+    UnknownModifier(String),
+    /// A number is too large.
+    NumberTooLarge,
+    /// A dice group has zero dice or more than 1000.
+    InvalidCount(u32),
+    /// A die has zero faces or more than 1000000.
+    InvalidSides(u32),
+    /// A keep or drop modifier selects more dice than are rolled.
+    InvalidKeep(u32),
+    /// Single sided dice cannot explode.
+    InfiniteExplosion,
+}
+
+/// Dice parsing error, with the character position at which it occurred
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiceError {
+    position: usize,
+    kind: DiceErrorKind,
+}
+
+impl DiceError {
+    /// Character offset into the expression
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    pub fn kind(&self) -> &DiceErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for DiceError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "At position {}: ", self.position)?;
+        match self.kind {
+            DiceErrorKind::UnexpectedEnd => write!(f, "unexpected end of expression"),
+            DiceErrorKind::UnexpectedCharacter(c) => write!(f, "unexpected character '{}'", c),
+            DiceErrorKind::UnknownModifier(ref word) => write!(f, "unknown modifier '{}'", word),
+            DiceErrorKind::NumberTooLarge => write!(f, "number too large"),
+            DiceErrorKind::InvalidCount(count) => write!(f, "cannot roll {} dice", count),
+            DiceErrorKind::InvalidSides(sides) => write!(f, "dice cannot have {} sides", sides),
+            DiceErrorKind::InvalidKeep(kept) => write!(f, "cannot keep or drop {} dice", kept),
+            DiceErrorKind::InfiniteExplosion => write!(f, "single sided dice cannot explode"),
+        }
+    }
+}
+
+impl Error for DiceError {}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Parser<'a> {
+        Parser {
+            chars: source.char_indices().peekable(),
+            position: 0,
+        }
+    }
+    fn error<T>(&self, position: usize, kind: DiceErrorKind) -> Result<T, DiceError> {
+        Err(DiceError { position, kind })
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.next();
+        }
+    }
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| {
+            self.position += 1;
+            c
+        })
+    }
+    fn number(&mut self) -> Result<Option<u32>, DiceError> {
+        let start = self.position;
+        let mut value: Option<u32> = None;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            self.next();
+            value = match value.unwrap_or(0).checked_mul(10).and_then(|v| v.checked_add(digit)) {
+                Some(value) => Some(value),
+                None => return self.error(start, DiceErrorKind::NumberTooLarge),
+            };
+        }
+        Ok(value)
+    }
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            self.next();
+            word.push(c.to_ascii_lowercase());
+        }
+        word
+    }
+    fn expression(&mut self) -> Result<Dice, DiceError> {
+        let mut terms = Vec::new();
+        let mut negative = false;
+        self.skip_whitespace();
+        if self.peek() == Some('-') {
+            self.next();
+            negative = true;
+        }
+        loop {
+            terms.push(self.term(negative)?);
+            self.skip_whitespace();
+            negative = match self.peek() {
+                Some('+') => false,
+                Some('-') => true,
+                Some(c) => return self.error(self.position, DiceErrorKind::UnexpectedCharacter(c)),
+                None => return Ok(Dice { terms }),
+            };
+            self.next();
+        }
+    }
+    fn term(&mut self, negative: bool) -> Result<Term, DiceError> {
+        self.skip_whitespace();
+        let start = self.position;
+        let count = self.number()?;
+        match self.peek() {
+            Some('d') | Some('D') => {
+                self.next();
+                self.roll(start, negative, count.unwrap_or(1))
+            }
+            _ => match count {
+                Some(value) => Ok(Term::Constant(if negative { -i64::from(value) } else { i64::from(value) })),
+                None => match self.peek() {
+                    Some(c) => self.error(self.position, DiceErrorKind::UnexpectedCharacter(c)),
+                    None => self.error(self.position, DiceErrorKind::UnexpectedEnd),
+                },
+            },
+        }
+    }
+    fn roll(&mut self, start: usize, negative: bool, mut count: u32) -> Result<Term, DiceError> {
+        if count == 0 || count > MAX_DICE {
+            return self.error(start, DiceErrorKind::InvalidCount(count));
+        }
+        let sides_position = self.position;
+        let sides = if self.peek() == Some('%') {
+            self.next();
+            100
+        } else {
+            match self.number()? {
+                Some(sides) => sides,
+                None => match self.peek() {
+                    Some(c) => return self.error(self.position, DiceErrorKind::UnexpectedCharacter(c)),
+                    None => return self.error(self.position, DiceErrorKind::UnexpectedEnd),
+                },
+            }
+        };
+        if sides == 0 || sides > MAX_SIDES {
+            return self.error(sides_position, DiceErrorKind::InvalidSides(sides));
+        }
+        let mut explode = false;
+        let mut keep = Keep::All;
+        loop {
+            self.skip_whitespace();
+            let position = self.position;
+            // Explosion is a single character, so it can be followed directly by another modifier.
+            if let Some('!') | Some('x') | Some('X') = self.peek() {
+                self.next();
+                explode = true;
+                continue;
+            }
+            let word = self.word();
+            if word.is_empty() {
+                break;
+            }
+            let word = match word.as_str() {
+                "drop" | "keep" => {
+                    self.skip_whitespace();
+                    format!("{} {}", word, self.word())
+                }
+                _ => word,
+            };
+            match word.as_str() {
+                "adv" | "advantage" | "dis" | "disadvantage" => {
+                    let kept = count;
+                    count = match count.checked_mul(2).filter(|doubled| *doubled <= MAX_DICE) {
+                        Some(doubled) => doubled,
+                        None => return self.error(position, DiceErrorKind::InvalidCount(count.saturating_mul(2))),
+                    };
+                    keep = if word.starts_with("adv") { Keep::Highest(kept) } else { Keep::Lowest(kept) };
+                }
+                "k" | "kh" | "kl" | "dh" | "dl" | "keep highest" | "keep lowest" | "drop highest" | "drop lowest" => {
+                    self.skip_whitespace();
+                    let amount = self.number()?.unwrap_or(1);
+                    let dropping = word.starts_with('d');
+                    if (dropping && amount >= count) || (!dropping && (amount == 0 || amount > count)) {
+                        return self.error(position, DiceErrorKind::InvalidKeep(amount));
+                    }
+                    keep = match word.as_str() {
+                        "k" | "kh" | "keep highest" => Keep::Highest(amount),
+                        "kl" | "keep lowest" => Keep::Lowest(amount),
+                        "dh" | "drop highest" => Keep::Lowest(count - amount),
+                        _ => Keep::Highest(count - amount),
+                    };
+                }
+                _ => return self.error(position, DiceErrorKind::UnknownModifier(word)),
+            }
+        }
+        if explode && sides == 1 {
+            return self.error(sides_position, DiceErrorKind::InfiniteExplosion);
+        }
+        Ok(Term::Roll {
+            negative,
+            count,
+            sides,
+            explode,
+            keep,
+        })
+    }
+}
+
+impl FromStr for Dice {
+    type Err = DiceError;
+
+    fn from_str(source: &str) -> Result<Dice, DiceError> {
+        Parser::new(source).expression()
+    }
+}
+
+impl Serialize for Dice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Dice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Dice, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        source.parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::GameRng;
+    use rand::SeedableRng;
+    use serde_json;
+    use std::time::{Duration, Instant};
+
+    fn dice(source: &str) -> Dice {
+        source.parse().unwrap()
+    }
+
+    fn error(source: &str) -> (usize, DiceErrorKind) {
+        let error = source.parse::<Dice>().unwrap_err();
+        (error.position(), error.kind().clone())
+    }
+
+    #[test]
+    fn parse_notation() {
+        assert_eq!(dice("3d6+2").to_string(), "3d6 + 2");
+        assert_eq!(dice(" d% - 1 ").to_string(), "1d100 - 1");
+        assert_eq!(dice("1d20 advantage").to_string(), "2d20kh1");
+        assert_eq!(dice("1d20 dis").to_string(), "2d20kl1");
+        assert_eq!(dice("4d6 drop lowest").to_string(), "4d6kh3");
+        assert_eq!(dice("4D6DH2").to_string(), "4d6kl2");
+        assert_eq!(dice("2d10x").to_string(), "2d10x");
+        assert_eq!(dice("2d10!+1d4-3").to_string(), "2d10x + 1d4 - 3");
+        assert_eq!(dice("-1d4+7").to_string(), "-1d4 + 7");
+        assert_eq!(Dice::new(2, 8).plus(-1), dice("2d8-1"));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for &explode in &[false, true] {
+            for &keep in &[Keep::All, Keep::Highest(1), Keep::Highest(3), Keep::Lowest(1), Keep::Lowest(2)] {
+                for &negative in &[false, true] {
+                    let roll = Term::Roll {
+                        negative,
+                        count: 3,
+                        sides: 10,
+                        explode,
+                        keep,
+                    };
+                    for terms in [vec![roll.clone()], vec![Term::Constant(-4), roll.clone(), Term::Constant(2)]] {
+                        let expression = Dice { terms };
+                        let text = expression.to_string();
+                        assert_eq!(text.parse::<Dice>(), Ok(expression.clone()), "{}", text);
+                        let json = serde_json::to_string(&expression).unwrap();
+                        assert_eq!(serde_json::from_str::<Dice>(&json).unwrap(), expression, "{}", json);
+                    }
+                }
+            }
+        }
+        assert_eq!(dice("2d10xkh1"), dice("2d10! keep highest"));
+        assert_eq!(dice("2d10XKL1"), dice("2d10x kl1"));
+        let largest = Dice::constant(i64::from(u32::MAX)).plus(-i64::from(u32::MAX));
+        assert_eq!(largest.to_string().parse::<Dice>(), Ok(largest));
+    }
+
+    #[test]
+    #[should_panic(expected = "Constant out of range")]
+    fn constants_are_limited_to_the_notation() {
+        Dice::constant(5_000_000_000);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error(""), (0, DiceErrorKind::UnexpectedEnd));
+        assert_eq!(error("3d"), (2, DiceErrorKind::UnexpectedEnd));
+        assert_eq!(error("3d6 +"), (5, DiceErrorKind::UnexpectedEnd));
+        assert_eq!(error("3d6 * 2"), (4, DiceErrorKind::UnexpectedCharacter('*')));
+        assert_eq!(error("0d6"), (0, DiceErrorKind::InvalidCount(0)));
+        assert_eq!(error("2+3d0"), (4, DiceErrorKind::InvalidSides(0)));
+        assert_eq!(error("4d6 kh5"), (4, DiceErrorKind::InvalidKeep(5)));
+        assert_eq!(error("4d6 drop lowest 4"), (4, DiceErrorKind::InvalidKeep(4)));
+        assert_eq!(error("1d6 twice"), (4, DiceErrorKind::UnknownModifier("twice".to_string())));
+        assert_eq!(error("3d1x"), (2, DiceErrorKind::InfiniteExplosion));
+        assert_eq!(error("99999999999d6"), (0, DiceErrorKind::NumberTooLarge));
+    }
+
+    #[test]
+    fn statistics() {
+        let simple = dice("3d6+2");
+        assert_eq!((simple.min(), simple.max()), (5, Some(20)));
+        assert!((simple.mean() - 12.5).abs() < 1e-9);
+
+        let best = dice("4d6 drop lowest");
+        assert_eq!((best.min(), best.max()), (3, Some(18)));
+        assert!((best.mean() - 15869.0 / 1296.0).abs() < 1e-9);
+
+        let advantage = dice("1d20 adv");
+        assert!((advantage.mean() - 13.825).abs() < 1e-9);
+        assert!((dice("1d20 dis").mean() - 7.175).abs() < 1e-9);
+
+        let exploding = dice("2d10x");
+        assert_eq!((exploding.min(), exploding.max()), (2, None));
+        assert!((exploding.mean() - 2.0 * 5.5 * 10.0 / 9.0).abs() < 1e-9);
+        assert!((dice("2d10x kh1").mean() - 2.0 * 5.5 * 10.0 / 9.0 + dice("2d10x kl1").mean()).abs() < 1e-9);
+
+        let huge = Dice {
+            terms: vec![Term::Constant(i64::MAX), Term::Constant(1)],
+        };
+        assert_eq!(huge.max(), Some(i64::MAX));
+
+        let penalty = dice("10-1d4");
+        assert_eq!((penalty.min(), penalty.max()), (6, Some(9)));
+        assert!((penalty.mean() - 7.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn large_keeps_are_prompt() {
+        let start = Instant::now();
+        let highest = dice("1000d1000000kh1").mean();
+        assert!((highest - 999_001.5).abs() < 1.0, "{}", highest);
+        let lowest = dice("1000d1000000kl1").mean();
+        assert!((lowest - 999.5).abs() < 1.0, "{}", lowest);
+        let exploding = dice("1000d1000000x kh500").mean();
+        assert!(exploding > 3.7e8 && exploding < 3.8e8, "{}", exploding);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn rolls_within_bounds() {
+        let mut rng = GameRng::from_seed(17);
+        let expression = dice("4d6kh3 + 2d4x - 1");
+        let mut total = 0;
+        for _ in 0..2000 {
+            let roll = expression.roll(&mut rng);
+            assert!(roll.total() >= expression.min());
+            let parts: i64 = roll.parts().iter().map(RollPart::total).sum();
+            assert_eq!(parts, roll.total());
+            match roll.parts()[0] {
+                RollPart::Dice { ref dice, .. } => assert_eq!(dice.iter().filter(|die| die.kept()).count(), 3),
+                _ => panic!("Expected dice"),
+            }
+            total += roll.total();
+        }
+        let mean = total as f64 / 2000.0;
+        assert!((mean - expression.mean()).abs() < 0.5, "{} vs {}", mean, expression.mean());
+    }
+
+    #[test]
+    fn breakdown_and_serde() {
+        let mut rng = GameRng::from_seed(3);
+        let roll = dice("4d6dl1+2").roll(&mut rng);
+        let text = roll.to_string();
+        assert!(text.starts_with("4d6kh3 ["), "{}", text);
+        assert!(text.contains('(') && text.ends_with(&format!(" + 2 = {}", roll.total())), "{}", text);
+
+        let json = serde_json::to_string(&dice("1d20 advantage + 5")).unwrap();
+        assert_eq!(json, "\"2d20kh1 + 5\"");
+        assert_eq!(serde_json::from_str::<Dice>(&json).unwrap(), dice("2d20kh1+5"));
+        assert!(serde_json::from_str::<Dice>("\"2d\"").is_err());
+    }
+}
@@ -15,12 +15,33 @@
 //
 
 use super::data::Data;
+use super::level::{LevelError, LevelId, LevelManager};
+use super::map::{Point, SpatialIndex};
+use super::random::{GameRng, Random};
+use super::save::{self, AutosavePolicy, AutosaveTrigger, Autosaver, Capture, Job, Migration, Migrations, SaveError, SaveHeader, SaveSlots};
 use super::Version;
+use oxide_ecs::{Entity, World};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Read, Write};
+
+/// Captures a save on behalf of an autosave
+type Snapshot<D> = fn(&Engine<D>) -> Result<Job, SaveError>;
 
 /// Engine API Layer
 pub struct Engine<D: Data> {
     frame_time: f64,
     data: D,
+    random: Random,
+    world: World,
+    levels: LevelManager,
+    game_version: Version,
+    migrations: Migrations,
+    save_slots: SaveSlots,
+    turn: u64,
+    autosaver: Option<Autosaver>,
+    autosave_snapshot: Option<Snapshot<D>>,
+    autosave_errors: Vec<SaveError>,
 }
 
 impl<D: Data> Engine<D> {
@@ -29,6 +50,16 @@ impl<D: Data> Engine<D> {
         Engine {
             frame_time,
             data,
+            random: Random::from_entropy(),
+            world: World::new(),
+            levels: LevelManager::new(),
+            game_version: Version::new(0, 0, 0),
+            migrations: Migrations::default(),
+            save_slots: SaveSlots::default(),
+            turn: 0,
+            autosaver: None,
+            autosave_snapshot: None,
+            autosave_errors: Vec::new(),
         }
     }
     pub fn lib_version() -> Version {
@@ -37,6 +68,124 @@ impl<D: Data> Engine<D> {
     pub fn data(&mut self) -> &mut D {
         &mut self.data
     }
+    /// Random Number Service
+    pub fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+    /// Named random stream, shorthand for `random().stream(name)`.
+    pub fn rng(&mut self, stream: &str) -> &mut GameRng {
+        self.random.stream(stream)
+    }
+    /// Entities, Components and Resources
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+    /// Entities, Components and Resources, mutably
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+    /// Frozen levels and the Links between them
+    pub fn levels(&self) -> &LevelManager {
+        &self.levels
+    }
+    pub fn levels_mut(&mut self) -> &mut LevelManager {
+        &mut self.levels
+    }
+    /// Move the travellers to another level, generating it on the first visit, and return the
+    /// living travellers in order. Their handles do not change.
+    ///
+    /// The current level is frozen with every Entity which is not travelling, and a failed change
+    /// leaves the game as it was. Travellers with a Position are placed at `arrival`, or on the
+    /// new level's Link back to the old one. Push a [`LevelChange`] instead to show a loading
+    /// screen while this runs.
+    ///
+    /// [`LevelChange`]: struct.LevelChange.html
+    pub fn change_level(&mut self, level: &LevelId, travellers: &[Entity], arrival: Option<Point>) -> Result<Vec<Entity>, LevelError> {
+        let turn = self.turn;
+        let arrived = self.levels.travel(&mut self.world, &self.random, turn, level, travellers, arrival)?;
+        self.maintain();
+        self.level_changed();
+        Ok(arrived)
+    }
+    /// Follow the Link leaving the current level at a point.
+    pub fn take_link(&mut self, at: Point, travellers: &[Entity]) -> Result<Vec<Entity>, LevelError> {
+        let link = self
+            .levels
+            .current()
+            .and_then(|current| self.levels.link_at(current, at))
+            .cloned()
+            .ok_or(LevelError::NoLink(at))?;
+        self.change_level(&link.to, travellers, link.arrival)
+    }
+    /// Version of the game, recorded in and checked against save files.
+    pub fn game_version(&self) -> &Version {
+        &self.game_version
+    }
+    pub fn set_game_version(&mut self, version: Version) {
+        self.game_version = version
+    }
+    /// Save Slots used by `save_slot` and `load_slot`.
+    pub fn save_slots(&self) -> &SaveSlots {
+        &self.save_slots
+    }
+    pub fn set_save_slots(&mut self, save_slots: SaveSlots) {
+        self.save_slots = save_slots
+    }
+    /// Number of turns ended so far.
+    pub fn turn(&self) -> u64 {
+        self.turn
+    }
+    /// End the current turn, autosaving if the policy asks for it.
+    pub fn end_turn(&mut self) {
+        self.turn += 1;
+        let turn = self.turn;
+        self.autosave(AutosaveTrigger::Turn(turn));
+    }
+    /// Notify the Engine that the player moved to another level, autosaving if the policy asks
+    /// for it.
+    pub fn level_changed(&mut self) {
+        self.autosave(AutosaveTrigger::LevelChange);
+    }
+    /// Bring derived Resources, such as the SpatialIndex, up to date with Component changes.
+    ///
+    /// Called after every Application update and after loading a save.
+    pub fn maintain(&mut self) {
+        SpatialIndex::sync(&mut self.world);
+    }
+    /// Block until every queued autosave has been written.
+    pub fn flush_autosaves(&mut self) {
+        if let Some(ref autosaver) = self.autosaver {
+            autosaver.flush();
+        }
+    }
+    /// Errors from autosaves taken since the last call.
+    pub fn autosave_errors(&mut self) -> Vec<SaveError> {
+        let mut errors: Vec<SaveError> = self.autosave_errors.drain(..).collect();
+        if let Some(ref autosaver) = self.autosaver {
+            errors.extend(autosaver.errors());
+        }
+        errors
+    }
+    /// Capture the game on the calling thread and queue it for serializing and writing if the
+    /// autosave policy asks for it.
+    pub(crate) fn autosave(&mut self, trigger: AutosaveTrigger) {
+        let snapshot = match (&self.autosaver, self.autosave_snapshot) {
+            (Some(autosaver), Some(snapshot)) if autosaver.policy().wants(trigger) => snapshot,
+            _ => return,
+        };
+        match snapshot(self) {
+            Ok(job) => {
+                if let Some(ref autosaver) = self.autosaver {
+                    autosaver.submit(self.save_slots.clone(), job);
+                }
+            }
+            Err(error) => self.autosave_errors.push(error),
+        }
+    }
+    /// Register a Migration bringing saves from older game versions up to `version`.
+    pub fn add_migration(&mut self, version: Version, migration: Migration) {
+        self.migrations.add(version, migration)
+    }
     pub fn set_fps(&mut self, fps: f64) {
         self.frame_time = 1.0 / fps
     }
@@ -45,3 +194,70 @@ impl<D: Data> Engine<D> {
         self.frame_time
     }
 }
+
+impl<D: Data + Serialize + DeserializeOwned> Engine<D> {
+    /// Write the game Data, the Random service, a World snapshot, the frozen levels and the turn
+    /// count as a save.
+    pub fn save<W: Write>(&self, writer: W) -> Result<SaveHeader, SaveError> {
+        save::write(writer, &self.data, &self.random, &self.world, &self.levels, self.turn, &self.game_version)
+    }
+    /// Load a save, migrating it if it was written by an older game version.
+    ///
+    /// The save is fully read and verified, and its World snapshot and frozen levels checked
+    /// against the registered Components and Resources, before any state is replaced. A save
+    /// which fails to load leaves the game untouched.
+    pub fn load<R: Read>(&mut self, reader: R) -> Result<SaveHeader, SaveError> {
+        let (header, payload) = save::read(reader, &self.game_version, &self.migrations)?;
+        self.world.check(&payload.world)?;
+        for snapshot in payload.levels.snapshots() {
+            self.world.check(snapshot)?;
+        }
+        self.world.clear_entities();
+        self.world.restore(payload.world)?;
+        self.levels.restore(payload.levels);
+        self.random = payload.random;
+        self.data = payload.data;
+        self.turn = payload.turn;
+        self.maintain();
+        Ok(header)
+    }
+    /// Autosave into the policy's slot whenever one of its triggers occurs.
+    ///
+    /// The game Data is cloned on the game thread and serialized by the autosave worker.
+    pub fn set_autosave(&mut self, policy: AutosavePolicy)
+        where
+            D: Clone,
+    {
+        self.flush_autosaves();
+        self.autosaver = Some(Autosaver::new(policy));
+        self.autosave_snapshot = Some(capture::<D>);
+    }
+    /// Atomically write a save into a named slot.
+    pub fn save_slot(&self, slot: &str) -> Result<SaveHeader, SaveError> {
+        let mut buffer = Vec::new();
+        let header = self.save(&mut buffer)?;
+        self.save_slots.write(slot, &buffer)?;
+        Ok(header)
+    }
+    /// Load the save in a named slot, deleting the slot afterwards if permadeath is enabled.
+    pub fn load_slot(&mut self, slot: &str) -> Result<SaveHeader, SaveError> {
+        let buffer = self.save_slots.read(slot)?;
+        let header = self.load(&buffer[..])?;
+        if self.save_slots.permadeath() {
+            self.save_slots.delete(slot)?;
+        }
+        Ok(header)
+    }
+}
+
+fn capture<D: Data + Clone + Serialize>(engine: &Engine<D>) -> Result<Job, SaveError> {
+    let capture = Capture::new(
+        engine.data.clone(),
+        engine.random.clone(),
+        &engine.world,
+        engine.levels.clone(),
+        engine.turn,
+        engine.game_version.clone(),
+    )?;
+    Ok(Box::new(move || capture.into_bytes()))
+}
@@ -0,0 +1,45 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Hex Field of View
+//!
+
+use super::{Transparency, View};
+use hex::{Hex, HexLayout};
+use map::Point;
+
+pub(super) fn compute<M: Transparency>(view: &mut View<M>, layout: HexLayout) {
+    let origin = layout.to_hex(view.origin);
+    let points: Vec<Point> = view.visible.points().collect();
+    for point in points {
+        let target = layout.to_hex(point);
+        if target.distance(origin) > view.range {
+            continue;
+        }
+        if is_clear(view, layout, origin, target, 1.0) || is_clear(view, layout, origin, target, -1.0) {
+            view.reveal(point);
+        }
+    }
+}
+
+/// Check if every hex strictly between the ends of a line is transparent.
+fn is_clear<M: Transparency>(view: &View<M>, layout: HexLayout, from: Hex, to: Hex, side: f64) -> bool {
+    let between = (from.distance(to) - 1).max(0) as usize;
+    from.nudged_line(to, side)
+        .skip(1)
+        .take(between)
+        .all(|hex| !view.is_opaque(layout.to_point(hex)))
+}
@@ -0,0 +1,211 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Remembered Map and Sightings
+//!
+
+use super::Visibility;
+use map::{Grid, Point};
+use oxide_ecs::{Component, Entity, EntityMap, Persistent};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+
+/// Appearance of a tile when it was last seen
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Remembered<T> {
+    /// What the tile looked like, typically its terrain and topmost item glyph
+    pub appearance: T,
+    /// Turn the tile was last seen on
+    pub turn: u64,
+}
+
+/// Per viewer memory of every tile it has seen
+///
+/// Update it from the viewer's [`Visibility`] each turn and draw remembered tiles that are not
+/// currently visible dimmed. Store it as a Component of the viewer or as a Resource for a single
+/// player; both persist with the save game.
+///
+/// [`Visibility`]: struct.Visibility.html
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapMemory<T> {
+    tiles: Grid<Option<Remembered<T>>>,
+}
+
+impl<T> MapMemory<T> {
+    /// Create a memory of a map where nothing has been seen yet.
+    pub fn new(width: i32, height: i32) -> MapMemory<T> {
+        MapMemory {
+            tiles: Grid::from_fn(width, height, |_| None),
+        }
+    }
+    /// Remember the current appearance of every visible tile.
+    ///
+    /// Visible points outside of the memory are ignored.
+    pub fn update<F: FnMut(Point) -> T>(&mut self, visibility: &Visibility, turn: u64, mut appearance: F) {
+        for point in visibility.points() {
+            if let Some(tile) = self.tiles.get_mut(point) {
+                *tile = Some(Remembered {
+                    appearance: appearance(point),
+                    turn,
+                });
+            }
+        }
+    }
+    pub fn get(&self, point: Point) -> Option<&Remembered<T>> {
+        self.tiles.get(point).and_then(Option::as_ref)
+    }
+    /// Check if a tile has ever been seen.
+    pub fn is_explored(&self, point: Point) -> bool {
+        self.get(point).is_some()
+    }
+    /// Every remembered tile, row by row.
+    pub fn explored(&self) -> impl Iterator<Item = (Point, &Remembered<T>)> {
+        self.tiles.iter().filter_map(|(point, tile)| tile.as_ref().map(|tile| (point, tile)))
+    }
+    /// Forget a tile, returning what was remembered.
+    pub fn forget(&mut self, point: Point) -> Option<Remembered<T>> {
+        self.tiles.set(point, None).and_then(|tile| tile)
+    }
+    /// Forget every tile, as when reading a scroll of amnesia.
+    pub fn forget_all(&mut self) {
+        for (_, tile) in self.tiles.iter_mut() {
+            *tile = None;
+        }
+    }
+    pub fn tiles(&self) -> &Grid<Option<Remembered<T>>> {
+        &self.tiles
+    }
+}
+
+impl<T: Clone + Debug + Send + Sync + 'static> Component for MapMemory<T> {}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> Persistent for MapMemory<T> {}
+
+/// Last known position of another Entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Sighting {
+    pub entity: Entity,
+    pub point: Point,
+    pub turn: u64,
+}
+
+/// Per viewer memory of where other Entities were last seen
+///
+/// Monsters use this to hunt a player who stepped out of view by heading to the last sighting.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sightings {
+    seen: Vec<Sighting>,
+}
+
+impl Sightings {
+    pub fn new() -> Sightings {
+        Sightings::default()
+    }
+    /// Record the position of every target currently visible.
+    pub fn update<I: IntoIterator<Item = (Entity, Point)>>(&mut self, visibility: &Visibility, turn: u64, targets: I) {
+        for (entity, point) in targets {
+            if visibility.is_visible(point) {
+                self.record(entity, point, turn);
+            }
+        }
+    }
+    /// Record that `entity` was seen at `point`.
+    pub fn record(&mut self, entity: Entity, point: Point, turn: u64) {
+        let sighting = Sighting { entity, point, turn };
+        match self.seen.iter_mut().find(|seen| seen.entity == entity) {
+            Some(seen) => *seen = sighting,
+            None => self.seen.push(sighting),
+        }
+    }
+    pub fn last_seen(&self, entity: Entity) -> Option<&Sighting> {
+        self.seen.iter().find(|seen| seen.entity == entity)
+    }
+    pub fn forget(&mut self, entity: Entity) -> Option<Sighting> {
+        let index = self.seen.iter().position(|seen| seen.entity == entity)?;
+        Some(self.seen.remove(index))
+    }
+    /// Forget every sighting made before `turn`.
+    pub fn forget_before(&mut self, turn: u64) {
+        self.seen.retain(|seen| seen.turn >= turn);
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Sighting> {
+        self.seen.iter()
+    }
+}
+
+impl Component for Sightings {}
+
+impl Persistent for Sightings {
+    fn map_entities(&mut self, map: &EntityMap) {
+        self.seen.retain_mut(|seen| match map.get(seen.entity) {
+            Some(entity) => {
+                seen.entity = entity;
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Algorithm, Radius};
+    use super::*;
+    use oxide_ecs::World;
+    use serde_json;
+
+    #[test]
+    fn remembers_last_seen_appearance() {
+        let mut map = Grid::new(9, 1, '.');
+        let mut visibility = Visibility::new();
+        let mut memory = MapMemory::new(9, 1);
+        visibility.compute(&map.map(|_| true), Point::new(0, 0), Radius::Square(4), Algorithm::Symmetric);
+        memory.update(&visibility, 1, |point| map[point]);
+        map[Point::new(2, 0)] = '$';
+        visibility.compute(&map.map(|_| true), Point::new(8, 0), Radius::Square(4), Algorithm::Symmetric);
+        memory.update(&visibility, 2, |point| map[point]);
+
+        assert_eq!(memory.explored().count(), 9);
+        assert_eq!(memory.get(Point::new(2, 0)), Some(&Remembered { appearance: '.', turn: 1 }));
+        assert_eq!(memory.get(Point::new(4, 0)).map(|tile| tile.turn), Some(2));
+        memory.forget_all();
+        assert!(!memory.is_explored(Point::new(4, 0)));
+    }
+
+    #[test]
+    fn sightings_follow_restored_entities() {
+        let mut world = World::new();
+        world.register_persistent::<Sightings>("sightings");
+        let monster = world.create_entity();
+        let player = world.create_entity();
+        let mut visibility = Visibility::new();
+        visibility.compute(&Grid::new(5, 5, true), Point::new(0, 0), Radius::Unlimited, Algorithm::Permissive);
+        let mut sightings = Sightings::new();
+        sightings.update(&visibility, 3, vec![(player, Point::new(4, 4)), (monster, Point::new(9, 9))]);
+        assert!(sightings.last_seen(monster).is_none());
+        world.insert(monster, sightings);
+        let json = serde_json::to_string(&world.snapshot().unwrap()).unwrap();
+
+        let mut restored = World::new();
+        restored.register_persistent::<Sightings>("sightings");
+        restored.create_entity();
+        let map = restored.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        let sightings = restored.get::<Sightings>(map.map(monster)).unwrap();
+        let seen = sightings.last_seen(map.map(player)).unwrap();
+        assert_eq!((seen.point, seen.turn), (Point::new(4, 4), 3));
+    }
+}
@@ -0,0 +1,336 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Field of View
+//!
+
+mod hex;
+mod memory;
+mod permissive;
+mod shadowcast;
+mod symmetric;
+
+pub use self::memory::{MapMemory, Remembered, Sighting, Sightings};
+
+use hex::HexLayout;
+use map::{Grid, Point};
+
+/// Map that light can pass through
+///
+/// Points outside of `width` x `height` are always treated as opaque.
+pub trait Transparency {
+    fn width(&self) -> i32;
+    fn height(&self) -> i32;
+    /// Returns true if light passes through the tile at `point`.
+    fn is_transparent(&self, point: Point) -> bool;
+}
+
+/// Cells set to `true` are transparent.
+impl Transparency for Grid<bool> {
+    fn width(&self) -> i32 {
+        Grid::width(self)
+    }
+    fn height(&self) -> i32 {
+        Grid::height(self)
+    }
+    fn is_transparent(&self, point: Point) -> bool {
+        self.get(point).cloned().unwrap_or(false)
+    }
+}
+
+/// Maximum view distance and the shape it covers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Radius {
+    /// Limited only by the edges of the map
+    Unlimited,
+    /// Euclidean distance, rounded up slightly so small circles do not look pinched
+    Circle(i32),
+    /// Chebyshev distance
+    Square(i32),
+    /// Manhattan distance
+    Diamond(i32),
+}
+
+impl Radius {
+    /// Maximum distance along either axis, or `None` if unlimited.
+    pub fn range(self) -> Option<i32> {
+        match self {
+            Radius::Unlimited => None,
+            Radius::Circle(r) | Radius::Square(r) | Radius::Diamond(r) => Some(r.max(0)),
+        }
+    }
+    /// Returns true if a point at `offset` from the viewer is within range.
+    pub fn contains(self, offset: Point) -> bool {
+        match self {
+            Radius::Unlimited => true,
+            Radius::Circle(r) => {
+                let r = i64::from(r);
+                offset.distance_squared(Point::ZERO) <= r * r + r
+            }
+            Radius::Square(r) => offset.chebyshev(Point::ZERO) <= r,
+            Radius::Diamond(r) => offset.manhattan(Point::ZERO) <= r,
+        }
+    }
+}
+
+/// Field of view algorithm
+///
+/// All three light walls and other opaque tiles that bound the visible area.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Algorithm {
+    /// Recursive shadowcasting. Fast, but not symmetric.
+    Shadowcasting,
+    /// Precise permissive field of view. A tile is visible if any unobstructed line connects any
+    /// part of the viewer's tile to any part of it. Symmetric and generous around pillars.
+    Permissive,
+    /// Symmetric shadowcasting. Floor tiles are visible only from tiles that can see them back.
+    Symmetric,
+}
+
+/// Reusable buffer of visible tiles
+///
+/// The buffer is only reallocated when the size of the map changes, so keeping one per viewer
+/// avoids allocating every turn.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Visibility {
+    origin: Point,
+    visible: Grid<bool>,
+}
+
+impl Visibility {
+    pub fn new() -> Visibility {
+        Visibility {
+            origin: Point::ZERO,
+            visible: Grid::new(0, 0, false),
+        }
+    }
+    /// Replace the contents of the buffer with what can be seen from `origin`.
+    ///
+    /// Nothing is visible from a point outside of the map.
+    pub fn compute<M: Transparency>(&mut self, map: &M, origin: Point, radius: Radius, algorithm: Algorithm) {
+        if !self.reset(map, origin) {
+            return;
+        }
+        let range = radius.range().unwrap_or_else(|| map.width().max(map.height()));
+        let mut view = View {
+            map,
+            origin,
+            radius,
+            range,
+            visible: &mut self.visible,
+        };
+        view.reveal(origin);
+        match algorithm {
+            Algorithm::Shadowcasting => shadowcast::compute(&mut view),
+            Algorithm::Permissive => permissive::compute(&mut view),
+            Algorithm::Symmetric => symmetric::compute(&mut view),
+        }
+    }
+    /// Replace the contents of the buffer with what can be seen from `origin` on a hex map.
+    ///
+    /// A hex is visible if a straight line reaches it without passing through an opaque hex,
+    /// breaking ties either way, so sight is symmetric. Bounded radii count hex steps whatever
+    /// their shape.
+    pub fn compute_hex<M: Transparency>(&mut self, map: &M, origin: Point, radius: Radius, layout: HexLayout) {
+        if !self.reset(map, origin) {
+            return;
+        }
+        let range = radius.range().unwrap_or_else(|| map.width() + map.height());
+        let mut view = View {
+            map,
+            origin,
+            radius: Radius::Unlimited,
+            range,
+            visible: &mut self.visible,
+        };
+        view.reveal(origin);
+        hex::compute(&mut view, layout);
+    }
+    /// Point the buffer was last computed from
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+    pub fn is_visible(&self, point: Point) -> bool {
+        self.visible.get(point).cloned().unwrap_or(false)
+    }
+    /// Every visible point, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> + '_ {
+        self.visible.iter().filter(|&(_, visible)| *visible).map(|(point, _)| point)
+    }
+    pub fn grid(&self) -> &Grid<bool> {
+        &self.visible
+    }
+    /// Clear the buffer for a map, returning false if nothing can be seen from `origin`.
+    fn reset<M: Transparency>(&mut self, map: &M, origin: Point) -> bool {
+        if self.visible.width() == map.width() && self.visible.height() == map.height() {
+            self.visible.fill(false);
+        } else {
+            self.visible = Grid::new(map.width(), map.height(), false);
+        }
+        self.origin = origin;
+        self.visible.in_bounds(origin)
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Visibility {
+        Visibility::new()
+    }
+}
+
+/// State shared by the algorithms during a single computation
+struct View<'a, M: 'a> {
+    map: &'a M,
+    origin: Point,
+    radius: Radius,
+    range: i32,
+    visible: &'a mut Grid<bool>,
+}
+
+impl<'a, M: Transparency> View<'a, M> {
+    fn is_opaque(&self, point: Point) -> bool {
+        !(self.visible.in_bounds(point) && self.map.is_transparent(point))
+    }
+    fn reveal(&mut self, point: Point) {
+        if self.radius.contains(point - self.origin) {
+            self.visible.set(point, true);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::GameRng;
+    use rand::{Rng, SeedableRng};
+
+    const ALGORITHMS: [Algorithm; 3] = [Algorithm::Shadowcasting, Algorithm::Permissive, Algorithm::Symmetric];
+
+    fn parse(rows: &[&str]) -> Grid<bool> {
+        let cells = rows.iter().flat_map(|row| row.chars().map(|c| c != '#')).collect();
+        Grid::from_vec(rows[0].len() as i32, rows.len() as i32, cells).unwrap()
+    }
+
+    #[test]
+    fn open_map_sees_whole_shape() {
+        let map = Grid::new(21, 21, true);
+        let mut visibility = Visibility::new();
+        for &algorithm in ALGORITHMS.iter() {
+            for &radius in [Radius::Circle(6), Radius::Square(6), Radius::Diamond(6), Radius::Unlimited].iter() {
+                visibility.compute(&map, Point::new(10, 10), radius, algorithm);
+                for point in map.points() {
+                    let expected = radius.contains(point - Point::new(10, 10));
+                    assert_eq!(visibility.is_visible(point), expected, "{:?} {:?} at {}", algorithm, radius, point);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn large_radii_do_not_overflow() {
+        let map = Grid::new(21, 21, true);
+        let mut visibility = Visibility::new();
+        for &algorithm in ALGORITHMS.iter() {
+            for &radius in [Radius::Circle(50_000), Radius::Circle(i32::MAX)].iter() {
+                visibility.compute(&map, Point::new(10, 10), radius, algorithm);
+                assert_eq!(visibility.points().count(), 21 * 21, "{:?} {:?}", algorithm, radius);
+            }
+        }
+        assert!(Radius::Circle(50_000).contains(Point::new(50_000, 0)));
+        assert!(!Radius::Circle(50_000).contains(Point::new(40_000, 40_000)));
+        assert!(Radius::Circle(i32::MAX).contains(Point::new(i32::MAX, 0)));
+        assert_eq!(::geometry::circle(Point::ZERO, 50_000).next(), Some(Point::new(-223, -50_000)));
+    }
+
+    #[test]
+    fn walls_cast_shadows() {
+        let map = parse(&[
+            "#########",
+            "#.......#",
+            "#...#...#",
+            "#.......#",
+            "#########",
+        ]);
+        let mut visibility = Visibility::new();
+        for &algorithm in ALGORITHMS.iter() {
+            visibility.compute(&map, Point::new(2, 2), Radius::Unlimited, algorithm);
+            assert!(visibility.is_visible(Point::new(4, 2)), "{:?}", algorithm);
+            assert!(visibility.is_visible(Point::new(0, 0)), "{:?}", algorithm);
+            assert!(visibility.is_visible(Point::new(7, 0)), "{:?}", algorithm);
+            assert!(!visibility.is_visible(Point::new(6, 2)), "{:?}", algorithm);
+            assert!(!visibility.is_visible(Point::new(7, 2)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn symmetric_algorithms_are_symmetric() {
+        let mut rng = GameRng::from_seed(7);
+        let map = Grid::from_fn(24, 24, |_| rng.gen_range(0, 4) != 0);
+        let floors: Vec<Point> = map.iter().filter(|&(_, open)| *open).map(|(point, _)| point).collect();
+        for &algorithm in [Algorithm::Permissive, Algorithm::Symmetric].iter() {
+            let views: Vec<Visibility> = floors
+                .iter()
+                .map(|&point| {
+                    let mut visibility = Visibility::new();
+                    visibility.compute(&map, point, Radius::Unlimited, algorithm);
+                    visibility
+                })
+                .collect();
+            for (a, view_a) in floors.iter().zip(views.iter()) {
+                for (b, view_b) in floors.iter().zip(views.iter()) {
+                    assert_eq!(view_a.is_visible(*b), view_b.is_visible(*a), "{:?} {} {}", algorithm, a, b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn origin_outside_map_sees_nothing() {
+        let map = Grid::new(4, 4, true);
+        let mut visibility = Visibility::new();
+        visibility.compute(&map, Point::new(-1, 2), Radius::Unlimited, Algorithm::Symmetric);
+        assert_eq!(visibility.points().count(), 0);
+    }
+
+    #[test]
+    fn hex_sight_is_symmetric() {
+        let open = Grid::new(15, 15, true);
+        let mut visibility = Visibility::new();
+        visibility.compute_hex(&open, Point::new(7, 7), Radius::Circle(4), HexLayout::OddRows);
+        assert_eq!(visibility.points().count(), 61);
+
+        let mut rng = GameRng::from_seed(3);
+        let map = Grid::from_fn(14, 14, |_| rng.gen_range(0, 4) != 0);
+        let floors: Vec<Point> = map.iter().filter(|&(_, open)| *open).map(|(point, _)| point).collect();
+        let views: Vec<Visibility> = floors
+            .iter()
+            .map(|&point| {
+                let mut visibility = Visibility::new();
+                visibility.compute_hex(&map, point, Radius::Unlimited, HexLayout::EvenColumns);
+                visibility
+            })
+            .collect();
+        for (a, view_a) in floors.iter().zip(views.iter()) {
+            for (b, view_b) in floors.iter().zip(views.iter()) {
+                assert_eq!(view_a.is_visible(*b), view_b.is_visible(*a), "{} {}", a, b);
+            }
+        }
+        let wall = parse(&["......", "..#...", "......"]);
+        visibility.compute_hex(&wall, Point::new(0, 1), Radius::Unlimited, HexLayout::OddRows);
+        assert!(visibility.is_visible(Point::new(2, 1)));
+        assert!(!visibility.is_visible(Point::new(4, 1)));
+    }
+}
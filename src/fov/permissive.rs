@@ -0,0 +1,203 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Precise Permissive Field of View
+//!
+//! Each quadrant is scanned diagonal by diagonal while tracking the set of views, wedges bounded
+//! by a shallow and a steep line, that can still reach further tiles. Opaque tiles either narrow a
+//! view by adding a bump to one of its lines, split it in two, or close it entirely.
+//!
+
+use super::{Transparency, View};
+use map::Point;
+
+/// Line between two lattice points in quadrant-local coordinates
+#[derive(Clone, Copy)]
+struct Line {
+    xi: i32,
+    yi: i32,
+    xf: i32,
+    yf: i32,
+}
+
+impl Line {
+    fn relative_slope(&self, x: i32, y: i32) -> i32 {
+        (self.yf - self.yi) * (self.xf - x) - (self.xf - self.xi) * (self.yf - y)
+    }
+    fn is_below(&self, x: i32, y: i32) -> bool {
+        self.relative_slope(x, y) > 0
+    }
+    fn is_below_or_collinear(&self, x: i32, y: i32) -> bool {
+        self.relative_slope(x, y) >= 0
+    }
+    fn is_above(&self, x: i32, y: i32) -> bool {
+        self.relative_slope(x, y) < 0
+    }
+    fn is_above_or_collinear(&self, x: i32, y: i32) -> bool {
+        self.relative_slope(x, y) <= 0
+    }
+    fn is_collinear(&self, x: i32, y: i32) -> bool {
+        self.relative_slope(x, y) == 0
+    }
+    fn is_line_collinear(&self, other: &Line) -> bool {
+        self.is_collinear(other.xi, other.yi) && self.is_collinear(other.xf, other.yf)
+    }
+}
+
+/// Corner of an opaque tile that a view line was bent around
+struct Bump {
+    x: i32,
+    y: i32,
+    parent: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+struct Wedge {
+    shallow: Line,
+    steep: Line,
+    shallow_bump: Option<usize>,
+    steep_bump: Option<usize>,
+}
+
+/// Views of one quadrant, with their bumps kept in an arena so views can share them
+struct Quadrant {
+    dx: i32,
+    dy: i32,
+    wedges: Vec<Wedge>,
+    bumps: Vec<Bump>,
+}
+
+pub(super) fn compute<M: Transparency>(view: &mut View<M>) {
+    let min_x = view.origin.x.min(view.range);
+    let max_x = (view.visible.width() - view.origin.x - 1).min(view.range);
+    let min_y = view.origin.y.min(view.range);
+    let max_y = (view.visible.height() - view.origin.y - 1).min(view.range);
+    check_quadrant(view, 1, 1, max_x, max_y);
+    check_quadrant(view, 1, -1, max_x, min_y);
+    check_quadrant(view, -1, -1, min_x, min_y);
+    check_quadrant(view, -1, 1, min_x, max_y);
+}
+
+fn check_quadrant<M: Transparency>(view: &mut View<M>, dx: i32, dy: i32, extent_x: i32, extent_y: i32) {
+    let mut quadrant = Quadrant {
+        dx,
+        dy,
+        wedges: vec![Wedge {
+            shallow: Line { xi: 0, yi: 1, xf: extent_x, yf: 0 },
+            steep: Line { xi: 1, yi: 0, xf: 0, yf: extent_y },
+            shallow_bump: None,
+            steep_bump: None,
+        }],
+        bumps: Vec::new(),
+    };
+    let mut i = 1;
+    while i <= extent_x + extent_y && !quadrant.wedges.is_empty() {
+        let mut index = 0;
+        let mut j = (i - extent_x).max(0);
+        while j <= i.min(extent_y) && index < quadrant.wedges.len() {
+            quadrant.visit(view, i - j, j, &mut index);
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+impl Quadrant {
+    fn visit<M: Transparency>(&mut self, view: &mut View<M>, x: i32, y: i32, index: &mut usize) {
+        let (top_x, top_y) = (x, y + 1);
+        let (bottom_x, bottom_y) = (x + 1, y);
+        while *index < self.wedges.len() && self.wedges[*index].steep.is_below_or_collinear(bottom_x, bottom_y) {
+            *index += 1;
+        }
+        if *index == self.wedges.len() || self.wedges[*index].shallow.is_above_or_collinear(top_x, top_y) {
+            return;
+        }
+        let point = Point::new(view.origin.x + x * self.dx, view.origin.y + y * self.dy);
+        view.reveal(point);
+        if !view.is_opaque(point) {
+            return;
+        }
+        let wedge = self.wedges[*index];
+        let above = wedge.shallow.is_above(bottom_x, bottom_y);
+        let below = wedge.steep.is_below(top_x, top_y);
+        if above && below {
+            self.wedges.remove(*index);
+        } else if above {
+            self.add_shallow_bump(*index, top_x, top_y);
+            self.check(*index);
+        } else if below {
+            self.add_steep_bump(*index, bottom_x, bottom_y);
+            self.check(*index);
+        } else {
+            let shallow_index = *index;
+            *index += 1;
+            let mut steep_index = *index;
+            self.wedges.insert(shallow_index, wedge);
+            self.add_steep_bump(shallow_index, bottom_x, bottom_y);
+            if !self.check(shallow_index) {
+                *index -= 1;
+                steep_index -= 1;
+            }
+            self.add_shallow_bump(steep_index, top_x, top_y);
+            self.check(steep_index);
+        }
+    }
+
+    fn add_shallow_bump(&mut self, index: usize, x: i32, y: i32) {
+        let wedge = &mut self.wedges[index];
+        wedge.shallow.xf = x;
+        wedge.shallow.yf = y;
+        self.bumps.push(Bump { x, y, parent: wedge.shallow_bump });
+        wedge.shallow_bump = Some(self.bumps.len() - 1);
+        let mut current = wedge.steep_bump;
+        while let Some(bump) = current {
+            let bump = &self.bumps[bump];
+            if wedge.shallow.is_above(bump.x, bump.y) {
+                wedge.shallow.xi = bump.x;
+                wedge.shallow.yi = bump.y;
+            }
+            current = bump.parent;
+        }
+    }
+
+    fn add_steep_bump(&mut self, index: usize, x: i32, y: i32) {
+        let wedge = &mut self.wedges[index];
+        wedge.steep.xf = x;
+        wedge.steep.yf = y;
+        self.bumps.push(Bump { x, y, parent: wedge.steep_bump });
+        wedge.steep_bump = Some(self.bumps.len() - 1);
+        let mut current = wedge.shallow_bump;
+        while let Some(bump) = current {
+            let bump = &self.bumps[bump];
+            if wedge.steep.is_below(bump.x, bump.y) {
+                wedge.steep.xi = bump.x;
+                wedge.steep.yi = bump.y;
+            }
+            current = bump.parent;
+        }
+    }
+
+    /// Remove a wedge that has narrowed to a line through the viewer, returning false if removed.
+    fn check(&mut self, index: usize) -> bool {
+        let (shallow, steep) = (self.wedges[index].shallow, self.wedges[index].steep);
+        if shallow.is_line_collinear(&steep) && (shallow.is_collinear(0, 1) || shallow.is_collinear(1, 0)) {
+            self.wedges.remove(index);
+            false
+        } else {
+            true
+        }
+    }
+}
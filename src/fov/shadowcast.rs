@@ -0,0 +1,81 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Recursive Shadowcasting
+//!
+
+use super::{Transparency, View};
+use map::Point;
+
+/// Transformations from octant-local coordinates to map coordinates
+const OCTANTS: [[i32; 4]; 8] = [
+    [1, 0, 0, 1],
+    [0, 1, 1, 0],
+    [0, -1, 1, 0],
+    [-1, 0, 0, 1],
+    [-1, 0, 0, -1],
+    [0, -1, -1, 0],
+    [0, 1, -1, 0],
+    [1, 0, 0, -1],
+];
+
+pub(super) fn compute<M: Transparency>(view: &mut View<M>) {
+    for octant in OCTANTS.iter() {
+        cast(view, octant, 1, 1.0, 0.0);
+    }
+}
+
+/// Scan an octant row by row, recursing into the light that passes each opaque run.
+fn cast<M: Transparency>(view: &mut View<M>, octant: &[i32; 4], row: i32, mut start: f64, end: f64) {
+    if start < end {
+        return;
+    }
+    let mut next_start = start;
+    for distance in row..=view.range {
+        let dy = -distance;
+        let mut blocked = false;
+        for dx in -distance..=0 {
+            let left = (f64::from(dx) - 0.5) / (f64::from(dy) + 0.5);
+            let right = (f64::from(dx) + 0.5) / (f64::from(dy) - 0.5);
+            if start < right {
+                continue;
+            } else if end > left {
+                break;
+            }
+            let point = Point::new(
+                view.origin.x + dx * octant[0] + dy * octant[1],
+                view.origin.y + dx * octant[2] + dy * octant[3],
+            );
+            view.reveal(point);
+            let opaque = view.is_opaque(point);
+            if blocked {
+                if opaque {
+                    next_start = right;
+                } else {
+                    blocked = false;
+                    start = next_start;
+                }
+            } else if opaque && distance < view.range {
+                blocked = true;
+                cast(view, octant, distance + 1, start, left);
+                next_start = right;
+            }
+        }
+        if blocked {
+            break;
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Symmetric Shadowcasting
+//!
+//! Quadrant based shadowcasting using exact rational slopes, after Albert Ford's description.
+//!
+
+use super::{Transparency, View};
+use map::Point;
+
+/// Slope of `num / den` columns per row, with `den` always positive
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    /// Slope through the near corner of the tile at `col` in row `depth`.
+    fn through(depth: i32, col: i32) -> Slope {
+        Slope {
+            num: 2 * col - 1,
+            den: 2 * depth,
+        }
+    }
+    /// `depth * slope` rounded to the nearest column, ties rounding up.
+    fn round_up(self, depth: i32) -> i32 {
+        (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+    }
+    /// `depth * slope` rounded to the nearest column, ties rounding down.
+    fn round_down(self, depth: i32) -> i32 {
+        -(self.den - 2 * depth * self.num).div_euclid(2 * self.den)
+    }
+}
+
+pub(super) fn compute<M: Transparency>(view: &mut View<M>) {
+    for quadrant in 0..4 {
+        scan(view, quadrant, 1, Slope { num: -1, den: 1 }, Slope { num: 1, den: 1 });
+    }
+}
+
+fn transform(origin: Point, quadrant: u8, depth: i32, col: i32) -> Point {
+    match quadrant {
+        0 => Point::new(origin.x + col, origin.y - depth),
+        1 => Point::new(origin.x + depth, origin.y + col),
+        2 => Point::new(origin.x + col, origin.y + depth),
+        _ => Point::new(origin.x - depth, origin.y + col),
+    }
+}
+
+fn scan<M: Transparency>(view: &mut View<M>, quadrant: u8, depth: i32, mut start: Slope, end: Slope) {
+    if depth > view.range {
+        return;
+    }
+    let mut previous = None;
+    for col in start.round_up(depth)..=end.round_down(depth) {
+        let point = transform(view.origin, quadrant, depth, col);
+        let opaque = view.is_opaque(point);
+        let symmetric = col * start.den >= depth * start.num && col * end.den <= depth * end.num;
+        if opaque || symmetric {
+            view.reveal(point);
+        }
+        match previous {
+            Some(true) if !opaque => start = Slope::through(depth, col),
+            Some(false) if opaque => scan(view, quadrant, depth + 1, start, Slope::through(depth, col)),
+            _ => {}
+        }
+        previous = Some(opaque);
+    }
+    if previous == Some(false) {
+        scan(view, quadrant, depth + 1, start, end);
+    }
+}
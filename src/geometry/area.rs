@@ -0,0 +1,75 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Grid Areas
+//!
+
+use fov::{Algorithm, Radius, Transparency, Visibility};
+use map::{Point, Rect};
+use std::f32::consts::PI;
+
+/// Square of tiles that could be within `radius` of `center`
+fn around(center: Point, radius: i32) -> Rect {
+    let radius = radius.max(0);
+    Rect::new(center.x - radius, center.y - radius, 2 * radius + 1, 2 * radius + 1)
+}
+
+/// Filled circle, using the same shape as a circular field of view.
+pub fn circle(center: Point, radius: i32) -> impl Iterator<Item = Point> {
+    around(center, radius).points().filter(move |point| Radius::Circle(radius).contains(*point - center))
+}
+
+/// Edge of a filled circle, without gaps between orthogonal neighbours.
+pub fn circle_outline(center: Point, radius: i32) -> impl Iterator<Item = Point> {
+    let inside = move |point: Point| Radius::Circle(radius).contains(point - center);
+    circle(center, radius).filter(move |point| {
+        [Point::NORTH, Point::EAST, Point::SOUTH, Point::WEST]
+            .iter()
+            .any(|offset| !inside(*point + *offset))
+    })
+}
+
+/// Tiles within `radius` of `origin` and within `angle` degrees of the direction towards
+/// `toward`, excluding `origin` itself.
+///
+/// `angle` is the full width of the cone. Nothing is produced if `toward` is `origin`.
+pub fn cone(origin: Point, toward: Point, radius: i32, angle: f32) -> impl Iterator<Item = Point> {
+    let direction = toward - origin;
+    let facing = (direction.y as f32).atan2(direction.x as f32);
+    let half = angle.to_radians() / 2.0 + 1e-4;
+    circle(origin, radius).filter(move |point| {
+        let offset = *point - origin;
+        if offset == Point::ZERO || direction == Point::ZERO {
+            return false;
+        }
+        let mut difference = (offset.y as f32).atan2(offset.x as f32) - facing;
+        if difference > PI {
+            difference -= 2.0 * PI;
+        } else if difference < -PI {
+            difference += 2.0 * PI;
+        }
+        difference.abs() <= half
+    })
+}
+
+/// Tiles caught in a blast of `radius` around `center`, where walls shelter the tiles behind them.
+///
+/// The walls bounding the blast are included, so they can be damaged. `buffer` is reused for
+/// the underlying field of view.
+pub fn blast<'a, M: Transparency>(map: &M, center: Point, radius: i32, buffer: &'a mut Visibility) -> impl Iterator<Item = Point> + 'a {
+    buffer.compute(map, center, Radius::Circle(radius), Algorithm::Symmetric);
+    buffer.points()
+}
@@ -0,0 +1,189 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Grid Lines
+//!
+
+use map::Point;
+
+/// Bresenham line between two points, inclusive
+///
+/// Fast and gap free, but the line from `a` to `b` may differ from the line from `b` to `a`.
+#[derive(Clone, Debug)]
+pub struct Bresenham {
+    current: Point,
+    end: Point,
+    dx: i32,
+    dy: i32,
+    step: Point,
+    error: i32,
+    done: bool,
+}
+
+impl Bresenham {
+    pub fn new(start: Point, end: Point) -> Bresenham {
+        let dx = (end.x - start.x).abs();
+        let dy = -(end.y - start.y).abs();
+        Bresenham {
+            current: start,
+            end,
+            dx,
+            dy,
+            step: (end - start).signum(),
+            error: dx + dy,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Bresenham {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if self.done {
+            return None;
+        }
+        let point = self.current;
+        if point == self.end {
+            self.done = true;
+        } else {
+            let doubled = 2 * self.error;
+            if doubled >= self.dy {
+                self.error += self.dy;
+                self.current.x += self.step.x;
+            }
+            if doubled <= self.dx {
+                self.error += self.dx;
+                self.current.y += self.step.y;
+            }
+        }
+        Some(point)
+    }
+}
+
+/// Line between two points, inclusive, that visits the same points in either direction
+///
+/// Each step rounds the exact position to the nearest tile, breaking ties towards positive
+/// coordinates, so targeting from `a` to `b` and from `b` to `a` passes the same tiles.
+#[derive(Clone, Debug)]
+pub struct SymmetricLine {
+    start: Point,
+    delta: Point,
+    steps: i32,
+    index: i32,
+}
+
+impl SymmetricLine {
+    pub fn new(start: Point, end: Point) -> SymmetricLine {
+        let delta = end - start;
+        SymmetricLine {
+            start,
+            delta,
+            steps: delta.x.abs().max(delta.y.abs()),
+            index: 0,
+        }
+    }
+    fn along(&self, start: i32, delta: i32) -> i32 {
+        let n = self.steps;
+        (2 * (start * n + self.index * delta) + n).div_euclid(2 * n)
+    }
+}
+
+impl Iterator for SymmetricLine {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if self.index > self.steps {
+            return None;
+        }
+        let point = if self.steps == 0 {
+            self.start
+        } else {
+            Point::new(self.along(self.start.x, self.delta.x), self.along(self.start.y, self.delta.y))
+        };
+        self.index += 1;
+        Some(point)
+    }
+}
+
+/// Every tile a line between the centres of two tiles touches, inclusive
+///
+/// Where the line passes exactly through a corner, both tiles beside the corner are included
+/// before the diagonal one.
+#[derive(Clone, Debug)]
+pub struct Supercover {
+    current: Point,
+    step: Point,
+    nx: i32,
+    ny: i32,
+    ix: i32,
+    iy: i32,
+    queue: [Point; 2],
+    queued: usize,
+    started: bool,
+}
+
+impl Supercover {
+    pub fn new(start: Point, end: Point) -> Supercover {
+        let delta = end - start;
+        Supercover {
+            current: start,
+            step: delta.signum(),
+            nx: delta.x.abs(),
+            ny: delta.y.abs(),
+            ix: 0,
+            iy: 0,
+            queue: [start; 2],
+            queued: 0,
+            started: false,
+        }
+    }
+}
+
+impl Iterator for Supercover {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if !self.started {
+            self.started = true;
+            return Some(self.current);
+        }
+        if self.queued > 0 {
+            self.queued -= 1;
+            return Some(self.queue[self.queued]);
+        }
+        if self.ix >= self.nx && self.iy >= self.ny {
+            return None;
+        }
+        let decision = (1 + 2 * self.ix) * self.ny - (1 + 2 * self.iy) * self.nx;
+        if decision == 0 {
+            let beside = Point::new(self.current.x + self.step.x, self.current.y);
+            self.queue = [self.current + self.step, Point::new(self.current.x, self.current.y + self.step.y)];
+            self.queued = 2;
+            self.current += self.step;
+            self.ix += 1;
+            self.iy += 1;
+            return Some(beside);
+        } else if decision < 0 {
+            self.current.x += self.step.x;
+            self.ix += 1;
+        } else {
+            self.current.y += self.step.y;
+            self.iy += 1;
+        }
+        Some(self.current)
+    }
+}
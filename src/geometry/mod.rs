@@ -0,0 +1,141 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Geometry
+//!
+//! Lines and areas over grid points for targeting and area effects.
+//!
+
+mod area;
+mod line;
+
+pub use self::area::{blast, circle, circle_outline, cone};
+pub use self::line::{Bresenham, Supercover, SymmetricLine};
+
+use fov::Transparency;
+use map::Point;
+use std::iter::{Skip, Take};
+
+/// Path of a projectile fired from `from` towards `to`
+///
+/// The projectile continues past `to` until it has travelled `range` tiles, and stops after
+/// the first opaque tile it hits or at the edge of the map. The firing tile is not included.
+/// Stop at creatures by ending iteration at the first occupied tile.
+pub fn projectile<M: Transparency>(map: &M, from: Point, to: Point, range: i32) -> Projectile<'_, M> {
+    let delta = to - from;
+    let length = delta.x.abs().max(delta.y.abs());
+    // Every step moves one tile along the major axis, so no projectile stays on the map for longer
+    // than its larger side.
+    let range = range.max(0).min(map.width().max(map.height()).max(0));
+    let reach = if length == 0 {
+        from
+    } else {
+        from + delta * ((i64::from(range) + i64::from(length) - 1) / i64::from(length)).max(1) as i32
+    };
+    let range = if length == 0 { 0 } else { range as usize };
+    Projectile {
+        map,
+        line: Bresenham::new(from, reach).skip(1).take(range),
+        stopped: false,
+    }
+}
+
+/// Iterator over the tiles a projectile passes through
+pub struct Projectile<'a, M: 'a> {
+    map: &'a M,
+    line: Take<Skip<Bresenham>>,
+    stopped: bool,
+}
+
+impl<'a, M: Transparency> Iterator for Projectile<'a, M> {
+    type Item = Point;
+
+    fn next(&mut self) -> Option<Point> {
+        if self.stopped {
+            return None;
+        }
+        let point = self.line.next()?;
+        if point.x < 0 || point.y < 0 || point.x >= self.map.width() || point.y >= self.map.height() {
+            self.stopped = true;
+            return None;
+        }
+        self.stopped = !self.map.is_transparent(point);
+        Some(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fov::Visibility;
+    use map::{Grid, Rect};
+
+    fn points<I: Iterator<Item = Point>>(iter: I) -> Vec<(i32, i32)> {
+        iter.map(|point| (point.x, point.y)).collect()
+    }
+
+    #[test]
+    fn lines_connect_endpoints() {
+        let (a, b) = (Point::new(1, 1), Point::new(6, 3));
+        assert_eq!(points(Bresenham::new(a, b)), vec![(1, 1), (2, 1), (3, 2), (4, 2), (5, 3), (6, 3)]);
+        assert_eq!(points(Bresenham::new(a, a)), vec![(1, 1)]);
+        for &b in [Point::new(6, 3), Point::new(-3, 2), Point::new(1, -4), Point::new(5, 5), Point::new(4, 2)].iter() {
+            let mut forward = points(SymmetricLine::new(a, b));
+            let mut backward = points(SymmetricLine::new(b, a));
+            assert_eq!(forward.first(), Some(&(a.x, a.y)));
+            assert_eq!(forward.last(), Some(&(b.x, b.y)));
+            forward.sort();
+            backward.sort();
+            assert_eq!(forward, backward);
+        }
+    }
+
+    #[test]
+    fn supercover_includes_corners() {
+        assert_eq!(points(Supercover::new(Point::new(0, 0), Point::new(2, 2))), vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 1), (1, 2), (2, 2)]);
+        assert_eq!(points(Supercover::new(Point::new(0, 0), Point::new(3, 1))), vec![(0, 0), (1, 0), (2, 0), (1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn areas() {
+        assert_eq!(circle(Point::ZERO, 1).count(), 9);
+        assert_eq!(circle(Point::ZERO, 0).count(), 1);
+        assert!(circle_outline(Point::ZERO, 5).all(|point| point.distance(Point::ZERO) > 3.5));
+        let east = points(cone(Point::ZERO, Point::new(5, 0), 2, 90.0));
+        assert_eq!(east, vec![(1, -1), (2, -1), (1, 0), (2, 0), (1, 1), (2, 1)]);
+        assert_eq!(cone(Point::ZERO, Point::ZERO, 3, 90.0).count(), 0);
+
+        let mut map = Grid::new(9, 9, true);
+        map.fill_rect(Rect::new(5, 0, 1, 9), false);
+        let mut buffer = Visibility::new();
+        let hit = points(blast(&map, Point::new(4, 4), 3, &mut buffer));
+        assert!(hit.contains(&(5, 4)));
+        assert!(!hit.contains(&(6, 4)));
+        assert!(hit.contains(&(1, 4)));
+    }
+
+    #[test]
+    fn projectiles_stop_when_blocked() {
+        let mut map = Grid::new(10, 3, true);
+        map[Point::new(7, 1)] = false;
+        assert_eq!(points(projectile(&map, Point::new(1, 1), Point::new(3, 1), 20)), vec![(2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1)]);
+        assert_eq!(points(projectile(&map, Point::new(1, 1), Point::new(3, 1), 3)), vec![(2, 1), (3, 1), (4, 1)]);
+        assert_eq!(points(projectile(&map, Point::new(1, 1), Point::new(1, 0), 5)), vec![(1, 0)]);
+        assert_eq!(projectile(&map, Point::new(1, 1), Point::new(1, 1), 5).count(), 0);
+        assert_eq!(points(projectile(&map, Point::new(1, 1), Point::new(3, 1), i32::MAX)), vec![(2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1)]);
+        assert_eq!(points(projectile(&map, Point::new(8, 1), Point::new(100_000, 1), i32::MAX - 1)), vec![(9, 1)]);
+    }
+}
@@ -0,0 +1,108 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Offset Coordinate Layouts
+//!
+
+use super::Hex;
+use map::{Point, Topology};
+
+/// How a hex map is laid out in the rows and columns of a Grid
+///
+/// Pointy topped hexes form rows, every other one shoved half a hex to the right. Flat topped
+/// hexes form columns, every other one shoved half a hex down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HexLayout {
+    /// Pointy topped, odd rows shoved right
+    OddRows,
+    /// Pointy topped, even rows shoved right
+    EvenRows,
+    /// Flat topped, odd columns shoved down
+    OddColumns,
+    /// Flat topped, even columns shoved down
+    EvenColumns,
+}
+
+impl HexLayout {
+    /// Hex stored at a point of the Grid
+    pub fn to_hex(self, point: Point) -> Hex {
+        let (col, row) = (point.x, point.y);
+        match self {
+            HexLayout::OddRows => Hex::new(col - (row - (row & 1)) / 2, row),
+            HexLayout::EvenRows => Hex::new(col - (row + (row & 1)) / 2, row),
+            HexLayout::OddColumns => Hex::new(col, row - (col - (col & 1)) / 2),
+            HexLayout::EvenColumns => Hex::new(col, row - (col + (col & 1)) / 2),
+        }
+    }
+    /// Point of the Grid storing a hex
+    pub fn to_point(self, hex: Hex) -> Point {
+        let (q, r) = (hex.q, hex.r);
+        match self {
+            HexLayout::OddRows => Point::new(q + (r - (r & 1)) / 2, r),
+            HexLayout::EvenRows => Point::new(q + (r + (r & 1)) / 2, r),
+            HexLayout::OddColumns => Point::new(q, r + (q - (q & 1)) / 2),
+            HexLayout::EvenColumns => Point::new(q, r + (q + (q & 1)) / 2),
+        }
+    }
+}
+
+impl Topology for HexLayout {
+    fn directions(&self) -> usize {
+        Hex::DIRECTIONS.len()
+    }
+    fn neighbor(&self, point: Point, direction: usize) -> Point {
+        self.to_point(self.to_hex(point).neighbor(direction))
+    }
+    fn distance(&self, from: Point, to: Point) -> i32 {
+        self.to_hex(from).distance(self.to_hex(to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::{Grid, Neighborhood, Regions};
+
+    const LAYOUTS: [HexLayout; 4] = [HexLayout::OddRows, HexLayout::EvenRows, HexLayout::OddColumns, HexLayout::EvenColumns];
+
+    #[test]
+    fn offsets_round_trip() {
+        for &layout in LAYOUTS.iter() {
+            for point in Grid::new(7, 6, ()).points().map(|point| point - Point::new(3, 3)) {
+                assert_eq!(layout.to_point(layout.to_hex(point)), point, "{:?}", layout);
+                for direction in 0..6 {
+                    let neighbor = layout.neighbor(point, direction);
+                    assert_eq!(layout.distance(point, neighbor), 1, "{:?}", layout);
+                    assert!(point.chebyshev(neighbor) == 1, "{:?}", layout);
+                }
+            }
+        }
+        assert_eq!(HexLayout::OddRows.neighbor(Point::new(2, 1), 0), Point::new(3, 1));
+        assert_eq!(HexLayout::OddRows.neighbor(Point::new(2, 1), 1), Point::new(3, 0));
+        assert_eq!(HexLayout::EvenRows.neighbor(Point::new(2, 1), 1), Point::new(2, 0));
+    }
+
+    #[test]
+    fn analysis_follows_hex_adjacency() {
+        let mut grid = Grid::new(5, 4, false);
+        grid.set(Point::new(1, 1), true);
+        grid.set(Point::new(2, 2), true);
+        let square = Regions::label(&grid, Neighborhood::Four, |open| *open);
+        assert_eq!(square.len(), 2);
+        assert_eq!(Regions::label(&grid, HexLayout::OddRows, |open| *open).len(), 1);
+        assert_eq!(Regions::label(&grid, HexLayout::EvenRows, |open| *open).len(), 2);
+    }
+}
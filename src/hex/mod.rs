@@ -0,0 +1,261 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Hexagonal Grids
+//!
+//! Hexes are addressed by axial coordinates in [`Hex`], with [`Cube`] coordinates for the
+//! algorithms that read better with three axes. Hex maps are stored in an ordinary [`Grid`]
+//! indexed by offset coordinates, and a [`HexLayout`] converts between the two. The layout is
+//! also a [`Topology`], so map analysis, FOV and pathfinding work on hex maps.
+//!
+//! [`Hex`]: struct.Hex.html
+//! [`Cube`]: struct.Cube.html
+//! [`Grid`]: struct.Grid.html
+//! [`HexLayout`]: enum.HexLayout.html
+//! [`Topology`]: trait.Topology.html
+//!
+
+mod layout;
+
+pub use self::layout::HexLayout;
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::ops::{Add, Mul, Sub};
+
+/// Offsets of the endpoints of a line, breaking ties between hexes on the same side every time
+const NUDGE: (f64, f64) = (1e-6, 2e-6);
+
+/// Hex in axial coordinates
+///
+/// The third cube coordinate is implied, `s = -q - r`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+
+impl Hex {
+    pub const ZERO: Hex = Hex { q: 0, r: 0 };
+    /// Unit steps to each neighbour, counter-clockwise starting from `+q`
+    pub const DIRECTIONS: [Hex; 6] = [
+        Hex { q: 1, r: 0 },
+        Hex { q: 1, r: -1 },
+        Hex { q: 0, r: -1 },
+        Hex { q: -1, r: 0 },
+        Hex { q: -1, r: 1 },
+        Hex { q: 0, r: 1 },
+    ];
+
+    pub const fn new(q: i32, r: i32) -> Hex {
+        Hex { q, r }
+    }
+    /// Implied third cube coordinate
+    pub fn s(self) -> i32 {
+        -self.q - self.r
+    }
+    /// Neighbour in one of the six `DIRECTIONS`, wrapping around past the last.
+    pub fn neighbor(self, direction: usize) -> Hex {
+        self + Hex::DIRECTIONS[direction % 6]
+    }
+    /// Neighbours in `DIRECTIONS` order
+    pub fn neighbors(self) -> [Hex; 6] {
+        let mut neighbors = Hex::DIRECTIONS;
+        for neighbor in &mut neighbors {
+            *neighbor = self + *neighbor;
+        }
+        neighbors
+    }
+    /// Steps from the origin
+    pub fn length(self) -> i32 {
+        (self.q.abs() + self.r.abs() + self.s().abs()) / 2
+    }
+    /// Fewest steps to another hex.
+    pub fn distance(self, other: Hex) -> i32 {
+        (self - other).length()
+    }
+    /// Hex containing a fractional axial position.
+    pub fn round(q: f64, r: f64) -> Hex {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        Hex::new(rq as i32, rr as i32)
+    }
+    /// Hexes along a straight line to another hex, both ends included.
+    pub fn line(self, to: Hex) -> impl Iterator<Item = Hex> {
+        self.nudged_line(to, 1.0)
+    }
+    /// Line with the ties broken to the other side of the one `line` takes, used where lines have
+    /// to be symmetric.
+    pub(crate) fn nudged_line(self, to: Hex, side: f64) -> impl Iterator<Item = Hex> {
+        let steps = self.distance(to);
+        let (nq, nr) = (NUDGE.0 * side, NUDGE.1 * side);
+        let (fq, fr) = (f64::from(self.q) + nq, f64::from(self.r) + nr);
+        let (tq, tr) = (f64::from(to.q) + nq, f64::from(to.r) + nr);
+        (0..=steps).map(move |step| {
+            let t = if steps == 0 { 0.0 } else { f64::from(step) / f64::from(steps) };
+            Hex::round(fq + (tq - fq) * t, fr + (tr - fr) * t)
+        })
+    }
+    /// Hexes exactly `radius` steps away, counter-clockwise. Just this hex for a radius of 0.
+    pub fn ring(self, radius: i32) -> impl Iterator<Item = Hex> {
+        let radius = radius.max(0);
+        let start = self + Hex::DIRECTIONS[4] * radius;
+        let count = if radius == 0 { 1 } else { 6 * radius as usize };
+        (0..count).scan(start, move |hex, index| {
+            let current = *hex;
+            if radius > 0 {
+                *hex = hex.neighbor(index / radius as usize);
+            }
+            Some(current)
+        })
+    }
+    /// Hexes at most `radius` steps away, ring by ring outward from this one.
+    pub fn spiral(self, radius: i32) -> impl Iterator<Item = Hex> {
+        (0..=radius.max(0)).flat_map(move |ring| self.ring(ring))
+    }
+}
+
+impl Display for Hex {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "[{}, {}]", self.q, self.r)
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+    fn sub(self, other: Hex) -> Hex {
+        Hex::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl Mul<i32> for Hex {
+    type Output = Hex;
+    fn mul(self, factor: i32) -> Hex {
+        Hex::new(self.q * factor, self.r * factor)
+    }
+}
+
+/// Hex in cube coordinates, whose three coordinates always sum to zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cube {
+    q: i32,
+    r: i32,
+    s: i32,
+}
+
+impl Cube {
+    /// Create cube coordinates, or `None` if they do not sum to zero.
+    pub fn new(q: i32, r: i32, s: i32) -> Option<Cube> {
+        if q + r + s == 0 {
+            Some(Cube { q, r, s })
+        } else {
+            None
+        }
+    }
+    pub fn q(self) -> i32 {
+        self.q
+    }
+    pub fn r(self) -> i32 {
+        self.r
+    }
+    pub fn s(self) -> i32 {
+        self.s
+    }
+    /// Rotate 60 degrees clockwise around the origin.
+    pub fn rotate_right(self) -> Cube {
+        Cube {
+            q: -self.r,
+            r: -self.s,
+            s: -self.q,
+        }
+    }
+    /// Rotate 60 degrees counter-clockwise around the origin.
+    pub fn rotate_left(self) -> Cube {
+        Cube {
+            q: -self.s,
+            r: -self.q,
+            s: -self.r,
+        }
+    }
+}
+
+impl From<Hex> for Cube {
+    fn from(hex: Hex) -> Cube {
+        Cube {
+            q: hex.q,
+            r: hex.r,
+            s: hex.s(),
+        }
+    }
+}
+
+impl From<Cube> for Hex {
+    fn from(cube: Cube) -> Hex {
+        Hex::new(cube.q, cube.r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances_and_shapes() {
+        let center = Hex::new(2, -1);
+        assert_eq!(Hex::new(0, 0).distance(Hex::new(3, -3)), 3);
+        assert_eq!(Hex::new(-2, 1).distance(Hex::new(2, 1)), 4);
+        assert!(center.neighbors().iter().all(|neighbor| neighbor.distance(center) == 1));
+        for radius in 0..4 {
+            let ring: Vec<Hex> = center.ring(radius).collect();
+            assert_eq!(ring.len(), if radius == 0 { 1 } else { 6 * radius as usize });
+            assert!(ring.iter().all(|hex| hex.distance(center) == radius));
+            assert!(ring.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+        }
+        let mut spiral: Vec<Hex> = center.spiral(3).collect();
+        assert_eq!(spiral.len(), 37);
+        spiral.sort();
+        spiral.dedup();
+        assert_eq!(spiral.len(), 37);
+    }
+
+    #[test]
+    fn lines_step_between_neighbours() {
+        let from = Hex::new(-3, 1);
+        for to in from.spiral(5) {
+            let line: Vec<Hex> = from.line(to).collect();
+            assert_eq!(line.len() as i32, from.distance(to) + 1);
+            assert_eq!((line[0], line[line.len() - 1]), (from, to));
+            assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+        }
+        let cube = Cube::from(Hex::new(1, -3));
+        assert_eq!(Cube::new(1, -3, 2), Some(cube));
+        assert_eq!(Cube::new(1, 1, 1), None);
+        assert_eq!(cube.rotate_right().rotate_left(), cube);
+        assert_eq!(Hex::from(cube.rotate_right().rotate_right().rotate_right()), Hex::new(-1, 3));
+    }
+}
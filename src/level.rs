@@ -0,0 +1,626 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Level Manager
+//!
+//! Games with several levels keep only the active one in the [`World`]. The Components of the
+//! others are frozen as snapshots, stored per level in the save, and put back when the player
+//! returns. A frozen level's Entities stay allocated without any Components, so their handles are
+//! never reused and references between levels survive any number of trips. Resources are shared
+//! by every level and are never frozen.
+//!
+//! A level is generated the first time it is entered, from a seed derived from its [`LevelId`] so
+//! the same master seed always produces the same dungeon.
+//!
+//! [`World`]: ../oxide_ecs/struct.World.html
+//! [`LevelId`]: struct.LevelId.html
+//!
+
+use super::data::Data;
+use super::engine::Engine;
+use super::event::Event;
+use super::map::{Point, Position};
+use super::random::{GameRng, Random};
+use super::state::{State, Transition};
+use oxide_ecs::{Entity, SnapshotError, World, WorldSnapshot};
+use rand::SeedableRng;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Generates a level the first time it is entered, returning the Links leaving it.
+///
+/// When it is called the only Entities with Components are the travellers. Resources are shared
+/// by every level, so keep state which belongs to a single level in Components.
+pub type LevelBuilder = fn(&LevelId, &mut GameRng, &mut World) -> Vec<Link>;
+
+/// Catches a level up on the given number of turns it spent frozen.
+///
+/// Levels are not simulated while the player is elsewhere, so this is the place for cheap, low
+/// fidelity approximations such as regrowing plants or moving monsters toward the stairs.
+pub type LevelSimulation = fn(&LevelId, u64, &mut World);
+
+/// Identifies a level by dungeon branch and depth.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct LevelId {
+    pub branch: String,
+    pub depth: u32,
+}
+
+impl LevelId {
+    pub fn new(branch: &str, depth: u32) -> LevelId {
+        LevelId {
+            branch: branch.to_string(),
+            depth,
+        }
+    }
+    /// Level one deeper in the same branch
+    pub fn below(&self) -> LevelId {
+        LevelId::new(&self.branch, self.depth + 1)
+    }
+    /// Level one shallower in the same branch, if this is not its first level.
+    pub fn above(&self) -> Option<LevelId> {
+        self.depth.checked_sub(1).map(|depth| LevelId::new(&self.branch, depth))
+    }
+}
+
+impl Display for LevelId {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{}:{}", self.branch, self.depth)
+    }
+}
+
+/// Kind of passage a Link represents
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LinkKind {
+    /// Stairs to the next level of the same branch
+    Stairs,
+    /// Entrance to another branch
+    Branch,
+    /// Any other connection, such as a portal or trapdoor
+    Portal,
+}
+
+/// One way passage from a point on one level to another level.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub from: LevelId,
+    pub at: Point,
+    pub to: LevelId,
+    /// Where travellers arrive. Without one they arrive on the destination's Link back to
+    /// `from`, so stairs only need to know their own position.
+    pub arrival: Option<Point>,
+    pub kind: LinkKind,
+}
+
+impl Link {
+    pub fn new(kind: LinkKind, from: LevelId, at: Point, to: LevelId) -> Link {
+        Link {
+            from,
+            at,
+            to,
+            arrival: None,
+            kind,
+        }
+    }
+    /// Arrive at a fixed point instead of the destination's Link back.
+    pub fn arriving_at(mut self, arrival: Point) -> Self {
+        self.arrival = Some(arrival);
+        self
+    }
+}
+
+/// Components of a level kept out of the World while the player is elsewhere
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct FrozenLevel {
+    level: LevelId,
+    turn: u64,
+    snapshot: WorldSnapshot,
+}
+
+/// Generates, freezes and restores the levels of a game.
+///
+/// Install a [`LevelBuilder`] before the first level change. The Engine owns the manager and
+/// saves the frozen levels and Links with the rest of the game, while the builder and simulation
+/// have to be installed again after a restart.
+///
+/// [`LevelBuilder`]: type.LevelBuilder.html
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct LevelManager {
+    current: Option<LevelId>,
+    links: Vec<Link>,
+    frozen: Vec<FrozenLevel>,
+    #[serde(skip)]
+    builder: Option<LevelBuilder>,
+    #[serde(skip)]
+    simulation: Option<LevelSimulation>,
+}
+
+impl LevelManager {
+    pub fn new() -> LevelManager {
+        LevelManager::default()
+    }
+    pub fn set_builder(&mut self, builder: LevelBuilder) {
+        self.builder = Some(builder)
+    }
+    /// Catch levels up on the turns they spent frozen when they are entered again. Without a
+    /// simulation levels resume exactly as they were left.
+    pub fn set_simulation(&mut self, simulation: LevelSimulation) {
+        self.simulation = Some(simulation)
+    }
+    /// Level currently in the World
+    pub fn current(&self) -> Option<&LevelId> {
+        self.current.as_ref()
+    }
+    /// Check if a level has been generated, either into the World or frozen.
+    pub fn is_generated(&self, level: &LevelId) -> bool {
+        self.current.as_ref() == Some(level) || self.frozen_since(level).is_some()
+    }
+    /// Turn on which a frozen level was left.
+    pub fn frozen_since(&self, level: &LevelId) -> Option<u64> {
+        self.frozen.iter().find(|frozen| frozen.level == *level).map(|frozen| frozen.turn)
+    }
+    /// Frozen levels, in the order they were left
+    pub fn frozen(&self) -> impl Iterator<Item = &LevelId> {
+        self.frozen.iter().map(|frozen| &frozen.level)
+    }
+    /// Add a Link, replacing any other leaving the same point.
+    pub fn add_link(&mut self, link: Link) {
+        self.links.retain(|other| other.from != link.from || other.at != link.at);
+        self.links.push(link);
+    }
+    /// Link leaving a level at a point
+    pub fn link_at(&self, level: &LevelId, at: Point) -> Option<&Link> {
+        self.links.iter().find(|link| link.from == *level && link.at == at)
+    }
+    /// Links leaving a level
+    pub fn links_from<'a>(&'a self, level: &'a LevelId) -> impl Iterator<Item = &'a Link> + 'a {
+        self.links.iter().filter(move |link| link.from == *level)
+    }
+    /// Snapshots of the frozen levels
+    pub(crate) fn snapshots(&self) -> impl Iterator<Item = &WorldSnapshot> {
+        self.frozen.iter().map(|frozen| &frozen.snapshot)
+    }
+    /// Replace the levels and Links with those read from a save, keeping the builder and
+    /// simulation.
+    pub(crate) fn restore(&mut self, saved: LevelManager) {
+        self.current = saved.current;
+        self.links = saved.links;
+        self.frozen = saved.frozen;
+    }
+    /// Freeze the current level and bring the travellers to another, returning the living
+    /// travellers in order. Their handles do not change.
+    ///
+    /// Everything which can fail is done before the first change, so an error leaves the World
+    /// and the levels as they were.
+    pub(crate) fn travel(
+        &mut self,
+        world: &mut World,
+        random: &Random,
+        turn: u64,
+        level: &LevelId,
+        travellers: &[Entity],
+        arrival: Option<Point>,
+    ) -> Result<Vec<Entity>, LevelError> {
+        let travellers: Vec<Entity> = travellers.iter().cloned().filter(|entity| world.is_alive(*entity)).collect();
+        if !self.is_generated(level) && self.builder.is_none() {
+            return Err(LevelError::NoBuilder(level.clone()));
+        }
+        let parked: HashSet<Entity> = self.frozen.iter().flat_map(|frozen| frozen.snapshot.entities().iter()).collect();
+        let staying: Vec<Entity> = world
+            .entities()
+            .iter()
+            .filter(|entity| !travellers.contains(entity) && !parked.contains(entity))
+            .collect();
+        let left = match self.current {
+            Some(ref current) if current == level => None,
+            Some(ref current) => Some(FrozenLevel {
+                level: current.clone(),
+                turn,
+                snapshot: world.snapshot_entities(&staying)?.without_resources(),
+            }),
+            None if staying.is_empty() => None,
+            None => return Err(LevelError::Unplaced(staying.len())),
+        };
+        let returning = match self.frozen.iter().position(|frozen| frozen.level == *level) {
+            Some(index) => {
+                world.reattach(self.frozen[index].snapshot.clone())?;
+                Some(self.frozen.remove(index))
+            }
+            None => None,
+        };
+
+        if let Some(left) = left {
+            for entity in &staying {
+                world.strip_entity(*entity);
+            }
+            self.frozen.retain(|frozen| frozen.level != left.level);
+            self.frozen.push(left);
+        }
+        let previous = self.current.replace(level.clone());
+        match returning {
+            Some(frozen) => {
+                if let Some(simulation) = self.simulation {
+                    simulation(level, turn.saturating_sub(frozen.turn), world);
+                }
+            }
+            None if previous.as_ref() == Some(level) => (),
+            None => {
+                let builder = self.builder.expect("Builder was checked before the World changed");
+                let mut rng = GameRng::from_seed(random.derive_seed(&format!("level:{}", level)));
+                for link in builder(level, &mut rng, world) {
+                    self.add_link(link);
+                }
+            }
+        }
+
+        let arrival = arrival.or_else(|| {
+            previous
+                .as_ref()
+                .and_then(|previous| self.links_from(level).find(|link| link.to == *previous))
+                .map(|link| link.at)
+        });
+        if let Some(point) = arrival {
+            for entity in &travellers {
+                if let Some(position) = world.get_mut::<Position>(*entity) {
+                    position.0 = point;
+                }
+            }
+        }
+        Ok(travellers)
+    }
+}
+
+/// Errors produced while changing level
+#[derive(Debug)]
+pub enum LevelError {
+    /// The level has not been generated and no LevelBuilder is installed.
+    NoBuilder(LevelId),
+    /// No Link leaves the current level at the point.
+    NoLink(Point),
+    /// Before the first level is entered, this many Entities besides the travellers exist, and no
+    /// level would keep them.
+    Unplaced(usize),
+    /// The World could not be captured or restored.
+    Snapshot(SnapshotError),
+}
+
+impl Display for LevelError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            LevelError::NoBuilder(ref level) => write!(f, "No level builder to generate '{}'", level),
+            LevelError::NoLink(point) => write!(f, "No link leaves the current level at {}", point),
+            LevelError::Unplaced(count) => write!(f, "{} entities exist outside of any level", count),
+            LevelError::Snapshot(ref error) => write!(f, "Level snapshot failed: {}", error),
+        }
+    }
+}
+
+impl Error for LevelError {}
+
+impl From<SnapshotError> for LevelError {
+    fn from(error: SnapshotError) -> LevelError {
+        LevelError::Snapshot(error)
+    }
+}
+
+/// Finishes a level change, choosing the State which follows it.
+pub type LevelChanged<D> = fn(&mut Engine<D>, Result<Vec<Entity>, LevelError>) -> Transition<D>;
+
+/// Loading State which changes level once it has been rendered.
+///
+/// Push it when generating a level may take long enough to need a loading screen. After the
+/// change it hands the travellers who arrived to its [`LevelChanged`] callback and follows the
+/// Transition that returns, usually `Pop` back to the game.
+///
+/// [`LevelChanged`]: type.LevelChanged.html
+pub struct LevelChange<D: Data> {
+    level: LevelId,
+    travellers: Vec<Entity>,
+    arrival: Option<Point>,
+    changed: LevelChanged<D>,
+    render: Option<fn(&mut Engine<D>, &LevelId)>,
+    rendered: bool,
+    done: bool,
+}
+
+impl<D: Data> LevelChange<D> {
+    pub fn new(level: LevelId, travellers: Vec<Entity>, changed: LevelChanged<D>) -> LevelChange<D> {
+        LevelChange {
+            level,
+            travellers,
+            arrival: None,
+            changed,
+            render: None,
+            rendered: false,
+            done: false,
+        }
+    }
+    /// Arrive at a fixed point instead of the destination's Link back.
+    pub fn arriving_at(mut self, arrival: Point) -> Self {
+        self.arrival = Some(arrival);
+        self
+    }
+    /// Draw the loading screen.
+    pub fn with_render(mut self, render: fn(&mut Engine<D>, &LevelId)) -> Self {
+        self.render = Some(render);
+        self
+    }
+}
+
+impl<D: Data> State<D> for LevelChange<D> {
+    fn initialize(&mut self, _engine: &mut Engine<D>) {}
+    fn cleanup(&mut self, _engine: &mut Engine<D>) {}
+    fn suspend(&mut self, _engine: &mut Engine<D>) {}
+    fn resume(&mut self, _engine: &mut Engine<D>) {
+        self.rendered = false;
+    }
+    fn handle(&mut self, _engine: &mut Engine<D>, _event: Event) -> Transition<D> {
+        Transition::Continue
+    }
+    fn update(&mut self, engine: &mut Engine<D>, _delta: f64) -> Transition<D> {
+        if !self.rendered || self.done {
+            return Transition::Continue;
+        }
+        self.done = true;
+        let result = engine.change_level(&self.level, &self.travellers, self.arrival);
+        (self.changed)(engine, result)
+    }
+    fn render(&mut self, engine: &mut Engine<D>) {
+        if let Some(render) = self.render {
+            render(engine, &self.level);
+        }
+        self.rendered = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxide_ecs::{Component, EntityMap, Persistent};
+    use save::SaveError;
+    use rand::Rng;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Progress;
+
+    impl Data for Progress {}
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Name(String);
+
+    impl Component for Name {}
+
+    impl Persistent for Name {}
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Target(Entity);
+
+    impl Component for Target {}
+
+    impl Persistent for Target {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.0 = map.map(self.0);
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Regrown(u64);
+
+    impl Persistent for Regrown {}
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Gold(u32);
+
+    impl Persistent for Gold {}
+
+    fn build(level: &LevelId, rng: &mut GameRng, world: &mut World) -> Vec<Link> {
+        let monster = world.create_entity();
+        world.insert(monster, Name(format!("{} {}", level, rng.gen_range(0, 1000))));
+        world.insert(monster, Position(Point::new(3, 3)));
+        let mut links = vec![Link::new(LinkKind::Stairs, level.clone(), Point::new(5, 5), level.below())];
+        if let Some(above) = level.above() {
+            links.push(Link::new(LinkKind::Stairs, level.clone(), Point::new(1, 1), above));
+        }
+        links
+    }
+
+    fn regrow(_level: &LevelId, turns: u64, world: &mut World) {
+        world.resources_mut().insert(Regrown(turns));
+    }
+
+    fn new_engine() -> Engine<Progress> {
+        let mut engine = Engine::new(Progress);
+        engine.world_mut().register_persistent::<Name>("name");
+        engine.world_mut().register_persistent::<Position>("position");
+        engine.world_mut().register_persistent::<Target>("target");
+        engine.world_mut().persist_resource::<Regrown>("regrown");
+        engine.world_mut().persist_resource::<Gold>("gold");
+        engine.levels_mut().set_builder(build);
+        engine.levels_mut().set_simulation(regrow);
+        engine.random().reseed(11);
+        engine
+    }
+
+    fn names(world: &World) -> Vec<String> {
+        let mut names: Vec<String> = world.storage::<Name>().unwrap().iter().map(|(_, name)| name.0.clone()).collect();
+        names.sort();
+        names
+    }
+
+    fn descend(engine: &mut Engine<Progress>) -> (Entity, Vec<String>) {
+        let player = engine.world_mut().create_entity();
+        engine.world_mut().insert(player, Name("player".to_string()));
+        engine.world_mut().insert(player, Position(Point::new(0, 0)));
+        let player = engine.change_level(&LevelId::new("dungeon", 1), &[player], None).unwrap()[0];
+        let first = names(engine.world());
+        let player = engine.take_link(Point::new(5, 5), &[player]).unwrap()[0];
+        (player, first)
+    }
+
+    #[test]
+    fn freezes_and_restores_levels() {
+        let mut engine = new_engine();
+        let (player, first) = descend(&mut engine);
+        assert_eq!(first.len(), 2);
+        assert_eq!(engine.levels().current(), Some(&LevelId::new("dungeon", 2)));
+        assert_eq!(engine.world().get::<Position>(player), Some(&Position(Point::new(1, 1))));
+        assert_eq!(names(engine.world()).len(), 2);
+        assert!(!names(engine.world()).iter().any(|name| name.starts_with("dungeon:1")));
+
+        for _ in 0..7 {
+            engine.end_turn();
+        }
+        assert_eq!(engine.take_link(Point::new(1, 1), &[player]).unwrap(), vec![player]);
+        assert_eq!(names(engine.world()), first);
+        assert_eq!(engine.world().get::<Position>(player), Some(&Position(Point::new(5, 5))));
+        assert_eq!(*engine.world().resources().fetch::<Regrown>(), Regrown(7));
+        assert_eq!(engine.levels().frozen_since(&LevelId::new("dungeon", 2)), Some(7));
+
+        let mut replay = new_engine();
+        assert_eq!(descend(&mut replay).1, first);
+    }
+
+    #[test]
+    fn references_between_levels_survive_trips() {
+        let mut engine = new_engine();
+        let (player, _) = descend(&mut engine);
+        engine.take_link(Point::new(1, 1), &[player]).unwrap();
+        let monster = engine.world().storage::<Name>().unwrap().iter().find(|&(entity, _)| entity != player).unwrap().0;
+        engine.world_mut().insert(player, Target(monster));
+
+        for trip in 0..2 {
+            engine.take_link(Point::new(5, 5), &[player]).unwrap();
+            assert!(engine.world().is_alive(monster));
+            assert_eq!(engine.world().get::<Name>(monster), None);
+            let newcomer = engine.world_mut().create_entity();
+            assert!(newcomer != monster);
+            engine.world_mut().delete_entity(newcomer);
+            engine.world_mut().resources_mut().insert(Gold(trip));
+            if trip == 1 {
+                let mut buffer = Vec::new();
+                engine.save(&mut buffer).unwrap();
+                engine = new_engine();
+                engine.load(&buffer[..]).unwrap();
+            }
+            engine.take_link(Point::new(1, 1), &[player]).unwrap();
+            assert_eq!(engine.world().get::<Target>(player), Some(&Target(monster)));
+            assert_eq!(*engine.world().resources().fetch::<Gold>(), Gold(trip));
+            assert!(engine.world().get::<Name>(monster).is_some_and(|name| name.0.starts_with("dungeon:1")));
+        }
+    }
+
+    #[test]
+    fn failed_change_leaves_levels_untouched() {
+        let mut engine = new_engine();
+        let (player, _) = descend(&mut engine);
+        let mut other = World::new();
+        other.persist_resource::<Regrown>("mystery");
+        other.resources_mut().insert(Regrown(1));
+        engine.levels_mut().frozen[0].snapshot = other.snapshot().unwrap();
+        let before = names(engine.world());
+        match engine.take_link(Point::new(1, 1), &[player]) {
+            Err(LevelError::Snapshot(_)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(names(engine.world()), before);
+        assert_eq!(engine.levels().current(), Some(&LevelId::new("dungeon", 2)));
+        assert!(engine.levels().is_generated(&LevelId::new("dungeon", 1)));
+
+        let mut fresh = new_engine();
+        let stray = fresh.world_mut().create_entity();
+        let player = fresh.world_mut().create_entity();
+        match fresh.change_level(&LevelId::new("dungeon", 1), &[player], None) {
+            Err(LevelError::Unplaced(1)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(fresh.world().is_alive(stray) && fresh.levels().current().is_none());
+    }
+
+    #[test]
+    fn load_checks_frozen_levels() {
+        let mut engine = new_engine();
+        descend(&mut engine);
+        let mut other = World::new();
+        other.persist_resource::<Regrown>("mystery");
+        other.resources_mut().insert(Regrown(1));
+        engine.levels_mut().frozen[0].snapshot = other.snapshot().unwrap();
+        let mut buffer = Vec::new();
+        engine.save(&mut buffer).unwrap();
+
+        let mut loaded = new_engine();
+        let player = loaded.world_mut().create_entity();
+        match loaded.load(&buffer[..]) {
+            Err(SaveError::Snapshot(SnapshotError::UnknownResource(ref name))) if name == "mystery" => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(loaded.world().is_alive(player));
+        assert!(loaded.levels().current().is_none());
+    }
+
+    #[test]
+    fn levels_survive_saves() {
+        let mut engine = new_engine();
+        let (player, first) = descend(&mut engine);
+        let mut buffer = Vec::new();
+        engine.save(&mut buffer).unwrap();
+
+        let mut loaded = new_engine();
+        loaded.load(&buffer[..]).unwrap();
+        assert!(loaded.levels().is_generated(&LevelId::new("dungeon", 1)));
+        assert!(loaded.levels().link_at(&LevelId::new("dungeon", 2), Point::new(1, 1)).is_some());
+        loaded.take_link(Point::new(1, 1), &[player]).unwrap();
+        assert_eq!(names(loaded.world()), first);
+    }
+
+    #[test]
+    fn missing_builder_leaves_world_untouched() {
+        let mut engine = Engine::new(Progress);
+        let player = engine.world_mut().create_entity();
+        match engine.change_level(&LevelId::new("dungeon", 1), &[player], None) {
+            Err(LevelError::NoBuilder(ref level)) if level.depth == 1 => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(engine.world().is_alive(player));
+        match engine.take_link(Point::new(5, 5), &[player]) {
+            Err(LevelError::NoLink(_)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    fn arrived(_engine: &mut Engine<Progress>, result: Result<Vec<Entity>, LevelError>) -> Transition<Progress> {
+        match result {
+            Ok(ref travellers) if travellers.len() == 1 => Transition::Pop,
+            _ => Transition::Halt,
+        }
+    }
+
+    #[test]
+    fn loading_state_waits_for_render() {
+        let mut engine = new_engine();
+        let player = engine.world_mut().create_entity();
+        let mut state = LevelChange::new(LevelId::new("dungeon", 1), vec![player], arrived).arriving_at(Point::new(2, 2));
+        match state.update(&mut engine, 0.1) {
+            Transition::Continue => assert!(engine.levels().current().is_none()),
+            _ => panic!("Changed level before rendering"),
+        }
+        state.render(&mut engine);
+        match state.update(&mut engine, 0.1) {
+            Transition::Pop => assert_eq!(engine.levels().current(), Some(&LevelId::new("dungeon", 1))),
+            _ => panic!("Level change failed"),
+        }
+    }
+}
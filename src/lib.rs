@@ -17,17 +17,52 @@
 //! Event Framework
 //!
 
+pub extern crate oxide_ecs;
+pub extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
 mod app;
 mod data;
+mod dice;
 mod engine;
 mod event;
+mod fov;
+mod geometry;
+mod hex;
+mod level;
+mod map;
+mod mapgen;
+mod noise;
+mod path;
+mod random;
+mod save;
 mod services;
 mod state;
+mod table;
 mod version;
 
 pub use self::app::Application;
 pub use self::data::Data;
+pub use self::dice::{Dice, DiceError, DiceErrorKind, DiceRoll, DieRoll, RollPart};
 pub use self::engine::Engine;
 pub use self::event::Event;
+pub use self::fov::{Algorithm, MapMemory, Radius, Remembered, Sighting, Sightings, Transparency, Visibility};
+pub use self::geometry::{blast, circle, circle_outline, cone, projectile, Bresenham, Projectile, Supercover, SymmetricLine};
+pub use self::hex::{Cube, Hex, HexLayout};
+pub use self::level::{LevelBuilder, LevelChange, LevelChanged, LevelError, LevelId, LevelManager, LevelSimulation, Link, LinkKind};
+pub use self::map::{Chokepoint, DistanceMap, Grid, Layer, Neighborhood, Point, Position, Rect, Regions, SpatialIndex, Space, TileMap, Topology};
+pub use self::mapgen::{
+    connect, BspGenerator, CellularAutomata, Connectivity, Constraints, CorridorStyle, DistantStairs, DrunkardsWalk, GeneratedMap, Glyph,
+    MapGenerator, Maze, MazeAlgorithm, MetaBuilder, Model, Pipeline, PipelineSnapshot, Requirement, RoomRounding, SpawnPoint, Symmetry, Tile,
+    Vault, VaultError, VaultErrorKind, VaultLibrary, VaultStamp, WaveFunctionCollapse,
+};
+pub use self::noise::{normalize, threshold, DomainWarp, Fractal, FractalKind, Noise, Perlin, Simplex, ValueNoise, Worley, WorleyDistance, WorleyOutput};
+pub use self::path::{DijkstraMap, GridGraph, Heuristic, HexGraph, PathFinder, Pathable, DIAGONAL_COST, ORTHOGONAL_COST};
+pub use self::random::{GameRng, Random};
+pub use self::save::{AutosavePolicy, AutosaveTrigger, Migration, SaveError, SaveHeader, SaveSlots};
 pub use self::state::{State,Transition};
-pub use self::version::{Version, VERSION};
+pub use self::table::{Outcome, RandomTable, TableEntry, TableError, MAX_TABLE_DRAWS};
+pub use self::version::{Version, VersionError, VERSION};
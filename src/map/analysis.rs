@@ -0,0 +1,348 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Map Analysis
+//!
+//! Connectivity and layout analysis of grid maps, shared by the map generators and AI. Every
+//! analysis takes a predicate choosing the passable cells, so it works on any grid, and all but
+//! the classification of spaces take a [`Topology`], so they work on hex maps too.
+//!
+//! [`Topology`]: trait.Topology.html
+//!
+
+use super::{Grid, Point, Topology};
+use std::collections::VecDeque;
+
+/// Connected regions of passable cells
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Regions {
+    labels: Grid<Option<usize>>,
+    sizes: Vec<usize>,
+}
+
+impl Regions {
+    /// Label every passable cell with the index of its region, numbering regions in row order.
+    pub fn label<T, N: Topology + Copy, F: Fn(&T) -> bool>(grid: &Grid<T>, topology: N, passable: F) -> Regions {
+        let mut labels = grid.map(|_| None);
+        let mut sizes = Vec::new();
+        let mut stack = Vec::new();
+        for start in grid.points() {
+            if labels[start].is_some() || !passable(&grid[start]) {
+                continue;
+            }
+            let region = sizes.len();
+            let mut size = 0;
+            labels[start] = Some(region);
+            stack.push(start);
+            while let Some(point) = stack.pop() {
+                size += 1;
+                for next in grid.neighbors(point, topology) {
+                    if labels[next].is_none() && passable(&grid[next]) {
+                        labels[next] = Some(region);
+                        stack.push(next);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+        Regions { labels, sizes }
+    }
+    /// Region containing a point, if it is passable.
+    pub fn region(&self, point: Point) -> Option<usize> {
+        self.labels.get(point).and_then(|label| *label)
+    }
+    /// Region of every cell
+    pub fn labels(&self) -> &Grid<Option<usize>> {
+        &self.labels
+    }
+    /// Number of regions
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+    /// Number of cells in each region
+    pub fn sizes(&self) -> &[usize] {
+        &self.sizes
+    }
+    /// Region with the most cells, the first one on ties.
+    pub fn largest(&self) -> Option<usize> {
+        (0..self.sizes.len()).max_by_key(|&region| (self.sizes[region], usize::MAX - region))
+    }
+    /// Cells of a region in row order
+    pub fn members(&self, region: usize) -> impl Iterator<Item = Point> + '_ {
+        self.labels.iter().filter(move |&(_, label)| *label == Some(region)).map(|(point, _)| point)
+    }
+    /// Check if every passable cell can reach every other.
+    pub fn is_connected(&self) -> bool {
+        self.sizes.len() <= 1
+    }
+}
+
+/// Number of steps from the nearest of a set of starting cells
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DistanceMap {
+    distances: Grid<Option<u32>>,
+    farthest: Option<(Point, u32)>,
+}
+
+impl DistanceMap {
+    /// Walk out from `starts` through passable cells. Starts which are out of bounds or not
+    /// passable are ignored.
+    pub fn new<T, N: Topology + Copy, F: Fn(&T) -> bool>(grid: &Grid<T>, starts: &[Point], topology: N, passable: F) -> DistanceMap {
+        let mut distances: Grid<Option<u32>> = grid.map(|_| None);
+        let mut farthest = None;
+        let mut queue = VecDeque::new();
+        for &start in starts {
+            if grid.get(start).is_some_and(&passable) && distances[start].is_none() {
+                distances[start] = Some(0);
+                farthest = farthest.or(Some((start, 0)));
+                queue.push_back(start);
+            }
+        }
+        while let Some(point) = queue.pop_front() {
+            let distance = distances[point].unwrap_or(0);
+            if farthest.is_some_and(|(_, most)| distance > most) {
+                farthest = Some((point, distance));
+            }
+            for next in grid.neighbors(point, topology) {
+                if distances[next].is_none() && passable(&grid[next]) {
+                    distances[next] = Some(distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+        DistanceMap { distances, farthest }
+    }
+    /// Steps to a point, if it is reachable.
+    pub fn distance(&self, point: Point) -> Option<u32> {
+        self.distances.get(point).and_then(|distance| *distance)
+    }
+    /// Steps to every cell
+    pub fn distances(&self) -> &Grid<Option<u32>> {
+        &self.distances
+    }
+    /// Reachable cell the most steps away, the first one reached on ties.
+    pub fn farthest(&self) -> Option<(Point, u32)> {
+        self.farthest
+    }
+    /// Number of reachable cells
+    pub fn reachable(&self) -> usize {
+        self.distances.cells().iter().filter(|distance| distance.is_some()).count()
+    }
+}
+
+/// Passable cell which splits its region in two or more when blocked
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Chokepoint {
+    pub point: Point,
+    /// Cells cut off from the largest remaining part of the region while the chokepoint is blocked
+    pub separated: usize,
+}
+
+impl Chokepoint {
+    /// Find every articulation point of the passable cells, in row order.
+    ///
+    /// Every cell along a dead end corridor is one, so filter on `separated` to keep only those
+    /// guarding a sizeable area.
+    pub fn find<T, N: Topology, F: Fn(&T) -> bool>(grid: &Grid<T>, topology: N, passable: F) -> Vec<Chokepoint> {
+        let count = grid.cells().len();
+        let directions = topology.directions();
+        let mut order = vec![0; count];
+        let mut low = vec![0; count];
+        let mut size = vec![0; count];
+        let mut cuts = vec![0; count];
+        let mut cut_size = vec![0; count];
+        let mut cut_largest = vec![0; count];
+        let mut time = 0;
+        let mut found = Vec::new();
+        let mut stack: Vec<(Point, Option<Point>, usize)> = Vec::new();
+        let at = |point: Point| (point.y * grid.width() + point.x) as usize;
+        for root in grid.points() {
+            let index = at(root);
+            if order[index] != 0 || !passable(&grid[root]) {
+                continue;
+            }
+            time += 1;
+            order[index] = time;
+            low[index] = time;
+            size[index] = 1;
+            let mut component = vec![root];
+            stack.push((root, None, 0));
+            while let Some(top) = stack.len().checked_sub(1) {
+                let (point, parent, next) = stack[top];
+                let current = at(point);
+                if next < directions {
+                    stack[top].2 += 1;
+                    let neighbor = topology.neighbor(point, next);
+                    if Some(neighbor) == parent || !grid.get(neighbor).is_some_and(&passable) {
+                        continue;
+                    }
+                    let other = at(neighbor);
+                    if order[other] == 0 {
+                        time += 1;
+                        order[other] = time;
+                        low[other] = time;
+                        size[other] = 1;
+                        component.push(neighbor);
+                        stack.push((neighbor, Some(point), 0));
+                    } else {
+                        low[current] = low[current].min(order[other]);
+                    }
+                } else {
+                    stack.pop();
+                    if let Some(parent) = parent {
+                        let above = at(parent);
+                        low[above] = low[above].min(low[current]);
+                        size[above] += size[current];
+                        if low[current] >= order[above] {
+                            cuts[above] += 1;
+                            cut_size[above] += size[current];
+                            cut_largest[above] = cut_largest[above].max(size[current]);
+                        }
+                    }
+                }
+            }
+            let total = component.len();
+            for point in component {
+                let index = at(point);
+                let required = if point == root { 2 } else { 1 };
+                if cuts[index] >= required {
+                    let largest = cut_largest[index].max(total - 1 - cut_size[index]);
+                    found.push(Chokepoint {
+                        point,
+                        separated: total - 1 - largest,
+                    });
+                }
+            }
+        }
+        found.sort_by_key(|chokepoint| (chokepoint.point.y, chokepoint.point.x));
+        found
+    }
+}
+
+/// Role of a passable cell in the layout of a map
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Space {
+    /// Part of a 2x2 block of passable cells
+    Room,
+    /// Narrow passage with two orthogonal ways on
+    Corridor,
+    /// Narrow passage with three or four orthogonal ways on
+    Junction,
+    /// Narrow passage with at most one orthogonal way on
+    DeadEnd,
+}
+
+impl Space {
+    /// Classify every passable cell, leaving the others `None`.
+    ///
+    /// Doorways between a room and a corridor count as corridor.
+    pub fn classify<T, F: Fn(&T) -> bool>(grid: &Grid<T>, passable: F) -> Grid<Option<Space>> {
+        let open = grid.map(|cell| passable(cell));
+        let is_open = |point: Point| open.get(point).cloned().unwrap_or(false);
+        Grid::from_fn(open.width(), open.height(), |point| {
+            if !open[point] {
+                return None;
+            }
+            let in_block = [Point::new(0, 0), Point::new(-1, 0), Point::new(0, -1), Point::new(-1, -1)].iter().any(|&corner| {
+                let corner = point + corner;
+                is_open(corner) && is_open(corner + Point::new(1, 0)) && is_open(corner + Point::new(0, 1)) && is_open(corner + Point::new(1, 1))
+            });
+            if in_block {
+                return Some(Space::Room);
+            }
+            match open.neighbors4(point).filter(|&next| open[next]).count() {
+                0 | 1 => Some(Space::DeadEnd),
+                2 => Some(Space::Corridor),
+                _ => Some(Space::Junction),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::Neighborhood;
+
+    fn parse(rows: &[&str]) -> Grid<bool> {
+        let cells = rows.iter().flat_map(|row| row.chars().map(|c| c != '#')).collect();
+        Grid::from_vec(rows[0].len() as i32, rows.len() as i32, cells).unwrap()
+    }
+
+    fn open(cell: &bool) -> bool {
+        *cell
+    }
+
+    const ROOMS: [&str; 7] = [
+        "############",
+        "#...#####..#",
+        "#.......#..#",
+        "#...###.#..#",
+        "#####.#....#",
+        "#####.####.#",
+        "############",
+    ];
+
+    #[test]
+    fn labels_regions_and_distances() {
+        let grid = parse(&ROOMS);
+        let regions = Regions::label(&grid, Neighborhood::Four, open);
+        assert_eq!(regions.sizes(), &[25, 2]);
+        assert_eq!(regions.largest(), Some(0));
+        assert_eq!(regions.region(Point::new(5, 5)), Some(1));
+        assert_eq!(regions.region(Point::new(0, 0)), None);
+        assert!(!regions.is_connected());
+        assert_eq!(regions.members(1).collect::<Vec<_>>(), vec![Point::new(5, 4), Point::new(5, 5)]);
+        assert_eq!(grid.flood_fill(Point::new(1, 1), Neighborhood::Four, open).len(), 25);
+
+        let distances = DistanceMap::new(&grid, &[Point::new(1, 1)], Neighborhood::Four, open);
+        assert_eq!(distances.reachable(), 25);
+        assert_eq!(distances.distance(Point::new(7, 2)), Some(7));
+        assert_eq!(distances.distance(Point::new(5, 5)), None);
+        assert_eq!(distances.distance(Point::new(10, 5)), Some(13));
+        assert_eq!(distances.farthest(), Some((Point::new(10, 1), 15)));
+    }
+
+    #[test]
+    fn finds_chokepoints() {
+        let grid = parse(&ROOMS);
+        let chokepoints = Chokepoint::find(&grid, Neighborhood::Four, open);
+        let points: Vec<Point> = chokepoints.iter().map(|chokepoint| chokepoint.point).collect();
+        let expected: Vec<Point> = (3..8).map(|x| Point::new(x, 2)).chain(vec![Point::new(7, 3)]).chain((7..11).map(|x| Point::new(x, 4))).collect();
+        assert_eq!(points, expected);
+        let corridor = chokepoints.iter().find(|chokepoint| chokepoint.point == Point::new(6, 2)).unwrap();
+        assert_eq!(corridor.separated, 11);
+        let stub = chokepoints.iter().find(|chokepoint| chokepoint.point == Point::new(10, 4)).unwrap();
+        assert_eq!(stub.separated, 1);
+    }
+
+    #[test]
+    fn classifies_spaces() {
+        let spaces = Space::classify(&parse(&ROOMS), open);
+        assert_eq!(spaces[Point::new(0, 0)], None);
+        assert_eq!(spaces[Point::new(2, 2)], Some(Space::Room));
+        assert_eq!(spaces[Point::new(5, 2)], Some(Space::Corridor));
+        assert_eq!(spaces[Point::new(7, 4)], Some(Space::Corridor));
+        assert_eq!(spaces[Point::new(9, 4)], Some(Space::Room));
+        assert_eq!(spaces[Point::new(10, 5)], Some(Space::DeadEnd));
+        assert_eq!(spaces[Point::new(5, 4)], Some(Space::DeadEnd));
+        let rooms = Regions::label(&spaces, Neighborhood::Four, |space| *space == Some(Space::Room));
+        assert_eq!(rooms.len(), 2);
+    }
+}
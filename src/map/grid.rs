@@ -0,0 +1,310 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Two Dimensional Grid
+//!
+
+use super::{Point, Rect, Topology};
+use oxide_ecs::Persistent;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::ops::{Index, IndexMut};
+
+/// Set of neighbouring cells considered adjacent
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Neighborhood {
+    /// Orthogonal neighbours only
+    Four,
+    /// Orthogonal and diagonal neighbours
+    Eight,
+}
+
+const ORTHOGONAL: [Point; 4] = [Point::NORTH, Point::EAST, Point::SOUTH, Point::WEST];
+
+const ALL: [Point; 8] = [
+    Point::NORTH,
+    Point::EAST,
+    Point::SOUTH,
+    Point::WEST,
+    Point::NORTH_EAST,
+    Point::SOUTH_EAST,
+    Point::SOUTH_WEST,
+    Point::NORTH_WEST,
+];
+
+impl Neighborhood {
+    /// Offsets to each neighbour, orthogonal neighbours first.
+    pub fn offsets(self) -> &'static [Point] {
+        match self {
+            Neighborhood::Four => &ORTHOGONAL,
+            Neighborhood::Eight => &ALL,
+        }
+    }
+}
+
+/// Rectangular grid of cells stored row by row
+///
+/// Deserializing checks that the dimensions are not negative and match the number of cells.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "GridData<T>")]
+pub struct Grid<T> {
+    width: i32,
+    height: i32,
+    cells: Vec<T>,
+}
+
+/// Serialized form of a Grid, checked before it becomes one
+#[derive(Deserialize)]
+struct GridData<T> {
+    width: i32,
+    height: i32,
+    cells: Vec<T>,
+}
+
+impl<T> TryFrom<GridData<T>> for Grid<T> {
+    type Error = String;
+    fn try_from(data: GridData<T>) -> Result<Grid<T>, String> {
+        let (width, height, found) = (data.width, data.height, data.cells.len());
+        Grid::from_vec(width, height, data.cells)
+            .ok_or_else(|| format!("{}x{} grid cannot hold {} cells", width, height, found))
+    }
+}
+
+impl<T> Grid<T> {
+    /// Create a grid by calling `f` for every cell.
+    ///
+    /// # Panics
+    /// If either dimension is negative.
+    pub fn from_fn<F: FnMut(Point) -> T>(width: i32, height: i32, mut f: F) -> Grid<T> {
+        assert!(width >= 0 && height >= 0, "Grid dimensions must not be negative");
+        let cells = Rect::new(0, 0, width, height).points().map(&mut f).collect();
+        Grid { width, height, cells }
+    }
+    /// Create a grid from cells stored row by row, or `None` if the number of cells is wrong.
+    pub fn from_vec(width: i32, height: i32, cells: Vec<T>) -> Option<Grid<T>> {
+        if width >= 0 && height >= 0 && cells.len() as i64 == i64::from(width) * i64::from(height) {
+            Some(Grid { width, height, cells })
+        } else {
+            None
+        }
+    }
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    /// Rectangle covering the whole grid
+    pub fn bounds(&self) -> Rect {
+        Rect::new(0, 0, self.width, self.height)
+    }
+    pub fn in_bounds(&self, point: Point) -> bool {
+        point.x >= 0 && point.y >= 0 && point.x < self.width && point.y < self.height
+    }
+    /// Index of a cell in the row by row storage.
+    pub fn index(&self, point: Point) -> Option<usize> {
+        if self.in_bounds(point) {
+            Some((point.y * self.width + point.x) as usize)
+        } else {
+            None
+        }
+    }
+    /// Point of a cell from its index in the row by row storage.
+    ///
+    /// # Panics
+    /// If the grid is zero cells wide, since no index refers to one of its cells.
+    pub fn point(&self, index: usize) -> Point {
+        assert!(self.width > 0, "Grid with no columns has no cell points");
+        let index = index as i32;
+        Point::new(index % self.width, index / self.width)
+    }
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.index(point).map(|index| &self.cells[index])
+    }
+    pub fn get_mut(&mut self, point: Point) -> Option<&mut T> {
+        self.index(point).map(move |index| &mut self.cells[index])
+    }
+    /// Replace a cell, returning the previous value or `None` if the point is out of bounds.
+    pub fn set(&mut self, point: Point, value: T) -> Option<T> {
+        self.get_mut(point).map(|cell| ::std::mem::replace(cell, value))
+    }
+    /// Cells row by row
+    pub fn cells(&self) -> &[T] {
+        &self.cells
+    }
+    /// Cells row by row, mutably
+    pub fn cells_mut(&mut self) -> &mut [T] {
+        &mut self.cells
+    }
+    /// Every point in the grid, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        self.bounds().points()
+    }
+    /// Every cell with its point, row by row.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.points().zip(self.cells.iter())
+    }
+    /// Every cell with its point, mutably.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Point, &mut T)> {
+        self.bounds().points().zip(self.cells.iter_mut())
+    }
+    /// Cells within a rectangle, clipped to the grid.
+    pub fn region(&self, rect: Rect) -> impl Iterator<Item = (Point, &T)> {
+        let clipped = rect.intersection(&self.bounds());
+        clipped.points().map(move |point| (point, &self[point]))
+    }
+    /// In bounds neighbours of a point.
+    pub fn neighbors<N: Topology>(&self, point: Point, topology: N) -> impl Iterator<Item = Point> {
+        let bounds = self.bounds();
+        (0..topology.directions())
+            .map(move |direction| topology.neighbor(point, direction))
+            .filter(move |neighbor| bounds.contains(*neighbor))
+    }
+    /// Orthogonal in bounds neighbours of a point.
+    pub fn neighbors4(&self, point: Point) -> impl Iterator<Item = Point> {
+        self.neighbors(point, Neighborhood::Four)
+    }
+    /// Orthogonal and diagonal in bounds neighbours of a point.
+    pub fn neighbors8(&self, point: Point) -> impl Iterator<Item = Point> {
+        self.neighbors(point, Neighborhood::Eight)
+    }
+    /// Create a grid of the same size by transforming every cell.
+    pub fn map<U, F: FnMut(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+    /// Points reachable from `start` through cells accepted by `passable`, nearest first.
+    ///
+    /// Empty if `start` is out of bounds or not passable.
+    pub fn flood_fill<N: Topology + Copy, F: Fn(&T) -> bool>(&self, start: Point, topology: N, passable: F) -> Vec<Point> {
+        let mut filled = Vec::new();
+        let mut seen = vec![false; self.cells.len()];
+        match self.index(start) {
+            Some(index) if passable(&self.cells[index]) => seen[index] = true,
+            _ => return filled,
+        }
+        filled.push(start);
+        let mut next = 0;
+        while next < filled.len() {
+            let point = filled[next];
+            next += 1;
+            for neighbor in self.neighbors(point, topology) {
+                let index = (neighbor.y * self.width + neighbor.x) as usize;
+                if !seen[index] && passable(&self.cells[index]) {
+                    seen[index] = true;
+                    filled.push(neighbor);
+                }
+            }
+        }
+        filled
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    /// Create a grid with every cell set to `value`.
+    pub fn new(width: i32, height: i32, value: T) -> Grid<T> {
+        Grid::from_fn(width, height, |_| value.clone())
+    }
+    /// Set every cell to `value`.
+    pub fn fill(&mut self, value: T) {
+        for cell in &mut self.cells {
+            *cell = value.clone();
+        }
+    }
+    /// Set every cell within a rectangle to `value`, clipped to the grid.
+    pub fn fill_rect(&mut self, rect: Rect, value: T) {
+        for point in rect.intersection(&self.bounds()).points() {
+            self[point] = value.clone();
+        }
+    }
+}
+
+/// # Panics
+/// If the point is out of bounds.
+impl<T> Index<Point> for Grid<T> {
+    type Output = T;
+    fn index(&self, point: Point) -> &T {
+        match self.index(point) {
+            Some(index) => &self.cells[index],
+            None => panic!("Point {} is outside of {}x{} grid", point, self.width, self.height),
+        }
+    }
+}
+
+/// # Panics
+/// If the point is out of bounds.
+impl<T> IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut T {
+        match self.index(point) {
+            Some(index) => &mut self.cells[index],
+            None => panic!("Point {} is outside of {}x{} grid", point, self.width, self.height),
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> Persistent for Grid<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    #[test]
+    fn bounds_checked_access() {
+        let mut grid = Grid::new(4, 3, 0u8);
+        assert_eq!(grid.get(Point::new(3, 2)), Some(&0));
+        assert_eq!(grid.get(Point::new(4, 0)), None);
+        assert_eq!(grid.get(Point::new(0, -1)), None);
+        assert_eq!(grid.set(Point::new(1, 2), 5), Some(0));
+        assert_eq!(grid.set(Point::new(-1, 2), 5), None);
+        assert_eq!(grid[Point::new(1, 2)], 5);
+        assert_eq!(grid.point(grid.index(Point::new(1, 2)).unwrap()), Point::new(1, 2));
+        assert_eq!(grid.iter().filter(|&(_, v)| *v == 5).map(|(p, _)| p).collect::<Vec<_>>(), vec![Point::new(1, 2)]);
+        assert!(Grid::from_vec(2, 2, vec![1, 2, 3]).is_none());
+    }
+
+    #[test]
+    fn neighbors_and_regions() {
+        let mut grid = Grid::from_fn(5, 5, |p| p.x + p.y * 5);
+        assert_eq!(grid.neighbors4(Point::new(0, 0)).count(), 2);
+        assert_eq!(grid.neighbors8(Point::new(0, 0)).count(), 3);
+        assert_eq!(grid.neighbors8(Point::new(2, 2)).count(), 8);
+        assert_eq!(grid.region(Rect::new(3, 3, 5, 5)).map(|(_, v)| *v).collect::<Vec<_>>(), vec![18, 19, 23, 24]);
+        grid.fill_rect(Rect::new(-1, -1, 2, 2), -1);
+        assert_eq!(grid.cells().iter().filter(|v| **v == -1).count(), 1);
+        assert!(grid.map(|v| *v < 0)[Point::ZERO]);
+    }
+
+    #[test]
+    fn deserialize_checks_cells() {
+        let grid: Grid<u8> = serde_json::from_str(r#"{"width":2,"height":1,"cells":[1,2]}"#).unwrap();
+        assert_eq!(grid[Point::new(1, 0)], 2);
+        assert!(serde_json::from_str::<Grid<u8>>(r#"{"width":2,"height":2,"cells":[1,2]}"#).is_err());
+        assert!(serde_json::from_str::<Grid<u8>>(r#"{"width":-1,"height":-2,"cells":[1,2]}"#).is_err());
+        assert!(Grid::<u8>::from_vec(i32::MAX, 2, Vec::new()).is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn index_out_of_bounds_panics() {
+        let grid = Grid::new(2, 2, 0u8);
+        let _ = grid[Point::new(2, 0)];
+    }
+}
@@ -0,0 +1,32 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Grid Maps
+//!
+
+mod analysis;
+mod grid;
+mod point;
+mod spatial;
+mod tilemap;
+mod topology;
+
+pub use self::analysis::{Chokepoint, DistanceMap, Regions, Space};
+pub use self::grid::{Grid, Neighborhood};
+pub use self::point::{Point, Rect};
+pub use self::spatial::{Position, SpatialIndex};
+pub use self::tilemap::{Layer, TileMap};
+pub use self::topology::Topology;
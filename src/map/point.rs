@@ -0,0 +1,214 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Grid Coordinates
+//!
+
+use std::fmt::{Display, Formatter, Result};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// Grid Coordinate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const ZERO: Point = Point { x: 0, y: 0 };
+    pub const NORTH: Point = Point { x: 0, y: -1 };
+    pub const SOUTH: Point = Point { x: 0, y: 1 };
+    pub const EAST: Point = Point { x: 1, y: 0 };
+    pub const WEST: Point = Point { x: -1, y: 0 };
+    pub const NORTH_EAST: Point = Point { x: 1, y: -1 };
+    pub const NORTH_WEST: Point = Point { x: -1, y: -1 };
+    pub const SOUTH_EAST: Point = Point { x: 1, y: 1 };
+    pub const SOUTH_WEST: Point = Point { x: -1, y: 1 };
+
+    pub const fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+    /// Taxicab distance, counting orthogonal steps.
+    pub fn manhattan(self, other: Point) -> i32 {
+        (self.x - other.x).abs() + (self.y - other.y).abs()
+    }
+    /// Chessboard distance, counting steps when diagonals cost the same as orthogonals.
+    pub fn chebyshev(self, other: Point) -> i32 {
+        (self.x - other.x).abs().max((self.y - other.y).abs())
+    }
+    /// Squared straight line distance, widened so distant points cannot overflow.
+    pub fn distance_squared(self, other: Point) -> i64 {
+        let (dx, dy) = (i64::from(self.x) - i64::from(other.x), i64::from(self.y) - i64::from(other.y));
+        dx * dx + dy * dy
+    }
+    /// Straight line distance.
+    pub fn distance(self, other: Point) -> f32 {
+        (self.distance_squared(other) as f32).sqrt()
+    }
+    /// Unit step in each axis towards another point.
+    pub fn signum(self) -> Point {
+        Point::new(self.x.signum(), self.y.signum())
+    }
+}
+
+impl Display for Point {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+impl From<(i32, i32)> for Point {
+    fn from((x, y): (i32, i32)) -> Point {
+        Point { x, y }
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+    fn add(self, other: Point) -> Point {
+        Point::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl AddAssign for Point {
+    fn add_assign(&mut self, other: Point) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+impl Sub for Point {
+    type Output = Point;
+    fn sub(self, other: Point) -> Point {
+        Point::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl SubAssign for Point {
+    fn sub_assign(&mut self, other: Point) {
+        self.x -= other.x;
+        self.y -= other.y;
+    }
+}
+
+impl Mul<i32> for Point {
+    type Output = Point;
+    fn mul(self, scale: i32) -> Point {
+        Point::new(self.x * scale, self.y * scale)
+    }
+}
+
+impl Neg for Point {
+    type Output = Point;
+    fn neg(self) -> Point {
+        Point::new(-self.x, -self.y)
+    }
+}
+
+/// Axis aligned rectangle of grid cells
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect { x, y, width, height }
+    }
+    /// Rectangle spanning two corners, inclusive.
+    pub fn from_corners(a: Point, b: Point) -> Rect {
+        let (left, top) = (a.x.min(b.x), a.y.min(b.y));
+        Rect::new(left, top, (a.x - b.x).abs() + 1, (a.y - b.y).abs() + 1)
+    }
+    /// Top left cell
+    pub fn min(&self) -> Point {
+        Point::new(self.x, self.y)
+    }
+    /// Bottom right cell, inclusive
+    pub fn max(&self) -> Point {
+        Point::new(self.x + self.width - 1, self.y + self.height - 1)
+    }
+    pub fn center(&self) -> Point {
+        Point::new(self.x + self.width / 2, self.y + self.height / 2)
+    }
+    pub fn area(&self) -> i32 {
+        self.width.max(0) * self.height.max(0)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.x && point.y >= self.y && point.x < self.x + self.width && point.y < self.y + self.height
+    }
+    pub fn intersects(&self, other: &Rect) -> bool {
+        !self.intersection(other).is_empty()
+    }
+    /// Overlapping area, empty if the rectangles do not overlap.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let left = self.x.max(other.x);
+        let top = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect::new(left, top, (right - left).max(0), (bottom - top).max(0))
+    }
+    /// Grow the rectangle by `amount` cells on every side, or shrink it if negative.
+    pub fn inflate(&self, amount: i32) -> Rect {
+        Rect::new(self.x - amount, self.y - amount, self.width + amount * 2, self.height + amount * 2)
+    }
+    /// Every cell in the rectangle, row by row.
+    pub fn points(&self) -> impl Iterator<Item = Point> {
+        let rect = *self;
+        (rect.y..rect.y + rect.height.max(0)).flat_map(move |y| (rect.x..rect.x + rect.width.max(0)).map(move |x| Point::new(x, y)))
+    }
+    /// Cells along the edge of the rectangle.
+    pub fn perimeter(&self) -> impl Iterator<Item = Point> {
+        let rect = *self;
+        self.points()
+            .filter(move |p| p.x == rect.x || p.y == rect.y || p.x == rect.x + rect.width - 1 || p.y == rect.y + rect.height - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        let a = Point::new(1, 2);
+        let b = Point::new(4, -2);
+        assert_eq!(a.manhattan(b), 7);
+        assert_eq!(a.chebyshev(b), 4);
+        assert_eq!(a.distance_squared(b), 25);
+        assert_eq!(b - a, Point::new(3, -4));
+        assert_eq!((b - a).signum(), Point::SOUTH_EAST + Point::NORTH * 2);
+    }
+
+    #[test]
+    fn rectangles() {
+        let room = Rect::new(2, 3, 4, 3);
+        assert_eq!(room.max(), Point::new(5, 5));
+        assert_eq!(Rect::from_corners(Point::new(5, 5), Point::new(2, 3)), room);
+        assert!(room.contains(Point::new(5, 5)) && !room.contains(Point::new(6, 5)));
+        assert_eq!(room.points().count(), 12);
+        assert_eq!(room.perimeter().count(), 10);
+        assert_eq!(room.intersection(&Rect::new(4, 0, 10, 4)), Rect::new(4, 3, 2, 1));
+        assert!(!room.intersects(&Rect::new(6, 3, 2, 2)));
+        assert_eq!(room.inflate(1), Rect::new(1, 2, 6, 5));
+    }
+}
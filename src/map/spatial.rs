@@ -0,0 +1,226 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Spatial Index
+//!
+
+use super::{Point, Rect};
+use fov::Radius;
+use oxide_ecs::{Component, ComponentEvent, Entity, Persistent, World};
+use std::collections::{BTreeMap, HashMap};
+
+/// Grid position of an Entity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Position(pub Point);
+
+impl Component for Position {}
+
+impl Persistent for Position {}
+
+/// Entities indexed by their [`Position`]
+///
+/// Install the index into a World once, then call [`sync`] to apply Position changes. The Engine
+/// syncs after every update, so call it directly only when querying in the middle of one. The
+/// index is rebuilt from Positions rather than saved.
+///
+/// [`Position`]: struct.Position.html
+/// [`sync`]: #method.sync
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+    cells: BTreeMap<Point, Vec<Entity>>,
+    positions: HashMap<Entity, Point>,
+}
+
+impl SpatialIndex {
+    pub fn new() -> SpatialIndex {
+        SpatialIndex::default()
+    }
+    /// Track Position changes and add an index of every current Position to the World's Resources.
+    pub fn install(world: &mut World) {
+        world.track::<Position>();
+        world.storage_mut::<Position>().drain_changes().for_each(drop);
+        let mut index = SpatialIndex::new();
+        for (entity, position) in world.storage_mut::<Position>().iter() {
+            index.insert(entity, position.0);
+        }
+        world.resources_mut().insert(index);
+    }
+    /// Apply every Position change since the last sync to the World's index, if it has one.
+    pub fn sync(world: &mut World) {
+        let changes: Vec<ComponentEvent> = world.storage_mut::<Position>().drain_changes().collect();
+        if changes.is_empty() {
+            return;
+        }
+        if let Some(mut index) = world.resources().try_fetch_mut::<SpatialIndex>() {
+            for change in changes {
+                match change {
+                    ComponentEvent::Inserted(entity) | ComponentEvent::Modified(entity) => match world.get::<Position>(entity) {
+                        Some(position) => index.insert(entity, position.0),
+                        None => index.remove(entity),
+                    },
+                    ComponentEvent::Removed(entity) => index.remove(entity),
+                }
+            }
+        }
+    }
+    /// Place an Entity at a point, moving it if it was already indexed.
+    pub fn insert(&mut self, entity: Entity, point: Point) {
+        if let Some(previous) = self.positions.insert(entity, point) {
+            if previous == point {
+                return;
+            }
+            self.detach(entity, previous);
+        }
+        self.cells.entry(point).or_default().push(entity);
+    }
+    /// Remove an Entity from the index.
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(previous) = self.positions.remove(&entity) {
+            self.detach(entity, previous);
+        }
+    }
+    /// Entities at a point, in the order they arrived
+    pub fn at(&self, point: Point) -> &[Entity] {
+        self.cells.get(&point).map_or(&[], |entities| entities.as_slice())
+    }
+    pub fn is_occupied(&self, point: Point) -> bool {
+        self.cells.contains_key(&point)
+    }
+    pub fn position(&self, entity: Entity) -> Option<Point> {
+        self.positions.get(&entity).cloned()
+    }
+    /// Every indexed Entity within a rectangle, column by column.
+    pub fn in_rect(&self, rect: Rect) -> impl Iterator<Item = (Point, Entity)> + '_ {
+        self.in_bounds(rect.min(), rect.max())
+    }
+    /// Every indexed Entity between two corners, inclusive, column by column.
+    ///
+    /// Narrow bounds are searched one column at a time; bounds spanning more columns than there
+    /// are occupied cells walk the occupied cells instead.
+    fn in_bounds(&self, min: Point, max: Point) -> impl Iterator<Item = (Point, Entity)> + '_ {
+        let columns = match (self.cells.keys().next(), self.cells.keys().next_back()) {
+            (Some(first), Some(last)) if min.x <= max.x && min.y <= max.y => Some(min.x.max(first.x)..=max.x.min(last.x)),
+            _ => None,
+        };
+        let narrow = columns
+            .as_ref()
+            .is_some_and(|columns| i64::from(*columns.end()) - i64::from(*columns.start()) < self.cells.len() as i64);
+        let (columns, wide) = if narrow { (columns, None) } else { (None, columns) };
+        let narrow = columns
+            .into_iter()
+            .flatten()
+            .flat_map(move |x| self.cells.range(Point::new(x, min.y)..=Point::new(x, max.y)));
+        let wide = wide.into_iter().flat_map(move |columns| {
+            self.cells
+                .range(Point::new(*columns.start(), min.y)..=Point::new(*columns.end(), max.y))
+                .filter(move |(point, _)| point.y >= min.y && point.y <= max.y)
+        });
+        narrow
+            .chain(wide)
+            .flat_map(|(point, entities)| entities.iter().map(move |entity| (*point, *entity)))
+    }
+    /// Every indexed Entity within `radius` of `center`, using the field of view shapes.
+    pub fn in_radius(&self, center: Point, radius: Radius) -> impl Iterator<Item = (Point, Entity)> + '_ {
+        let (min, max) = match radius.range() {
+            Some(range) => (
+                Point::new(center.x.saturating_sub(range), center.y.saturating_sub(range)),
+                Point::new(center.x.saturating_add(range), center.y.saturating_add(range)),
+            ),
+            None => (Point::new(i32::MIN, i32::MIN), Point::new(i32::MAX, i32::MAX)),
+        };
+        self.in_bounds(min, max).filter(move |&(point, _)| radius.contains(point - center))
+    }
+    /// Number of indexed Entities
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+    fn detach(&mut self, entity: Entity, point: Point) {
+        let empty = match self.cells.get_mut(&point) {
+            Some(entities) => {
+                entities.retain(|other| *other != entity);
+                entities.is_empty()
+            }
+            None => false,
+        };
+        if empty {
+            self.cells.remove(&point);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_position_changes() {
+        let mut world = World::new();
+        let orc = world.create_entity();
+        world.insert(orc, Position(Point::new(1, 1)));
+        SpatialIndex::install(&mut world);
+        let goblin = world.create_entity();
+        let rat = world.create_entity();
+        world.insert(goblin, Position(Point::new(1, 1)));
+        world.insert(rat, Position(Point::new(8, 3)));
+        SpatialIndex::sync(&mut world);
+        assert_eq!(world.resources().fetch::<SpatialIndex>().at(Point::new(1, 1)), &[orc, goblin]);
+
+        world.get_mut::<Position>(orc).unwrap().0 = Point::new(2, 1);
+        world.delete_entity(goblin);
+        world.remove::<Position>(rat);
+        SpatialIndex::sync(&mut world);
+        let index = world.resources().fetch::<SpatialIndex>();
+        assert!(!index.is_occupied(Point::new(1, 1)));
+        assert_eq!(index.position(orc), Some(Point::new(2, 1)));
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn area_queries() {
+        let mut world = World::new();
+        let mut index = SpatialIndex::new();
+        let points = [Point::new(0, 0), Point::new(3, 0), Point::new(2, 2), Point::new(5, 5), Point::new(2, -1)];
+        let entities: Vec<Entity> = points
+            .iter()
+            .map(|point| {
+                let entity = world.create_entity();
+                index.insert(entity, *point);
+                entity
+            })
+            .collect();
+        let found: Vec<Entity> = index.in_rect(Rect::new(1, 0, 3, 3)).map(|(_, entity)| entity).collect();
+        assert_eq!(found, vec![entities[2], entities[1]]);
+        assert_eq!(index.in_radius(Point::new(2, 0), Radius::Diamond(2)).count(), 4);
+        assert_eq!(index.in_radius(Point::new(2, 0), Radius::Square(1)).count(), 2);
+        assert_eq!(index.in_radius(Point::ZERO, Radius::Unlimited).count(), 5);
+        assert_eq!(index.in_radius(Point::ZERO, Radius::Circle(i32::MAX)).count(), 5);
+        assert_eq!(index.in_radius(Point::new(2, 0), Radius::Diamond(i32::MAX)).count(), 5);
+
+        let edge = world.create_entity();
+        index.insert(edge, Point::new(i32::MAX, 0));
+        assert_eq!(index.in_radius(Point::ZERO, Radius::Unlimited).count(), 6);
+        assert_eq!(index.in_radius(Point::ZERO, Radius::Square(i32::MAX)).count(), 6);
+        let near: Vec<Entity> = index.in_radius(Point::new(i32::MAX - 1, 0), Radius::Circle(1)).map(|(_, entity)| entity).collect();
+        assert_eq!(near, vec![edge]);
+    }
+}
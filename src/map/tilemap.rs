@@ -0,0 +1,174 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Layered Tile Map
+//!
+
+use super::{Grid, Point, Rect};
+use oxide_ecs::Persistent;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Named overlay drawn above the terrain of a [`TileMap`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layer<T> {
+    name: String,
+    cells: Grid<Option<T>>,
+}
+
+impl<T> Layer<T> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn cells(&self) -> &Grid<Option<T>> {
+        &self.cells
+    }
+    pub fn cells_mut(&mut self) -> &mut Grid<Option<T>> {
+        &mut self.cells
+    }
+    pub fn get(&self, point: Point) -> Option<&T> {
+        self.cells.get(point).and_then(Option::as_ref)
+    }
+    /// Place a tile, returning the previous one.
+    pub fn set(&mut self, point: Point, tile: T) -> Option<T> {
+        self.cells.set(point, Some(tile)).and_then(|previous| previous)
+    }
+    /// Clear a tile, returning the previous one.
+    pub fn clear(&mut self, point: Point) -> Option<T> {
+        self.cells.set(point, None).and_then(|previous| previous)
+    }
+}
+
+/// Terrain grid with any number of named overlay layers
+///
+/// Overlays are ordered bottom to top in the order they were added.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileMap<T> {
+    terrain: Grid<T>,
+    layers: Vec<Layer<T>>,
+}
+
+impl<T> TileMap<T> {
+    /// Conventional name of the layer holding items lying on the floor
+    pub const ITEMS: &'static str = "items";
+    /// Conventional name of the layer holding cosmetic decals
+    pub const DECALS: &'static str = "decals";
+
+    /// Create a tile map over existing terrain with no overlays.
+    pub fn from_terrain(terrain: Grid<T>) -> TileMap<T> {
+        TileMap { terrain, layers: Vec::new() }
+    }
+    pub fn width(&self) -> i32 {
+        self.terrain.width()
+    }
+    pub fn height(&self) -> i32 {
+        self.terrain.height()
+    }
+    pub fn bounds(&self) -> Rect {
+        self.terrain.bounds()
+    }
+    pub fn in_bounds(&self, point: Point) -> bool {
+        self.terrain.in_bounds(point)
+    }
+    pub fn terrain(&self) -> &Grid<T> {
+        &self.terrain
+    }
+    pub fn terrain_mut(&mut self) -> &mut Grid<T> {
+        &mut self.terrain
+    }
+    /// Add an empty overlay on top of the existing ones, or return the existing overlay of that name.
+    pub fn add_layer(&mut self, name: &str) -> &mut Layer<T> {
+        let index = match self.layers.iter().position(|layer| layer.name == name) {
+            Some(index) => index,
+            None => {
+                let cells = Grid::from_fn(self.width(), self.height(), |_| None);
+                self.layers.push(Layer { name: name.to_owned(), cells });
+                self.layers.len() - 1
+            }
+        };
+        &mut self.layers[index]
+    }
+    pub fn layer(&self, name: &str) -> Option<&Layer<T>> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+    pub fn layer_mut(&mut self, name: &str) -> Option<&mut Layer<T>> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
+    }
+    pub fn remove_layer(&mut self, name: &str) -> Option<Layer<T>> {
+        let index = self.layers.iter().position(|layer| layer.name == name)?;
+        Some(self.layers.remove(index))
+    }
+    /// Overlays from bottom to top
+    pub fn layers(&self) -> &[Layer<T>] {
+        &self.layers
+    }
+    /// Topmost tile at a point, falling back to the terrain when no overlay covers it.
+    pub fn top(&self, point: Point) -> Option<&T> {
+        self.layers
+            .iter()
+            .rev()
+            .filter_map(|layer| layer.get(point))
+            .next()
+            .or_else(|| self.terrain.get(point))
+    }
+}
+
+impl<T: Clone> TileMap<T> {
+    /// Create a tile map with every terrain cell set to `terrain` and no overlays.
+    pub fn new(width: i32, height: i32, terrain: T) -> TileMap<T> {
+        TileMap::from_terrain(Grid::new(width, height, terrain))
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + 'static> Persistent for TileMap<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxide_ecs::World;
+    use serde_json;
+
+    #[test]
+    fn layers_stack_over_terrain() {
+        let mut map = TileMap::new(3, 3, '.');
+        map.add_layer(TileMap::<char>::DECALS).set(Point::new(1, 1), '~');
+        map.add_layer(TileMap::<char>::ITEMS).set(Point::new(1, 1), '!');
+        assert_eq!(map.top(Point::new(1, 1)), Some(&'!'));
+        assert_eq!(map.layer_mut(TileMap::<char>::ITEMS).unwrap().clear(Point::new(1, 1)), Some('!'));
+        assert_eq!(map.top(Point::new(1, 1)), Some(&'~'));
+        assert_eq!(map.top(Point::new(0, 0)), Some(&'.'));
+        assert_eq!(map.top(Point::new(3, 0)), None);
+        assert_eq!(map.add_layer(TileMap::<char>::DECALS).get(Point::new(1, 1)), Some(&'~'));
+        assert_eq!(map.layers().len(), 2);
+        assert!(map.remove_layer(TileMap::<char>::DECALS).is_some());
+        assert_eq!(map.layers().len(), 1);
+    }
+
+    #[test]
+    fn persists_as_world_resource() {
+        let mut world = World::new();
+        world.persist_resource::<TileMap<char>>("map");
+        let mut map = TileMap::new(2, 1, '#');
+        map.add_layer(TileMap::<char>::ITEMS).set(Point::new(1, 0), '$');
+        world.resources_mut().insert(map.clone());
+
+        let json = serde_json::to_string(&world.snapshot().unwrap()).unwrap();
+        let mut restored = World::new();
+        restored.persist_resource::<TileMap<char>>("map");
+        restored.restore(serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(*restored.resources().fetch::<TileMap<char>>(), map);
+    }
+}
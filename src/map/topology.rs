@@ -0,0 +1,51 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Map Topology
+//!
+
+use super::{Neighborhood, Point};
+
+/// Adjacency between the cells of a map addressed by grid Points
+///
+/// Square maps use a [`Neighborhood`], while hexagonal maps stored in offset coordinates use a
+/// [`HexLayout`]. Algorithms taking a topology work on either.
+///
+/// [`Neighborhood`]: enum.Neighborhood.html
+/// [`HexLayout`]: enum.HexLayout.html
+pub trait Topology {
+    /// Number of neighbours of every cell, ignoring the edges of the map
+    fn directions(&self) -> usize;
+    /// Neighbour of `point` in a direction from 0 to `directions() - 1`.
+    fn neighbor(&self, point: Point, direction: usize) -> Point;
+    /// Fewest steps between two points
+    fn distance(&self, from: Point, to: Point) -> i32;
+}
+
+impl Topology for Neighborhood {
+    fn directions(&self) -> usize {
+        self.offsets().len()
+    }
+    fn neighbor(&self, point: Point, direction: usize) -> Point {
+        point + self.offsets()[direction]
+    }
+    fn distance(&self, from: Point, to: Point) -> i32 {
+        match *self {
+            Neighborhood::Four => from.manhattan(to),
+            Neighborhood::Eight => from.chebyshev(to),
+        }
+    }
+}
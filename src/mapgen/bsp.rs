@@ -0,0 +1,332 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Binary Space Partition Rooms and Corridors
+//!
+
+use super::{GeneratedMap, MapGenerator, Tile};
+use map::{Grid, Point, Rect};
+use rand::Rng;
+use random::GameRng;
+use std::collections::VecDeque;
+
+/// Shape of the corridors joining rooms
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CorridorStyle {
+    /// One horizontal and one vertical leg, turning once
+    LShaped,
+    /// A single straight run where the rooms overlap, falling back to L-shaped where they do not
+    Straight,
+    /// A meandering walk that drifts towards the other room
+    Winding,
+}
+
+/// Rooms in the leaves of a binary space partition, joined along the partition tree
+///
+/// The map is split recursively until every partition is small enough for the largest room,
+/// a room is placed in each partition, and sibling partitions are joined by a corridor. Every
+/// room is therefore reachable from every other. The spawn point is the centre of the first
+/// room and the stairs the centre of the room the most corridors away from it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BspGenerator {
+    min_room: i32,
+    max_room: i32,
+    corridors: CorridorStyle,
+    door_chance: f32,
+}
+
+impl BspGenerator {
+    /// Create a generator of rooms 4 to 10 tiles across joined by L-shaped corridors, with doors
+    /// wherever they fit.
+    pub fn new() -> BspGenerator {
+        BspGenerator {
+            min_room: 4,
+            max_room: 10,
+            corridors: CorridorStyle::LShaped,
+            door_chance: 1.0,
+        }
+    }
+    /// Limit the width and height of rooms, in floor tiles.
+    ///
+    /// # Panics
+    /// If `min` is less than 1 or greater than `max`.
+    pub fn room_size(mut self, min: i32, max: i32) -> Self {
+        assert!(min >= 1 && min <= max, "Invalid room size range {}..{}", min, max);
+        self.min_room = min;
+        self.max_room = max;
+        self
+    }
+    pub fn corridors(mut self, style: CorridorStyle) -> Self {
+        self.corridors = style;
+        self
+    }
+    /// Chance, from 0 to 1, of placing a door where a corridor enters a room.
+    pub fn door_chance(mut self, chance: f32) -> Self {
+        self.door_chance = chance;
+        self
+    }
+
+    /// Split `area` recursively, returning the indices of the rooms placed within it.
+    fn partition(&self, map: &mut GeneratedMap, area: Rect, rng: &mut GameRng) -> Vec<usize> {
+        let min_leaf = self.min_room + 2;
+        let max_leaf = self.max_room + 2;
+        let can_split_x = area.width >= 2 * min_leaf;
+        let can_split_y = area.height >= 2 * min_leaf;
+        let wants_split = area.width > max_leaf || area.height > max_leaf;
+        if !wants_split || !(can_split_x || can_split_y) {
+            return self.place_room(map, area, rng).into_iter().collect();
+        }
+        let vertical = match (can_split_x, can_split_y) {
+            (true, false) => true,
+            (false, true) => false,
+            _ if area.width * 4 > area.height * 5 => true,
+            _ if area.height * 4 > area.width * 5 => false,
+            _ => rng.gen(),
+        };
+        let (first, second) = if vertical {
+            let split = rng.gen_range(min_leaf, area.width - min_leaf + 1);
+            (
+                Rect::new(area.x, area.y, split, area.height),
+                Rect::new(area.x + split, area.y, area.width - split, area.height),
+            )
+        } else {
+            let split = rng.gen_range(min_leaf, area.height - min_leaf + 1);
+            (
+                Rect::new(area.x, area.y, area.width, split),
+                Rect::new(area.x, area.y + split, area.width, area.height - split),
+            )
+        };
+        let mut left = self.partition(map, first, rng);
+        let right = self.partition(map, second, rng);
+        if let (Some(&a), Some(&b)) = (rng.choose(&left), rng.choose(&right)) {
+            let (from, to) = (map.rooms[a], map.rooms[b]);
+            self.connect(&mut map.tiles, from, to, rng);
+            map.connections.push((a, b));
+        }
+        left.extend(right);
+        left
+    }
+
+    /// Place a room inside a leaf, leaving at least one wall between it and the leaf's edge.
+    fn place_room(&self, map: &mut GeneratedMap, leaf: Rect, rng: &mut GameRng) -> Option<usize> {
+        let max_width = self.max_room.min(leaf.width - 2);
+        let max_height = self.max_room.min(leaf.height - 2);
+        if max_width < self.min_room || max_height < self.min_room {
+            return None;
+        }
+        let width = rng.gen_range(self.min_room, max_width + 1);
+        let height = rng.gen_range(self.min_room, max_height + 1);
+        let x = rng.gen_range(leaf.x + 1, leaf.x + leaf.width - width);
+        let y = rng.gen_range(leaf.y + 1, leaf.y + leaf.height - height);
+        let room = Rect::new(x, y, width, height);
+        map.tiles.fill_rect(room, Tile::Floor);
+        map.rooms.push(room);
+        Some(map.rooms.len() - 1)
+    }
+
+    fn connect(&self, tiles: &mut Grid<Tile>, from: Rect, to: Rect, rng: &mut GameRng) {
+        let start = random_point(from, rng);
+        let end = random_point(to, rng);
+        match self.corridors {
+            CorridorStyle::LShaped => l_shaped(tiles, start, end, rng.gen()),
+            CorridorStyle::Straight => {
+                let columns = from.intersection(&Rect::new(to.x, from.y, to.width, from.height));
+                let rows = from.intersection(&Rect::new(from.x, to.y, from.width, to.height));
+                if !columns.is_empty() {
+                    let x = rng.gen_range(columns.x, columns.x + columns.width);
+                    carve_line(tiles, Point::new(x, start.y), Point::new(x, end.y));
+                } else if !rows.is_empty() {
+                    let y = rng.gen_range(rows.y, rows.y + rows.height);
+                    carve_line(tiles, Point::new(start.x, y), Point::new(end.x, y));
+                } else {
+                    l_shaped(tiles, start, end, rng.gen());
+                }
+            }
+            CorridorStyle::Winding => winding(tiles, start, end, rng),
+        }
+    }
+
+    /// Turn corridor tiles entering a room between two walls into doors.
+    fn place_doors(&self, map: &mut GeneratedMap, rng: &mut GameRng) {
+        for room in &map.rooms {
+            let edge = room.inflate(1);
+            for point in edge.perimeter() {
+                let inward = if point.x == edge.x {
+                    Point::EAST
+                } else if point.x == edge.x + edge.width - 1 {
+                    Point::WEST
+                } else if point.y == edge.y {
+                    Point::SOUTH
+                } else {
+                    Point::NORTH
+                };
+                let corner = (point.x == edge.x || point.x == edge.x + edge.width - 1)
+                    && (point.y == edge.y || point.y == edge.y + edge.height - 1);
+                if corner || map.tiles.get(point) != Some(&Tile::Corridor) {
+                    continue;
+                }
+                let side = Point::new(inward.y, inward.x);
+                let framed = map.tiles.get(point + side) == Some(&Tile::Wall) && map.tiles.get(point - side) == Some(&Tile::Wall);
+                if framed && rng.next_f32() < self.door_chance {
+                    map.tiles[point] = Tile::Door;
+                    map.doors.push(point);
+                }
+            }
+        }
+    }
+
+    /// Pick the spawn room and the room the most connections away from it for the stairs.
+    fn place_stairs(&self, map: &mut GeneratedMap) {
+        let first = match map.rooms.first() {
+            Some(room) => *room,
+            None => return,
+        };
+        let mut hops = vec![None; map.rooms.len()];
+        let mut queue = VecDeque::new();
+        hops[0] = Some(0);
+        queue.push_back(0);
+        while let Some(room) = queue.pop_front() {
+            let next = hops[room].map_or(0, |hops| hops + 1);
+            for &(a, b) in &map.connections {
+                let other = if a == room { b } else if b == room { a } else { continue };
+                if hops[other].is_none() {
+                    hops[other] = Some(next);
+                    queue.push_back(other);
+                }
+            }
+        }
+        let farthest = (0..map.rooms.len()).max_by_key(|&room| hops[room].unwrap_or(0)).unwrap_or(0);
+        map.spawn = Some(first.center());
+        map.stairs = Some(map.rooms[farthest].center());
+    }
+}
+
+impl Default for BspGenerator {
+    fn default() -> BspGenerator {
+        BspGenerator::new()
+    }
+}
+
+impl MapGenerator for BspGenerator {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = GeneratedMap::new(width, height);
+        self.partition(&mut map, Rect::new(0, 0, width, height), rng);
+        self.place_doors(&mut map, rng);
+        self.place_stairs(&mut map);
+        map
+    }
+}
+
+fn random_point(rect: Rect, rng: &mut GameRng) -> Point {
+    Point::new(rng.gen_range(rect.x, rect.x + rect.width), rng.gen_range(rect.y, rect.y + rect.height))
+}
+
+fn carve(tiles: &mut Grid<Tile>, point: Point) {
+    if tiles.get(point) == Some(&Tile::Wall) {
+        tiles[point] = Tile::Corridor;
+    }
+}
+
+/// Carve a horizontal or vertical line, inclusive.
+fn carve_line(tiles: &mut Grid<Tile>, from: Point, to: Point) {
+    for point in Rect::from_corners(from, to).points() {
+        carve(tiles, point);
+    }
+}
+
+fn l_shaped(tiles: &mut Grid<Tile>, from: Point, to: Point, horizontal_first: bool) {
+    let corner = if horizontal_first { Point::new(to.x, from.y) } else { Point::new(from.x, to.y) };
+    carve_line(tiles, from, corner);
+    carve_line(tiles, corner, to);
+}
+
+/// Walk from `from` to `to`, usually stepping closer but sometimes wandering, never touching
+/// the edge of the map.
+fn winding(tiles: &mut Grid<Tile>, from: Point, to: Point, rng: &mut GameRng) {
+    let inner = tiles.bounds().inflate(-1);
+    let mut current = from;
+    carve(tiles, current);
+    while current != to {
+        let toward = (to - current).signum();
+        let step = if rng.gen_range(0, 10) < 7 {
+            match (toward.x, toward.y) {
+                (0, y) => Point::new(0, y),
+                (x, 0) => Point::new(x, 0),
+                (x, y) => if rng.gen() { Point::new(x, 0) } else { Point::new(0, y) },
+            }
+        } else {
+            *rng.choose(&[Point::NORTH, Point::SOUTH, Point::EAST, Point::WEST]).expect("Directions are not empty")
+        };
+        if inner.contains(current + step) {
+            current += step;
+            carve(tiles, current);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Number of walkable tiles reachable from `start`
+    fn reachable(tiles: &Grid<Tile>, start: Point) -> usize {
+        let mut seen = Grid::new(tiles.width(), tiles.height(), false);
+        let mut stack = vec![start];
+        seen[start] = true;
+        let mut count = 0;
+        while let Some(point) = stack.pop() {
+            count += 1;
+            for next in tiles.neighbors4(point) {
+                if tiles[next].is_walkable() && !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn rooms_are_connected() {
+        for &style in [CorridorStyle::LShaped, CorridorStyle::Straight, CorridorStyle::Winding].iter() {
+            for seed in 0..10 {
+                let generator = BspGenerator::new().corridors(style).room_size(3, 8);
+                let map = generator.generate(80, 40, &mut GameRng::from_seed(seed));
+                assert!(map.rooms.len() >= 4, "{:?} seed {}", style, seed);
+                assert_eq!(map.connections.len(), map.rooms.len() - 1);
+                for room in &map.rooms {
+                    assert!(room.width >= 3 && room.width <= 8 && room.height >= 3 && room.height <= 8);
+                }
+                let walkable = map.tiles.cells().iter().filter(|tile| tile.is_walkable()).count();
+                assert_eq!(reachable(&map.tiles, map.spawn.unwrap()), walkable, "{:?} seed {}", style, seed);
+                assert!(map.tiles.bounds().perimeter().all(|point| map.tiles[point] == Tile::Wall));
+                assert!(map.doors.iter().all(|door| map.tiles[*door] == Tile::Door));
+                assert!(map.stairs.is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_same_map() {
+        let generator = BspGenerator::new().corridors(CorridorStyle::Winding).door_chance(0.5);
+        let a = generator.generate(60, 30, &mut GameRng::from_seed(41));
+        let b = generator.generate(60, 30, &mut GameRng::from_seed(41));
+        let c = generator.generate(60, 30, &mut GameRng::from_seed(42));
+        assert_eq!(a, b);
+        assert!(a != c);
+    }
+}
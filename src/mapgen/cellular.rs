@@ -0,0 +1,126 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Cellular Automata Caves
+//!
+
+use super::connect::{connect, place_spawn_and_stairs};
+use super::{Connectivity, GeneratedMap, MapGenerator, Tile};
+use map::{Grid, Point};
+use rand::Rng;
+use random::GameRng;
+
+/// Caves grown from random noise by repeatedly applying birth and survival rules
+///
+/// Rules count the walls among a tile's eight neighbours, with tiles beyond the edge of the map
+/// counting as walls. A floor tile becomes wall when its count is a birth count, and a wall tile
+/// stays wall when its count is a survival count. The defaults, B5678/S45678, smooth noise into
+/// open caverns.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellularAutomata {
+    fill: f32,
+    birth: [bool; 9],
+    survival: [bool; 9],
+    iterations: u32,
+    connectivity: Connectivity,
+}
+
+impl CellularAutomata {
+    pub fn new() -> CellularAutomata {
+        CellularAutomata {
+            fill: 0.45,
+            birth: counts(&[5, 6, 7, 8]),
+            survival: counts(&[4, 5, 6, 7, 8]),
+            iterations: 4,
+            connectivity: Connectivity::RemovePockets,
+        }
+    }
+    /// Chance, from 0 to 1, of each tile starting as wall.
+    pub fn fill(mut self, fill: f32) -> Self {
+        self.fill = fill;
+        self
+    }
+    /// Set the wall counts at which floor turns to wall and wall remains wall.
+    ///
+    /// # Panics
+    /// If a count is greater than 8.
+    pub fn rules(mut self, birth: &[u8], survival: &[u8]) -> Self {
+        self.birth = counts(birth);
+        self.survival = counts(survival);
+        self
+    }
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.iterations = iterations;
+        self
+    }
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+
+    fn step(&self, tiles: &Grid<Tile>) -> Grid<Tile> {
+        let inner = tiles.bounds().inflate(-1);
+        Grid::from_fn(tiles.width(), tiles.height(), |point| {
+            if !inner.contains(point) {
+                return Tile::Wall;
+            }
+            let walls = [-1, 0, 1]
+                .iter()
+                .flat_map(|&dy| [-1, 0, 1].iter().map(move |&dx| Point::new(dx, dy)))
+                .filter(|&offset| offset != Point::ZERO && tiles.get(point + offset).is_none_or(|tile| *tile == Tile::Wall))
+                .count();
+            let rule = if tiles[point] == Tile::Wall { &self.survival } else { &self.birth };
+            if rule[walls] {
+                Tile::Wall
+            } else {
+                Tile::Floor
+            }
+        })
+    }
+}
+
+impl Default for CellularAutomata {
+    fn default() -> CellularAutomata {
+        CellularAutomata::new()
+    }
+}
+
+impl MapGenerator for CellularAutomata {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = GeneratedMap::new(width, height);
+        let inner = map.tiles.bounds().inflate(-1);
+        for point in inner.points() {
+            if rng.next_f32() >= self.fill {
+                map.tiles[point] = Tile::Floor;
+            }
+        }
+        for _ in 0..self.iterations {
+            map.tiles = self.step(&map.tiles);
+        }
+        connect(&mut map.tiles, self.connectivity);
+        place_spawn_and_stairs(&mut map);
+        map
+    }
+}
+
+fn counts(values: &[u8]) -> [bool; 9] {
+    let mut counts = [false; 9];
+    for &value in values {
+        assert!(value <= 8, "Neighbour count {} is greater than 8", value);
+        counts[value as usize] = true;
+    }
+    counts
+}
@@ -0,0 +1,152 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Connectivity Post-Processing
+//!
+
+use super::{GeneratedMap, Tile};
+use map::{DistanceMap, Grid, Neighborhood, Point, Regions};
+use std::collections::VecDeque;
+
+/// How disconnected walkable regions are dealt with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Connectivity {
+    /// Keep the largest region and fill every other one with wall.
+    RemovePockets,
+    /// Dig the shortest tunnel from every other region to the largest one.
+    Tunnel,
+}
+
+/// Make every walkable tile reachable from every other by orthogonal steps.
+///
+/// Tunnels are dug as floor and never touch the edge of the map.
+pub fn connect(tiles: &mut Grid<Tile>, connectivity: Connectivity) {
+    let regions = walkable_regions(tiles);
+    let main = match regions.largest() {
+        Some(main) => main,
+        None => return,
+    };
+    match connectivity {
+        Connectivity::RemovePockets => {
+            for (point, label) in regions.labels().iter() {
+                if label.is_some() && *label != Some(main) {
+                    tiles[point] = Tile::Wall;
+                }
+            }
+        }
+        Connectivity::Tunnel => {
+            let mut connected = regions.labels().map(|label| *label == Some(main));
+            for region in (0..regions.len()).filter(|&region| region != main) {
+                let members: Vec<Point> = regions.members(region).collect();
+                tunnel(tiles, &mut connected, &members);
+            }
+        }
+    }
+}
+
+/// Orthogonally connected regions of walkable tiles
+pub(super) fn walkable_regions(tiles: &Grid<Tile>) -> Regions {
+    Regions::label(tiles, Neighborhood::Four, |tile| tile.is_walkable())
+}
+
+/// Dig from `members` to the nearest connected tile, marking them connected.
+fn tunnel(tiles: &mut Grid<Tile>, connected: &mut Grid<bool>, members: &[Point]) {
+    let inner = tiles.bounds().inflate(-1);
+    let mut parents: Grid<Option<Point>> = tiles.map(|_| None);
+    let mut queue = VecDeque::new();
+    for &member in members {
+        parents[member] = Some(member);
+        queue.push_back(member);
+    }
+    while let Some(point) = queue.pop_front() {
+        if connected[point] {
+            let mut current = point;
+            while let Some(parent) = parents[current].filter(|parent| *parent != current) {
+                if !tiles[parent].is_walkable() {
+                    tiles[parent] = Tile::Floor;
+                }
+                connected[parent] = true;
+                current = parent;
+            }
+            break;
+        }
+        for next in tiles.neighbors4(point) {
+            if inner.contains(next) && parents[next].is_none() {
+                parents[next] = Some(point);
+                queue.push_back(next);
+            }
+        }
+    }
+    for &member in members {
+        connected[member] = true;
+    }
+}
+
+/// Spawn at the walkable tile nearest the centre and put the stairs as far away as possible.
+pub(super) fn place_spawn_and_stairs(map: &mut GeneratedMap) {
+    let center = map.tiles.bounds().center();
+    map.spawn = nearest_walkable(&map.tiles, center);
+    map.stairs = map.spawn.and_then(|spawn| farthest_walkable(&map.tiles, spawn));
+}
+
+/// Walkable tile closest in a straight line to `target`
+pub(super) fn nearest_walkable(tiles: &Grid<Tile>, target: Point) -> Option<Point> {
+    tiles
+        .iter()
+        .filter(|&(_, tile)| tile.is_walkable())
+        .map(|(point, _)| point)
+        .min_by_key(|point| point.distance_squared(target))
+}
+
+/// Walkable tile taking the most orthogonal steps to reach from `start`
+pub(super) fn farthest_walkable(tiles: &Grid<Tile>, start: Point) -> Option<Point> {
+    DistanceMap::new(tiles, &[start], Neighborhood::Four, |tile| tile.is_walkable())
+        .farthest()
+        .map(|(point, _)| point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rows: &[&str]) -> Grid<Tile> {
+        let cells = rows.iter().flat_map(|row| row.chars().map(|c| if c == '#' { Tile::Wall } else { Tile::Floor })).collect();
+        Grid::from_vec(rows[0].len() as i32, rows.len() as i32, cells).unwrap()
+    }
+
+    const POCKETS: [&str; 5] = [
+        "##########",
+        "#...#..#.#",
+        "#...#..###",
+        "#...####.#",
+        "##########",
+    ];
+
+    #[test]
+    fn pockets_are_removed() {
+        let mut tiles = parse(&POCKETS);
+        connect(&mut tiles, Connectivity::RemovePockets);
+        assert_eq!(walkable_regions(&tiles).sizes(), &[9]);
+    }
+
+    #[test]
+    fn pockets_are_tunnelled() {
+        let mut tiles = parse(&POCKETS);
+        connect(&mut tiles, Connectivity::Tunnel);
+        assert_eq!(walkable_regions(&tiles).sizes(), &[9 + 4 + 1 + 1 + 3]);
+        assert!(tiles.bounds().perimeter().all(|point| tiles[point] == Tile::Wall));
+    }
+}
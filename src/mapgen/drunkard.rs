@@ -0,0 +1,115 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Drunkard's Walk Caves
+//!
+
+use super::connect::{connect, place_spawn_and_stairs};
+use super::{Connectivity, GeneratedMap, MapGenerator, Tile};
+use map::Point;
+use rand::Rng;
+use random::GameRng;
+
+/// Caves dug by random walkers until enough of the map is floor
+///
+/// The first walker starts in the centre of the map. Each later walker starts either there or
+/// on a random floor tile already dug, and digs for a fixed number of steps without touching
+/// the edge of the map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DrunkardsWalk {
+    floor: f32,
+    lifetime: u32,
+    from_center: bool,
+    connectivity: Connectivity,
+}
+
+impl DrunkardsWalk {
+    /// Maximum number of walkers, bounding the work on maps that cannot reach the floor target
+    const MAX_WALKERS: u32 = 10_000;
+
+    pub fn new() -> DrunkardsWalk {
+        DrunkardsWalk {
+            floor: 0.4,
+            lifetime: 400,
+            from_center: false,
+            connectivity: Connectivity::RemovePockets,
+        }
+    }
+    /// Fraction of the map, from 0 to 1, to dig before stopping.
+    pub fn floor(mut self, floor: f32) -> Self {
+        self.floor = floor;
+        self
+    }
+    /// Number of steps each walker takes.
+    pub fn lifetime(mut self, lifetime: u32) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+    /// Start every walker in the centre, producing a denser cave around it.
+    pub fn from_center(mut self, from_center: bool) -> Self {
+        self.from_center = from_center;
+        self
+    }
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+}
+
+impl Default for DrunkardsWalk {
+    fn default() -> DrunkardsWalk {
+        DrunkardsWalk::new()
+    }
+}
+
+impl MapGenerator for DrunkardsWalk {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = GeneratedMap::new(width, height);
+        let inner = map.tiles.bounds().inflate(-1);
+        if inner.is_empty() {
+            return map;
+        }
+        let target = (self.floor * inner.area() as f32) as usize;
+        let mut floors = vec![inner.center()];
+        map.tiles[inner.center()] = Tile::Floor;
+        let mut walkers = 0;
+        while floors.len() < target && walkers < DrunkardsWalk::MAX_WALKERS {
+            walkers += 1;
+            let mut current = if self.from_center {
+                inner.center()
+            } else {
+                *rng.choose(&floors).expect("Floors are not empty")
+            };
+            for _ in 0..self.lifetime {
+                let step = *rng.choose(&[Point::NORTH, Point::SOUTH, Point::EAST, Point::WEST]).expect("Directions are not empty");
+                if !inner.contains(current + step) {
+                    continue;
+                }
+                current += step;
+                if map.tiles[current] == Tile::Wall {
+                    map.tiles[current] = Tile::Floor;
+                    floors.push(current);
+                    if floors.len() >= target {
+                        break;
+                    }
+                }
+            }
+        }
+        connect(&mut map.tiles, self.connectivity);
+        place_spawn_and_stairs(&mut map);
+        map
+    }
+}
@@ -0,0 +1,344 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Mazes
+//!
+
+use super::connect::{connect, place_spawn_and_stairs};
+use super::meta::tidy;
+use super::{Connectivity, GeneratedMap, MapGenerator, MetaBuilder, Tile};
+use map::{Grid, Point, Rect};
+use rand::Rng;
+use random::GameRng;
+
+const STEPS: [Point; 4] = [Point::NORTH, Point::EAST, Point::SOUTH, Point::WEST];
+
+/// Algorithm used to carve a perfect maze, in which every cell is reachable by exactly one path
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MazeAlgorithm {
+    /// Depth first search. Long, winding passages with few branches.
+    RecursiveBacktracker,
+    /// Randomized Prim's. Many short dead ends radiating from the start.
+    Prim,
+    /// Randomized Kruskal's. Evenly spread branches.
+    Kruskal,
+    /// Eller's, built one row at a time.
+    Eller,
+    /// Loop-erased random walks. Chosen uniformly from every possible maze.
+    Wilson,
+}
+
+/// Maze of one tile wide passages
+///
+/// Cells sit on odd coordinates within the area being carved, with walls in between. Braiding
+/// opens a wall at a fraction of the dead ends, turning the perfect maze into one with loops.
+///
+/// As a meta-builder the maze replaces everything in its area, rooms included, and is tunnelled
+/// into the rest of the map.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Maze {
+    algorithm: MazeAlgorithm,
+    braid: f32,
+    area: Option<Rect>,
+}
+
+impl Maze {
+    pub fn new(algorithm: MazeAlgorithm) -> Maze {
+        Maze {
+            algorithm,
+            braid: 0.0,
+            area: None,
+        }
+    }
+    /// Fraction, from 0 to 1, of dead ends to remove.
+    pub fn braid(mut self, braid: f32) -> Self {
+        self.braid = braid;
+        self
+    }
+    /// Carve only the tiles within `area`, whose edge is left as wall, rather than the whole map.
+    pub fn within(mut self, area: Rect) -> Self {
+        self.area = Some(area);
+        self
+    }
+    /// Fill `area` with wall and carve the maze into it.
+    pub fn carve(&self, tiles: &mut Grid<Tile>, area: Rect, rng: &mut GameRng) {
+        let area = area.intersection(&tiles.bounds());
+        tiles.fill_rect(area, Tile::Wall);
+        let cells = Rect::new(0, 0, (area.width - 1) / 2, (area.height - 1) / 2);
+        if cells.is_empty() {
+            return;
+        }
+        let passages = match self.algorithm {
+            MazeAlgorithm::RecursiveBacktracker => backtracker(cells, rng),
+            MazeAlgorithm::Prim => prim(cells, rng),
+            MazeAlgorithm::Kruskal => kruskal(cells, rng),
+            MazeAlgorithm::Eller => eller(cells, rng),
+            MazeAlgorithm::Wilson => wilson(cells, rng),
+        };
+        let tile = |cell: Point| Point::new(area.x + 1 + cell.x * 2, area.y + 1 + cell.y * 2);
+        for cell in cells.points() {
+            tiles.set(tile(cell), Tile::Corridor);
+        }
+        for (a, b) in passages {
+            tiles.set(tile(a) + (b - a), Tile::Corridor);
+        }
+        self.braid_dead_ends(tiles, cells, &tile, rng);
+    }
+
+    fn braid_dead_ends(&self, tiles: &mut Grid<Tile>, cells: Rect, tile: &dyn Fn(Point) -> Point, rng: &mut GameRng) {
+        let openings = |tiles: &Grid<Tile>, cell: Point| STEPS.iter().filter(|&&step| tiles[tile(cell) + step].is_walkable()).count();
+        let mut dead_ends: Vec<Point> = cells.points().filter(|&cell| openings(tiles, cell) == 1).collect();
+        rng.shuffle(&mut dead_ends);
+        let count = (dead_ends.len() as f32 * self.braid.clamp(0.0, 1.0)).round() as usize;
+        for &cell in &dead_ends[..count] {
+            if openings(tiles, cell) != 1 {
+                continue;
+            }
+            let closed: Vec<Point> = STEPS
+                .iter()
+                .map(|&step| cell + step)
+                .filter(|&next| cells.contains(next) && !tiles[tile(cell) + (next - cell)].is_walkable())
+                .collect();
+            let preferred: Vec<Point> = closed.iter().cloned().filter(|&next| openings(tiles, next) == 1).collect();
+            let choices = if preferred.is_empty() { &closed } else { &preferred };
+            if let Some(&next) = rng.choose(choices) {
+                tiles.set(tile(cell) + (next - cell), Tile::Corridor);
+            }
+        }
+    }
+}
+
+impl MapGenerator for Maze {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = GeneratedMap::new(width, height);
+        let area = self.area.unwrap_or_else(|| map.tiles.bounds());
+        self.carve(&mut map.tiles, area, rng);
+        place_spawn_and_stairs(&mut map);
+        map
+    }
+}
+
+impl MetaBuilder for Maze {
+    fn apply(&self, map: &mut GeneratedMap, rng: &mut GameRng) {
+        let area = self.area.unwrap_or_else(|| map.tiles.bounds());
+        self.carve(&mut map.tiles, area, rng);
+        let rooms = map.rooms.len();
+        map.rooms.retain(|room| !room.intersects(&area));
+        if map.rooms.len() != rooms {
+            map.connections.clear();
+        }
+        connect(&mut map.tiles, Connectivity::Tunnel);
+        tidy(map);
+    }
+}
+
+fn neighbors(cells: Rect, cell: Point) -> impl Iterator<Item = Point> {
+    STEPS.iter().map(move |&step| cell + step).filter(move |&next| cells.contains(next))
+}
+
+fn backtracker(cells: Rect, rng: &mut GameRng) -> Vec<(Point, Point)> {
+    let mut passages = Vec::new();
+    let mut visited = Grid::new(cells.width, cells.height, false);
+    let start = Point::new(rng.gen_range(0, cells.width), rng.gen_range(0, cells.height));
+    let mut stack = vec![start];
+    visited[start] = true;
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<Point> = neighbors(cells, cell).filter(|&next| !visited[next]).collect();
+        match rng.choose(&unvisited) {
+            Some(&next) => {
+                visited[next] = true;
+                passages.push((cell, next));
+                stack.push(next);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+    passages
+}
+
+fn prim(cells: Rect, rng: &mut GameRng) -> Vec<(Point, Point)> {
+    let mut passages = Vec::new();
+    let mut visited = Grid::new(cells.width, cells.height, false);
+    let start = Point::new(rng.gen_range(0, cells.width), rng.gen_range(0, cells.height));
+    visited[start] = true;
+    let mut frontier: Vec<(Point, Point)> = neighbors(cells, start).map(|next| (start, next)).collect();
+    while !frontier.is_empty() {
+        let (from, to) = frontier.swap_remove(rng.gen_range(0, frontier.len()));
+        if visited[to] {
+            continue;
+        }
+        visited[to] = true;
+        passages.push((from, to));
+        frontier.extend(neighbors(cells, to).filter(|&next| !visited[next]).map(|next| (to, next)));
+    }
+    passages
+}
+
+fn kruskal(cells: Rect, rng: &mut GameRng) -> Vec<(Point, Point)> {
+    let mut walls: Vec<(Point, Point)> = cells
+        .points()
+        .flat_map(|cell| vec![(cell, cell + Point::EAST), (cell, cell + Point::SOUTH)])
+        .filter(|&(_, next)| cells.contains(next))
+        .collect();
+    rng.shuffle(&mut walls);
+    let mut parents: Grid<Point> = Grid::from_fn(cells.width, cells.height, |cell| cell);
+    let root = |parents: &mut Grid<Point>, mut cell: Point| {
+        while parents[cell] != cell {
+            let grandparent = parents[parents[cell]];
+            parents[cell] = grandparent;
+            cell = grandparent;
+        }
+        cell
+    };
+    let mut passages = Vec::new();
+    for (a, b) in walls {
+        let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+        if root_a != root_b {
+            parents[root_a] = root_b;
+            passages.push((a, b));
+        }
+    }
+    passages
+}
+
+fn eller(cells: Rect, rng: &mut GameRng) -> Vec<(Point, Point)> {
+    let mut passages = Vec::new();
+    let mut sets: Vec<usize> = (0..cells.width as usize).collect();
+    let mut next_set = sets.len();
+    for y in 0..cells.height {
+        let last = y == cells.height - 1;
+        for x in 1..cells.width as usize {
+            if sets[x - 1] != sets[x] && (last || rng.gen()) {
+                let (from, to) = (sets[x], sets[x - 1]);
+                sets.iter_mut().filter(|set| **set == from).for_each(|set| *set = to);
+                passages.push((Point::new(x as i32 - 1, y), Point::new(x as i32, y)));
+            }
+        }
+        if last {
+            break;
+        }
+        let mut below: Vec<Option<usize>> = vec![None; sets.len()];
+        let mut order: Vec<usize> = (0..sets.len()).collect();
+        rng.shuffle(&mut order);
+        let mut descended = Vec::new();
+        for x in order {
+            if !descended.contains(&sets[x]) || rng.gen() {
+                descended.push(sets[x]);
+                below[x] = Some(sets[x]);
+                passages.push((Point::new(x as i32, y), Point::new(x as i32, y + 1)));
+            }
+        }
+        sets = below
+            .into_iter()
+            .map(|set| {
+                set.unwrap_or_else(|| {
+                    next_set += 1;
+                    next_set
+                })
+            })
+            .collect();
+    }
+    passages
+}
+
+fn wilson(cells: Rect, rng: &mut GameRng) -> Vec<(Point, Point)> {
+    let mut passages = Vec::new();
+    let mut in_maze = Grid::new(cells.width, cells.height, false);
+    let mut exits: Grid<Point> = Grid::new(cells.width, cells.height, Point::ZERO);
+    let mut order: Vec<Point> = cells.points().collect();
+    rng.shuffle(&mut order);
+    in_maze[order[0]] = true;
+    for &start in &order[1..] {
+        let mut cell = start;
+        while !in_maze[cell] {
+            let steps: Vec<Point> = neighbors(cells, cell).collect();
+            let next = *rng.choose(&steps).unwrap_or(&cell);
+            exits[cell] = next;
+            cell = next;
+        }
+        let mut cell = start;
+        while !in_maze[cell] {
+            in_maze[cell] = true;
+            passages.push((cell, exits[cell]));
+            cell = exits[cell];
+        }
+    }
+    passages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::connect::walkable_regions;
+    use mapgen::{BspGenerator, Pipeline};
+    use rand::SeedableRng;
+
+    const ALGORITHMS: [MazeAlgorithm; 5] = [
+        MazeAlgorithm::RecursiveBacktracker,
+        MazeAlgorithm::Prim,
+        MazeAlgorithm::Kruskal,
+        MazeAlgorithm::Eller,
+        MazeAlgorithm::Wilson,
+    ];
+
+    fn connected(tiles: &Grid<Tile>) -> bool {
+        walkable_regions(tiles).is_connected()
+    }
+
+    fn dead_ends(tiles: &Grid<Tile>) -> usize {
+        tiles
+            .iter()
+            .filter(|&(point, tile)| tile.is_walkable() && tiles.neighbors4(point).filter(|&next| tiles[next].is_walkable()).count() == 1)
+            .count()
+    }
+
+    #[test]
+    fn mazes_are_perfect() {
+        for &algorithm in ALGORITHMS.iter() {
+            for seed in 0..3 {
+                let map = Maze::new(algorithm).generate(41, 31, &mut GameRng::from_seed(seed));
+                let corridors = map.tiles.cells().iter().filter(|tile| tile.is_walkable()).count();
+                assert_eq!(corridors, 2 * 20 * 15 - 1, "{:?}", algorithm);
+                assert!(connected(&map.tiles), "{:?}", algorithm);
+                assert!(map.spawn.is_some() && map.stairs.is_some());
+                assert_eq!(map, Maze::new(algorithm).generate(41, 31, &mut GameRng::from_seed(seed)));
+            }
+        }
+    }
+
+    #[test]
+    fn braiding_removes_dead_ends() {
+        for &algorithm in ALGORITHMS.iter() {
+            let perfect = Maze::new(algorithm).generate(41, 31, &mut GameRng::from_seed(2));
+            let half = Maze::new(algorithm).braid(0.5).generate(41, 31, &mut GameRng::from_seed(2));
+            let braided = Maze::new(algorithm).braid(1.0).generate(41, 31, &mut GameRng::from_seed(2));
+            assert!(dead_ends(&half.tiles) < dead_ends(&perfect.tiles), "{:?}", algorithm);
+            assert_eq!(dead_ends(&braided.tiles), 0, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn maze_regions_in_a_pipeline() {
+        let area = Rect::new(20, 4, 21, 15);
+        let pipeline = Pipeline::new(BspGenerator::new()).with(Maze::new(MazeAlgorithm::Kruskal).within(area));
+        let map = pipeline.generate(60, 30, &mut GameRng::from_seed(3));
+        assert!(connected(&map.tiles));
+        assert_eq!(map.tiles[Point::new(21, 5)], Tile::Corridor);
+        assert!(map.rooms.iter().all(|room| !room.intersects(&area)));
+    }
+}
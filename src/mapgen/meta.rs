@@ -0,0 +1,221 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Meta-Builders
+//!
+
+use super::connect::{connect, farthest_walkable, nearest_walkable};
+use super::{Connectivity, GeneratedMap, MetaBuilder, Tile};
+use map::{Point, Rect};
+use rand::Rng;
+use random::GameRng;
+
+/// Carve every room into the largest ellipse fitting its rectangle, then tunnel back any
+/// corridors cut off by the corners.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RoomRounding;
+
+impl MetaBuilder for RoomRounding {
+    fn apply(&self, map: &mut GeneratedMap, _rng: &mut GameRng) {
+        for room in &map.rooms {
+            let (width, height) = (room.width as f32, room.height as f32);
+            for point in room.points() {
+                let dx = (2 * (point.x - room.x) + 1) as f32 / width - 1.0;
+                let dy = (2 * (point.y - room.y) + 1) as f32 / height - 1.0;
+                if dx * dx + dy * dy > 1.0 {
+                    map.tiles.set(point, Tile::Wall);
+                }
+            }
+        }
+        connect(&mut map.tiles, Connectivity::Tunnel);
+        tidy(map);
+    }
+}
+
+/// Mirror one half of the map onto the other, tunnelling between the halves if they do not meet
+///
+/// Rooms in the copied half are mirrored, rooms in the overwritten half are dropped and rooms
+/// crossing the axis are kept. Connections no longer describe the corridors, so they are cleared.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Symmetry {
+    /// Copy the left half onto the right.
+    Horizontal,
+    /// Copy the top half onto the bottom.
+    Vertical,
+    /// Copy the top left quarter onto the other three.
+    Both,
+}
+
+impl Symmetry {
+    fn mirror(map: &mut GeneratedMap, flip: fn(Point, i32, i32) -> Point, keep: fn(Point, i32, i32) -> bool) {
+        let (width, height) = (map.tiles.width(), map.tiles.height());
+        for point in map.tiles.points() {
+            if keep(point, width, height) {
+                let tile = map.tiles[point];
+                map.tiles.set(flip(point, width, height), tile);
+            }
+        }
+        let mut rooms = Vec::with_capacity(map.rooms.len() * 2);
+        for room in &map.rooms {
+            let (min, max) = (room.min(), room.max());
+            match (keep(min, width, height), keep(max, width, height)) {
+                (true, true) => {
+                    rooms.push(*room);
+                    rooms.push(Rect::from_corners(flip(min, width, height), flip(max, width, height)));
+                }
+                (true, false) => rooms.push(*room),
+                _ => {}
+            }
+        }
+        map.rooms = rooms;
+        map.connections.clear();
+    }
+}
+
+impl MetaBuilder for Symmetry {
+    fn apply(&self, map: &mut GeneratedMap, _rng: &mut GameRng) {
+        let horizontal = |map: &mut GeneratedMap| {
+            Symmetry::mirror(map, |p, width, _| Point::new(width - 1 - p.x, p.y), |p, width, _| p.x < width / 2)
+        };
+        let vertical = |map: &mut GeneratedMap| {
+            Symmetry::mirror(map, |p, _, height| Point::new(p.x, height - 1 - p.y), |p, _, height| p.y < height / 2)
+        };
+        match *self {
+            Symmetry::Horizontal => horizontal(map),
+            Symmetry::Vertical => vertical(map),
+            Symmetry::Both => {
+                horizontal(map);
+                vertical(map);
+            }
+        }
+        connect(&mut map.tiles, Connectivity::Tunnel);
+        map.doors = map.tiles.iter().filter(|&(_, tile)| *tile == Tile::Door).map(|(point, _)| point).collect();
+        tidy(map);
+    }
+}
+
+/// Region culling, removing or joining walkable areas cut off from the rest of the map.
+impl MetaBuilder for Connectivity {
+    fn apply(&self, map: &mut GeneratedMap, _rng: &mut GameRng) {
+        connect(&mut map.tiles, *self);
+        tidy(map);
+    }
+}
+
+/// Where to put the player's starting point
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum SpawnPoint {
+    /// Walkable tile nearest the centre of the map
+    Center,
+    /// Walkable tile nearest the centre of a random room, or a random walkable tile if there are
+    /// no rooms
+    RandomRoom,
+    /// Any walkable tile
+    Random,
+}
+
+impl MetaBuilder for SpawnPoint {
+    fn apply(&self, map: &mut GeneratedMap, rng: &mut GameRng) {
+        let random = |map: &GeneratedMap, rng: &mut GameRng| {
+            let floors: Vec<Point> = map.tiles.iter().filter(|&(_, tile)| tile.is_walkable()).map(|(point, _)| point).collect();
+            rng.choose(&floors).cloned()
+        };
+        map.spawn = match *self {
+            SpawnPoint::Center => nearest_walkable(&map.tiles, map.tiles.bounds().center()),
+            SpawnPoint::RandomRoom => match rng.choose(&map.rooms) {
+                Some(room) => nearest_walkable(&map.tiles, room.center()),
+                None => random(map, rng),
+            },
+            SpawnPoint::Random => random(map, rng),
+        };
+    }
+}
+
+/// Put the stairs on the walkable tile taking the most steps to reach from the spawn point.
+///
+/// Does nothing if the map has no spawn point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DistantStairs;
+
+impl MetaBuilder for DistantStairs {
+    fn apply(&self, map: &mut GeneratedMap, _rng: &mut GameRng) {
+        if let Some(spawn) = map.spawn {
+            map.stairs = farthest_walkable(&map.tiles, spawn);
+        }
+    }
+}
+
+/// Drop doors and points of interest left on tiles that changed underneath them.
+pub(super) fn tidy(map: &mut GeneratedMap) {
+    let tiles = &map.tiles;
+    map.doors.retain(|&door| tiles[door] == Tile::Door);
+    map.spawn = map.spawn.filter(|&spawn| tiles[spawn].is_walkable());
+    map.stairs = map.stairs.filter(|&stairs| tiles[stairs].is_walkable());
+    map.items.retain(|&(point, _)| tiles[point].is_walkable());
+    map.spawns.retain(|&(point, _)| tiles[point].is_walkable());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::connect::walkable_regions;
+    use mapgen::{BspGenerator, MapGenerator};
+    use rand::SeedableRng;
+
+    fn connected(map: &GeneratedMap) -> bool {
+        walkable_regions(&map.tiles).is_connected()
+    }
+
+    #[test]
+    fn rounded_rooms_lose_their_corners() {
+        let mut map = BspGenerator::new().room_size(5, 9).generate(60, 40, &mut GameRng::from_seed(5));
+        RoomRounding.apply(&mut map, &mut GameRng::from_seed(0));
+        let corners = map.rooms.iter().filter(|room| map.tiles[room.min()] == Tile::Wall && map.tiles[room.max()] == Tile::Wall);
+        assert!(corners.count() * 2 > map.rooms.len());
+        assert!(map.rooms.iter().all(|room| map.tiles[room.center()].is_walkable()));
+        assert!(connected(&map));
+    }
+
+    #[test]
+    fn symmetry_mirrors_tiles() {
+        for &symmetry in [Symmetry::Horizontal, Symmetry::Vertical, Symmetry::Both].iter() {
+            let mut map = BspGenerator::new().generate(50, 30, &mut GameRng::from_seed(8));
+            symmetry.apply(&mut map, &mut GameRng::from_seed(0));
+            assert!(connected(&map), "{:?}", symmetry);
+            let mirrored = map.tiles.points().filter(|&p| match symmetry {
+                Symmetry::Horizontal => map.tiles[p] == map.tiles[Point::new(49 - p.x, p.y)],
+                Symmetry::Vertical => map.tiles[p] == map.tiles[Point::new(p.x, 29 - p.y)],
+                Symmetry::Both => map.tiles[p] == map.tiles[Point::new(49 - p.x, 29 - p.y)],
+            });
+            assert!(mirrored.count() > 50 * 30 * 9 / 10, "{:?}", symmetry);
+            assert!(map.connections.is_empty());
+        }
+    }
+
+    #[test]
+    fn spawn_and_distant_stairs() {
+        let mut map = GeneratedMap::new(20, 5);
+        for x in 1..19 {
+            map.tiles.set(Point::new(x, 2), Tile::Corridor);
+        }
+        SpawnPoint::Center.apply(&mut map, &mut GameRng::from_seed(0));
+        assert_eq!(map.spawn, Some(Point::new(10, 2)));
+        DistantStairs.apply(&mut map, &mut GameRng::from_seed(0));
+        assert_eq!(map.stairs, Some(Point::new(1, 2)));
+        SpawnPoint::RandomRoom.apply(&mut map, &mut GameRng::from_seed(4));
+        assert!(map.spawn.is_some_and(|spawn| map.tiles[spawn].is_walkable()));
+    }
+}
@@ -0,0 +1,158 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Map Generation
+//!
+//! Generators fill a grid of [`Tile`]s using a [`GameRng`], normally the Engine's
+//! `Random::MAP` stream, so the same seed always produces the same map.
+//!
+//! [`Tile`]: enum.Tile.html
+//! [`GameRng`]: struct.GameRng.html
+//!
+
+mod bsp;
+mod cellular;
+mod connect;
+mod drunkard;
+mod maze;
+mod meta;
+mod pipeline;
+mod vault;
+mod wfc;
+
+pub use self::bsp::{BspGenerator, CorridorStyle};
+pub use self::cellular::CellularAutomata;
+pub use self::connect::{connect, Connectivity};
+pub use self::drunkard::DrunkardsWalk;
+pub use self::maze::{Maze, MazeAlgorithm};
+pub use self::meta::{DistantStairs, RoomRounding, SpawnPoint, Symmetry};
+pub use self::pipeline::{MetaBuilder, Pipeline, PipelineSnapshot, Requirement};
+pub use self::vault::{Glyph, Vault, VaultError, VaultErrorKind, VaultLibrary, VaultStamp};
+pub use self::wfc::{Constraints, Model, WaveFunctionCollapse};
+
+use fov::Transparency;
+use map::{Grid, Point, Rect};
+use random::GameRng;
+
+/// Terrain produced by the generators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Tile {
+    Wall,
+    Floor,
+    Corridor,
+    /// Closed door. Walkable, but blocks sight.
+    Door,
+}
+
+impl Tile {
+    pub fn is_walkable(self) -> bool {
+        self != Tile::Wall
+    }
+    pub fn is_transparent(self) -> bool {
+        self == Tile::Floor || self == Tile::Corridor
+    }
+}
+
+impl Transparency for Grid<Tile> {
+    fn width(&self) -> i32 {
+        Grid::width(self)
+    }
+    fn height(&self) -> i32 {
+        Grid::height(self)
+    }
+    fn is_transparent(&self, point: Point) -> bool {
+        self.get(point).is_some_and(|tile| tile.is_transparent())
+    }
+}
+
+/// Generated tiles along with what the generator knows about their layout
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GeneratedMap {
+    pub tiles: Grid<Tile>,
+    /// Floor area of every room
+    pub rooms: Vec<Rect>,
+    /// Pairs of indices into `rooms` joined by a corridor
+    pub connections: Vec<(usize, usize)>,
+    pub doors: Vec<Point>,
+    /// Suggested starting point for the player
+    pub spawn: Option<Point>,
+    /// Suggested location of the stairs, far from `spawn`
+    pub stairs: Option<Point>,
+    /// Items to place, by name
+    #[serde(default)]
+    pub items: Vec<(Point, String)>,
+    /// Monsters to place, by the name of the spawn table to roll them from
+    #[serde(default)]
+    pub spawns: Vec<(Point, String)>,
+}
+
+impl GeneratedMap {
+    /// Create a map that is solid wall with no metadata.
+    pub fn new(width: i32, height: i32) -> GeneratedMap {
+        GeneratedMap {
+            tiles: Grid::new(width, height, Tile::Wall),
+            rooms: Vec::new(),
+            connections: Vec::new(),
+            doors: Vec::new(),
+            spawn: None,
+            stairs: None,
+            items: Vec::new(),
+            spawns: Vec::new(),
+        }
+    }
+}
+
+/// Source of generated maps
+pub trait MapGenerator {
+    /// Generate a `width` x `height` map, drawing every random choice from `rng`.
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::connect::walkable_regions;
+    use rand::SeedableRng;
+
+    fn connected(map: &GeneratedMap) -> bool {
+        walkable_regions(&map.tiles).is_connected()
+    }
+
+    #[test]
+    fn caves_are_connected() {
+        let generators: Vec<Box<dyn MapGenerator>> = vec![
+            Box::new(CellularAutomata::new()),
+            Box::new(CellularAutomata::new().connectivity(Connectivity::Tunnel).iterations(2)),
+            Box::new(CellularAutomata::new().rules(&[6, 7, 8], &[3, 4, 5, 6, 7, 8]).fill(0.5)),
+            Box::new(DrunkardsWalk::new()),
+            Box::new(DrunkardsWalk::new().from_center(true).lifetime(100).floor(0.3)),
+        ];
+        for generator in &generators {
+            for seed in 0..5 {
+                let map = generator.generate(60, 30, &mut GameRng::from_seed(seed));
+                let floors = map.tiles.cells().iter().filter(|tile| tile.is_walkable()).count();
+                assert!(floors > 200);
+                assert!(connected(&map));
+                assert!(map.tiles.bounds().perimeter().all(|point| map.tiles[point] == Tile::Wall));
+                assert!(map.spawn.is_some() && map.stairs != map.spawn);
+                assert_eq!(map, generator.generate(60, 30, &mut GameRng::from_seed(seed)));
+            }
+        }
+        let walk = DrunkardsWalk::new().floor(0.5).generate(40, 20, &mut GameRng::from_seed(9));
+        assert!(walk.tiles.cells().iter().filter(|tile| tile.is_walkable()).count() >= 38 * 18 / 2);
+    }
+}
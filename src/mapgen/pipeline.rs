@@ -0,0 +1,222 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Generator Pipeline
+//!
+
+use super::connect::walkable_regions;
+use super::{GeneratedMap, MapGenerator, Tile};
+use map::{Chokepoint, DistanceMap, Neighborhood};
+use rand::Rng;
+use random::GameRng;
+use std::fmt::Debug;
+
+/// Step that reworks a map produced earlier in a [`Pipeline`]
+///
+/// [`Pipeline`]: struct.Pipeline.html
+pub trait MetaBuilder: Debug {
+    /// Modify `map` in place, drawing every random choice from `rng`.
+    fn apply(&self, map: &mut GeneratedMap, rng: &mut GameRng);
+}
+
+/// Copy of the map taken after a step of a [`Pipeline`]
+///
+/// [`Pipeline`]: struct.Pipeline.html
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineSnapshot {
+    /// `"initial"` for the initial generator, otherwise the step's Debug representation
+    pub step: String,
+    pub map: GeneratedMap,
+}
+
+/// Property the final map of a [`Pipeline`] is validated against
+///
+/// [`Pipeline`]: struct.Pipeline.html
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Requirement {
+    /// Every walkable tile can reach every other by orthogonal steps.
+    Connected,
+    /// At least this fraction of the tiles is walkable.
+    Walkable(f32),
+    /// Spawn and stairs are both placed, at least this many orthogonal steps apart.
+    StairsDistance(u32),
+    /// Blocking any single tile cuts off at most this many walkable tiles.
+    Chokepoints(usize),
+}
+
+impl Requirement {
+    pub fn is_met(&self, map: &GeneratedMap) -> bool {
+        let walkable = |tile: &Tile| tile.is_walkable();
+        match *self {
+            Requirement::Connected => walkable_regions(&map.tiles).is_connected(),
+            Requirement::Walkable(fraction) => {
+                let count = map.tiles.cells().iter().filter(|tile| tile.is_walkable()).count();
+                count as f32 >= fraction * map.tiles.cells().len() as f32
+            }
+            Requirement::StairsDistance(steps) => match (map.spawn, map.stairs) {
+                (Some(spawn), Some(stairs)) => DistanceMap::new(&map.tiles, &[spawn], Neighborhood::Four, walkable)
+                    .distance(stairs)
+                    .is_some_and(|distance| distance >= steps),
+                _ => false,
+            },
+            Requirement::Chokepoints(limit) => Chokepoint::find(&map.tiles, Neighborhood::Four, walkable)
+                .iter()
+                .all(|chokepoint| chokepoint.separated <= limit),
+        }
+    }
+}
+
+/// Initial generator followed by a chain of meta-builders
+///
+/// The pipeline draws a single seed from the rng it is given and seeds a separate stream for each
+/// step from it, so adding, removing or changing one step does not disturb the random choices of
+/// the steps before it. A map failing one of the pipeline's [`Requirement`]s is thrown away and
+/// generated again from a fresh seed, up to the attempt limit, after which the last map is kept.
+///
+/// [`Requirement`]: enum.Requirement.html
+pub struct Pipeline {
+    initial: Box<dyn MapGenerator>,
+    steps: Vec<Box<dyn MetaBuilder>>,
+    requirements: Vec<Requirement>,
+    attempts: usize,
+}
+
+impl Pipeline {
+    pub fn new<G: MapGenerator + 'static>(initial: G) -> Pipeline {
+        Pipeline {
+            initial: Box::new(initial),
+            steps: Vec::new(),
+            requirements: Vec::new(),
+            attempts: 10,
+        }
+    }
+    /// Append a step, run after every step added before it.
+    pub fn with<M: MetaBuilder + 'static>(mut self, step: M) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+    /// Validate the final map, generating it again while it fails.
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        self.requirements.push(requirement);
+        self
+    }
+    /// Maximum number of maps generated while looking for one meeting every requirement.
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+    /// Number of meta-builders following the initial generator
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+    /// Generate a map, also returning a snapshot taken after every step of the attempt kept.
+    ///
+    /// The final map is the same one `generate` produces from the same rng.
+    pub fn generate_with_snapshots(&self, width: i32, height: i32, rng: &mut GameRng) -> (GeneratedMap, Vec<PipelineSnapshot>) {
+        let mut snapshots = Vec::with_capacity(self.steps.len() + 1);
+        let map = self.run(width, height, rng, Some(&mut snapshots));
+        (map, snapshots)
+    }
+
+    fn run(&self, width: i32, height: i32, rng: &mut GameRng, mut snapshots: Option<&mut Vec<PipelineSnapshot>>) -> GeneratedMap {
+        let mut attempt = 1;
+        loop {
+            if let Some(ref mut snapshots) = snapshots {
+                snapshots.clear();
+            }
+            let map = self.attempt(width, height, rng, snapshots.as_deref_mut());
+            if attempt >= self.attempts || self.requirements.iter().all(|requirement| requirement.is_met(&map)) {
+                return map;
+            }
+            attempt += 1;
+        }
+    }
+
+    fn attempt(&self, width: i32, height: i32, rng: &mut GameRng, mut snapshots: Option<&mut Vec<PipelineSnapshot>>) -> GeneratedMap {
+        let seed = rng.next_u64();
+        let mut map = self.initial.generate(width, height, &mut GameRng::new(seed, 0));
+        if let Some(ref mut snapshots) = snapshots {
+            snapshots.push(PipelineSnapshot {
+                step: "initial".to_string(),
+                map: map.clone(),
+            });
+        }
+        for (index, step) in self.steps.iter().enumerate() {
+            step.apply(&mut map, &mut GameRng::new(seed, index as u64 + 1));
+            if let Some(ref mut snapshots) = snapshots {
+                snapshots.push(PipelineSnapshot {
+                    step: format!("{:?}", step),
+                    map: map.clone(),
+                });
+            }
+        }
+        map
+    }
+}
+
+impl MapGenerator for Pipeline {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        self.run(width, height, rng, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::{BspGenerator, CellularAutomata, Connectivity, DistantStairs, RoomRounding, SpawnPoint, Symmetry};
+    use rand::SeedableRng;
+
+    #[test]
+    fn snapshots_every_step() {
+        let pipeline = Pipeline::new(BspGenerator::new()).with(RoomRounding).with(SpawnPoint::Center).with(DistantStairs);
+        let (map, snapshots) = pipeline.generate_with_snapshots(60, 40, &mut GameRng::from_seed(3));
+        let steps: Vec<&str> = snapshots.iter().map(|snapshot| snapshot.step.as_str()).collect();
+        assert_eq!(steps, vec!["initial", "RoomRounding", "Center", "DistantStairs"]);
+        assert_eq!(snapshots[3].map, map);
+        assert_eq!(map, pipeline.generate(60, 40, &mut GameRng::from_seed(3)));
+        assert!(map.spawn.is_some() && map.stairs.is_some() && map.spawn != map.stairs);
+    }
+
+    #[test]
+    fn later_steps_do_not_disturb_earlier_ones() {
+        let short = Pipeline::new(BspGenerator::new()).with(SpawnPoint::Random);
+        let long = Pipeline::new(BspGenerator::new()).with(SpawnPoint::Random).with(Symmetry::Vertical);
+        let (_, first) = short.generate_with_snapshots(50, 30, &mut GameRng::from_seed(11));
+        let (_, second) = long.generate_with_snapshots(50, 30, &mut GameRng::from_seed(11));
+        assert_eq!(first[..], second[..2]);
+    }
+
+    #[test]
+    fn requirements_reject_maps() {
+        let caves = || Pipeline::new(CellularAutomata::new().connectivity(Connectivity::Tunnel)).with(SpawnPoint::Center).with(DistantStairs);
+        let required = Requirement::StairsDistance(40);
+        let seed = (0..20)
+            .find(|&seed| !required.is_met(&caves().generate(50, 30, &mut GameRng::from_seed(seed))))
+            .unwrap();
+        let map = caves().require(required).generate(50, 30, &mut GameRng::from_seed(seed));
+        assert!(required.is_met(&map));
+        assert!(Requirement::Connected.is_met(&map));
+
+        let impossible = caves().require(Requirement::Walkable(1.0)).attempts(3);
+        let (map, snapshots) = impossible.generate_with_snapshots(50, 30, &mut GameRng::from_seed(seed));
+        assert!(!Requirement::Walkable(1.0).is_met(&map));
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots[2].map, map);
+    }
+}
@@ -0,0 +1,633 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Prefab Vaults
+//!
+//! Vaults are hand-drawn rooms stamped into generated maps. They can be written as serde data or
+//! in a plain text format, one or more to a file:
+//!
+//! ```text
+//! ; Comments start with a semicolon
+//! vault treasury
+//! weight 2
+//! depth 5 10
+//! doors 1
+//! mirror no
+//! legend $ floor item gold
+//! legend o floor spawn orcs
+//! map
+//! #####
+//! #$.o#
+//! ##+##
+//! end
+//! ```
+//!
+//! `#`, `.`, `+` and space stand for wall, floor, door and the map's own tile unless the legend
+//! says otherwise.
+//!
+
+use super::connect::connect;
+use super::meta::tidy;
+use super::{Connectivity, GeneratedMap, MetaBuilder, Tile};
+use map::{Grid, Point, Rect};
+use rand::Rng;
+use random::GameRng;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// What a character of a vault's art places
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Glyph {
+    /// Tile to place, or `None` to keep the map's tile
+    #[serde(default)]
+    pub tile: Option<Tile>,
+    /// Name of an item to place
+    #[serde(default)]
+    pub item: Option<String>,
+    /// Name of the spawn table to roll a monster from
+    #[serde(default)]
+    pub spawn: Option<String>,
+}
+
+impl Glyph {
+    pub fn tile(tile: Tile) -> Glyph {
+        Glyph {
+            tile: Some(tile),
+            ..Glyph::default()
+        }
+    }
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Hand-drawn room with placement constraints
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vault {
+    pub name: String,
+    /// Rows of art, all the same length
+    pub rows: Vec<String>,
+    /// Glyphs for the characters of the art, overriding the defaults
+    #[serde(default)]
+    pub legend: BTreeMap<char, Glyph>,
+    /// Relative chance of being chosen
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// First depth at which the vault can be placed
+    #[serde(default)]
+    pub min_depth: u32,
+    /// Last depth at which the vault can be placed
+    #[serde(default)]
+    pub max_depth: Option<u32>,
+    /// Exact number of doors on the edge of the vault that must open onto the map. If not set,
+    /// the vault is placed in solid rock and tunnelled into the map.
+    #[serde(default)]
+    pub doors: Option<usize>,
+    /// Allow quarter turns
+    #[serde(default = "default_true")]
+    pub rotate: bool,
+    /// Allow reflection
+    #[serde(default = "default_true")]
+    pub mirror: bool,
+}
+
+impl Vault {
+    /// Vault with no constraints, drawn with the default glyphs.
+    pub fn new(name: &str, rows: &[&str]) -> Vault {
+        Vault {
+            name: name.to_string(),
+            rows: rows.iter().map(|row| row.to_string()).collect(),
+            legend: BTreeMap::new(),
+            weight: 1,
+            min_depth: 0,
+            max_depth: None,
+            doors: None,
+            rotate: true,
+            mirror: true,
+        }
+    }
+    pub fn width(&self) -> i32 {
+        self.rows.first().map_or(0, |row| row.chars().count() as i32)
+    }
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+    /// Glyph drawn by a character, if the legend or the defaults define it.
+    pub fn glyph(&self, c: char) -> Option<Glyph> {
+        if let Some(glyph) = self.legend.get(&c) {
+            return Some(glyph.clone());
+        }
+        match c {
+            '#' => Some(Glyph::tile(Tile::Wall)),
+            '.' => Some(Glyph::tile(Tile::Floor)),
+            '+' => Some(Glyph::tile(Tile::Door)),
+            ' ' => Some(Glyph::default()),
+            _ => None,
+        }
+    }
+    /// Returns true if the vault can be placed at a depth.
+    pub fn allows_depth(&self, depth: u32) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max| depth <= max)
+    }
+    /// Check that the art is rectangular and every character is defined.
+    pub fn validate(&self) -> Result<(), VaultError> {
+        let error = |kind| Err(VaultError::new(&self.name, None, kind));
+        if self.rows.is_empty() || self.width() == 0 {
+            return error(VaultErrorKind::EmptyMap);
+        }
+        if self.rows.iter().any(|row| row.chars().count() as i32 != self.width()) {
+            return error(VaultErrorKind::RaggedMap);
+        }
+        match self.rows.iter().flat_map(|row| row.chars()).find(|&c| self.glyph(c).is_none()) {
+            Some(c) => error(VaultErrorKind::UndefinedGlyph(c)),
+            None => Ok(()),
+        }
+    }
+    /// Glyphs of the art after `turns` quarter turns clockwise, mirrored left to right first if
+    /// `mirrored` is set. Undefined characters keep the map's tile.
+    pub fn grid(&self, turns: u32, mirrored: bool) -> Grid<Glyph> {
+        let rows: Vec<Vec<char>> = self.rows.iter().map(|row| row.chars().collect()).collect();
+        let (width, height) = (self.width(), self.height());
+        let source = |x: i32, y: i32| {
+            let x = if mirrored { width - 1 - x } else { x };
+            let c = rows[y as usize].get(x as usize).cloned().unwrap_or(' ');
+            self.glyph(c).unwrap_or_default()
+        };
+        match turns % 4 {
+            0 => Grid::from_fn(width, height, |p| source(p.x, p.y)),
+            1 => Grid::from_fn(height, width, |p| source(p.y, height - 1 - p.x)),
+            2 => Grid::from_fn(width, height, |p| source(width - 1 - p.x, height - 1 - p.y)),
+            _ => Grid::from_fn(height, width, |p| source(width - 1 - p.y, p.x)),
+        }
+    }
+    /// Every distinct orientation the vault may be placed in.
+    fn orientations(&self) -> Vec<Grid<Glyph>> {
+        let mut grids: Vec<Grid<Glyph>> = Vec::new();
+        for &mirrored in [false, true].iter().take(if self.mirror { 2 } else { 1 }) {
+            for turns in 0..if self.rotate { 4 } else { 1 } {
+                let grid = self.grid(turns, mirrored);
+                if !grids.contains(&grid) {
+                    grids.push(grid);
+                }
+            }
+        }
+        grids
+    }
+}
+
+/// Collection of vaults to choose from
+///
+/// Every vault is validated when the library is created, parsed or deserialized.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "LibraryData")]
+pub struct VaultLibrary {
+    pub vaults: Vec<Vault>,
+}
+
+/// Serialized form of a VaultLibrary, validated before it becomes one
+#[derive(Deserialize)]
+struct LibraryData {
+    vaults: Vec<Vault>,
+}
+
+impl TryFrom<LibraryData> for VaultLibrary {
+    type Error = VaultError;
+    fn try_from(data: LibraryData) -> Result<VaultLibrary, VaultError> {
+        VaultLibrary::new(data.vaults)
+    }
+}
+
+impl VaultLibrary {
+    /// Library of vaults, validating each of them.
+    pub fn new(vaults: Vec<Vault>) -> Result<VaultLibrary, VaultError> {
+        for vault in &vaults {
+            vault.validate()?;
+        }
+        Ok(VaultLibrary { vaults })
+    }
+    /// Read vaults written in the plain text format.
+    pub fn parse(text: &str) -> Result<VaultLibrary, VaultError> {
+        let mut vaults: Vec<Vault> = Vec::new();
+        let mut lines = text.lines().enumerate().map(|(index, line)| (index + 1, line));
+        while let Some((line, content)) = lines.next() {
+            let mut words = content.split_whitespace();
+            let keyword = match words.next() {
+                Some(keyword) if !keyword.starts_with(';') => keyword,
+                _ => continue,
+            };
+            let values: Vec<&str> = words.collect();
+            let result = match (keyword, vaults.last_mut()) {
+                ("vault", _) if values.is_empty() => Err(VaultErrorKind::MissingValue("name")),
+                ("vault", _) => {
+                    vaults.push(Vault::new(&values.join(" "), &[]));
+                    Ok(())
+                }
+                (_, None) => Err(VaultErrorKind::OutsideVault),
+                ("map", Some(vault)) => {
+                    vault.rows.clear();
+                    loop {
+                        match lines.next() {
+                            Some((_, "end")) => break Ok(()),
+                            Some((_, row)) => vault.rows.push(row.to_string()),
+                            None => break Err(VaultErrorKind::UnterminatedMap),
+                        }
+                    }
+                }
+                (_, Some(vault)) => parse_setting(vault, keyword, &values),
+            };
+            if let Err(kind) = result {
+                let name = vaults.last().map_or("", |vault| vault.name.as_str());
+                return Err(VaultError::new(name, Some(line), kind));
+            }
+        }
+        VaultLibrary::new(vaults)
+    }
+    /// Draw a vault allowed at `depth`, weighted by each vault's weight.
+    pub fn choose(&self, depth: u32, rng: &mut GameRng) -> Option<&Vault> {
+        let allowed = || self.vaults.iter().filter(|vault| vault.allows_depth(depth) && vault.weight > 0);
+        let total: u32 = allowed().map(|vault| vault.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.gen_range(0, total);
+        allowed().find(|vault| {
+            if roll < vault.weight {
+                return true;
+            }
+            roll -= vault.weight;
+            false
+        })
+    }
+}
+
+/// Apply a `keyword value...` line to a vault.
+fn parse_setting(vault: &mut Vault, keyword: &str, values: &[&str]) -> Result<(), VaultErrorKind> {
+    let number = |index: usize| -> Result<u32, VaultErrorKind> {
+        let value = values.get(index).ok_or(VaultErrorKind::MissingValue("number"))?;
+        value.parse().map_err(|_| VaultErrorKind::InvalidValue(value.to_string()))
+    };
+    let flag = || match values.first() {
+        Some(&"yes") => Ok(true),
+        Some(&"no") => Ok(false),
+        Some(value) => Err(VaultErrorKind::InvalidValue(value.to_string())),
+        None => Err(VaultErrorKind::MissingValue("yes or no")),
+    };
+    match keyword {
+        "weight" => vault.weight = number(0)?,
+        "depth" => {
+            vault.min_depth = number(0)?;
+            vault.max_depth = if values.len() > 1 { Some(number(1)?) } else { None };
+        }
+        "doors" => vault.doors = Some(number(0)? as usize),
+        "rotate" => vault.rotate = flag()?,
+        "mirror" => vault.mirror = flag()?,
+        "legend" => {
+            let (c, glyph) = parse_legend(values)?;
+            vault.legend.insert(c, glyph);
+        }
+        _ => return Err(VaultErrorKind::UnknownKeyword(keyword.to_string())),
+    }
+    Ok(())
+}
+
+/// Parse `C TILE [item NAME] [spawn TABLE]`.
+fn parse_legend(values: &[&str]) -> Result<(char, Glyph), VaultErrorKind> {
+    let mut chars = values.first().ok_or(VaultErrorKind::MissingValue("character"))?.chars();
+    let c = match (chars.next(), chars.next()) {
+        (Some(c), None) => c,
+        _ => return Err(VaultErrorKind::InvalidValue(values[0].to_string())),
+    };
+    let tile = match values.get(1) {
+        Some(&"wall") => Some(Tile::Wall),
+        Some(&"floor") => Some(Tile::Floor),
+        Some(&"corridor") => Some(Tile::Corridor),
+        Some(&"door") => Some(Tile::Door),
+        Some(&"keep") => None,
+        Some(other) => return Err(VaultErrorKind::UnknownTile(other.to_string())),
+        None => return Err(VaultErrorKind::MissingValue("tile")),
+    };
+    let mut glyph = Glyph {
+        tile,
+        ..Glyph::default()
+    };
+    let mut rest = values[2..].iter();
+    while let Some(&key) = rest.next() {
+        let value = rest.next().ok_or(VaultErrorKind::MissingValue("name"))?.to_string();
+        match key {
+            "item" => glyph.item = Some(value),
+            "spawn" => glyph.spawn = Some(value),
+            _ => return Err(VaultErrorKind::UnknownKeyword(key.to_string())),
+        }
+    }
+    Ok((c, glyph))
+}
+
+/// Stamp vaults from a library into a map
+///
+/// Vaults never overwrite anything but wall or touch the edge of the map. A vault with a
+/// `doors` constraint is placed flush against the map's open areas, with exactly that many of its
+/// edge doors and nothing else opening onto them. Other vaults are placed in solid rock and
+/// tunnelled into the map. Placed vaults are added to the map's rooms, and vaults that do not
+/// fit anywhere are skipped.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultStamp {
+    library: VaultLibrary,
+    depth: u32,
+    count: usize,
+}
+
+impl VaultStamp {
+    pub fn new(library: VaultLibrary) -> VaultStamp {
+        VaultStamp {
+            library,
+            depth: 0,
+            count: 1,
+        }
+    }
+    /// Depth of the level, restricting which vaults may be chosen.
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = depth;
+        self
+    }
+    /// Number of vaults to place.
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+}
+
+impl MetaBuilder for VaultStamp {
+    fn apply(&self, map: &mut GeneratedMap, rng: &mut GameRng) {
+        for _ in 0..self.count {
+            let vault = match self.library.choose(self.depth, rng) {
+                Some(vault) => vault,
+                None => break,
+            };
+            let mut candidates: Vec<(Point, usize)> = Vec::new();
+            let orientations = vault.orientations();
+            for (index, grid) in orientations.iter().enumerate() {
+                let origins = Rect::new(1, 1, map.tiles.width() - grid.width() - 1, map.tiles.height() - grid.height() - 1);
+                candidates.extend(origins.points().filter(|&origin| fits(map, grid, origin, vault.doors)).map(|origin| (origin, index)));
+            }
+            let (origin, index) = match rng.choose(&candidates) {
+                Some(candidate) => *candidate,
+                None => continue,
+            };
+            let grid = &orientations[index];
+            for (offset, glyph) in grid.iter() {
+                let point = origin + offset;
+                if let Some(tile) = glyph.tile {
+                    map.tiles.set(point, tile);
+                    if tile == Tile::Door {
+                        map.doors.push(point);
+                    }
+                }
+                if let Some(ref item) = glyph.item {
+                    map.items.push((point, item.clone()));
+                }
+                if let Some(ref spawn) = glyph.spawn {
+                    map.spawns.push((point, spawn.clone()));
+                }
+            }
+            map.rooms.push(Rect::new(origin.x, origin.y, grid.width(), grid.height()));
+        }
+        connect(&mut map.tiles, Connectivity::Tunnel);
+        tidy(map);
+    }
+}
+
+/// Returns true if a vault may be stamped with its top left corner at `origin`.
+fn fits(map: &GeneratedMap, grid: &Grid<Glyph>, origin: Point, doors: Option<usize>) -> bool {
+    let area = Rect::new(origin.x, origin.y, grid.width(), grid.height());
+    let covered: Vec<(Point, Tile)> = grid.iter().filter_map(|(offset, glyph)| glyph.tile.map(|tile| (origin + offset, tile))).collect();
+    if !covered.iter().all(|&(point, _)| map.tiles[point] == Tile::Wall) {
+        return false;
+    }
+    let doors = match doors {
+        Some(doors) => doors,
+        None => return area.inflate(1).points().all(|point| map.tiles.get(point) == Some(&Tile::Wall)),
+    };
+    let mut openings = 0;
+    for (point, tile) in covered {
+        if !tile.is_walkable() {
+            continue;
+        }
+        let opens = map
+            .tiles
+            .neighbors4(point)
+            .any(|next| !area.contains(next) && map.tiles[next].is_walkable());
+        if opens && tile != Tile::Door {
+            return false;
+        }
+        openings += opens as usize;
+    }
+    openings == doors
+}
+
+/// Vault Errors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VaultErrorKind {
+    /// A setting appears before the first `vault` line.
+    OutsideVault,
+    UnknownKeyword(String),
+    /// A setting is missing a value of the named kind.
+    MissingValue(&'static str),
+    InvalidValue(String),
+    UnknownTile(String),
+    /// A `map` is not followed by an `end` line.
+    UnterminatedMap,
+    EmptyMap,
+    /// Rows of the art are of different lengths.
+    RaggedMap,
+    /// A character of the art is not in the legend or the defaults.
+    UndefinedGlyph(char),
+}
+
+/// Error reading or validating a vault
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VaultError {
+    vault: String,
+    line: Option<usize>,
+    kind: VaultErrorKind,
+}
+
+impl VaultError {
+    fn new(vault: &str, line: Option<usize>, kind: VaultErrorKind) -> VaultError {
+        VaultError {
+            vault: vault.to_string(),
+            line,
+            kind,
+        }
+    }
+    /// Name of the vault, empty if the error came before the first one
+    pub fn vault(&self) -> &str {
+        &self.vault
+    }
+    /// Line of the text the error was found on, if it was parsed
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+    pub fn kind(&self) -> &VaultErrorKind {
+        &self.kind
+    }
+}
+
+impl Display for VaultError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if let Some(line) = self.line {
+            write!(f, "At line {}: ", line)?;
+        }
+        if !self.vault.is_empty() {
+            write!(f, "In vault '{}': ", self.vault)?;
+        }
+        match self.kind {
+            VaultErrorKind::OutsideVault => write!(f, "setting before the first vault"),
+            VaultErrorKind::UnknownKeyword(ref word) => write!(f, "unknown keyword '{}'", word),
+            VaultErrorKind::MissingValue(expected) => write!(f, "missing {}", expected),
+            VaultErrorKind::InvalidValue(ref value) => write!(f, "invalid value '{}'", value),
+            VaultErrorKind::UnknownTile(ref tile) => write!(f, "unknown tile '{}'", tile),
+            VaultErrorKind::UnterminatedMap => write!(f, "map has no end line"),
+            VaultErrorKind::EmptyMap => write!(f, "map is empty"),
+            VaultErrorKind::RaggedMap => write!(f, "map rows differ in length"),
+            VaultErrorKind::UndefinedGlyph(c) => write!(f, "character '{}' is not in the legend", c),
+        }
+    }
+}
+
+impl Error for VaultError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::connect::walkable_regions;
+    use rand::SeedableRng;
+    use serde_json;
+
+    const LIBRARY: &str = "
+; Treasure behind a single door
+vault treasury
+weight 2
+depth 5 10
+doors 1
+mirror no
+legend $ floor item gold
+legend o floor spawn orcs
+map
+#####
+#$.o#
+##+##
+end
+
+vault shrine
+legend _ keep
+map
+_###_
+##.##
+#...#
+##.##
+_###_
+end
+";
+
+    fn connected(tiles: &Grid<Tile>) -> bool {
+        walkable_regions(tiles).is_connected()
+    }
+
+    #[test]
+    fn parses_text() {
+        let library = VaultLibrary::parse(LIBRARY).unwrap();
+        let treasury = &library.vaults[0];
+        assert_eq!((treasury.name.as_str(), treasury.weight, treasury.min_depth, treasury.max_depth), ("treasury", 2, 5, Some(10)));
+        assert_eq!((treasury.doors, treasury.rotate, treasury.mirror), (Some(1), true, false));
+        assert_eq!(treasury.glyph('o').and_then(|glyph| glyph.spawn), Some("orcs".to_string()));
+        assert_eq!(library.vaults[1].glyph('_'), Some(Glyph::default()));
+        assert_eq!((library.vaults[1].width(), library.vaults[1].height()), (5, 5));
+        let json = serde_json::to_string(&library).unwrap();
+        assert_eq!(serde_json::from_str::<VaultLibrary>(&json).unwrap(), library);
+        let ragged = r#"{"vaults":[{"name":"a","rows":["...",".."]}]}"#;
+        assert!(serde_json::from_str::<VaultLibrary>(ragged).is_err());
+        assert!(serde_json::from_str::<VaultStamp>(&format!(r#"{{"library":{},"depth":0,"count":1}}"#, ragged)).is_err());
+
+        let error = |text: &str| VaultLibrary::parse(text).unwrap_err();
+        assert_eq!(error("weight 2").kind(), &VaultErrorKind::OutsideVault);
+        let unknown = error("vault a\n\nsize 3");
+        assert_eq!((unknown.line(), unknown.vault()), (Some(3), "a"));
+        assert_eq!(unknown.kind(), &VaultErrorKind::UnknownKeyword("size".to_string()));
+        assert_eq!(error("vault a\nlegend x lava").kind(), &VaultErrorKind::UnknownTile("lava".to_string()));
+        assert_eq!(error("vault a\nmap\n###").kind(), &VaultErrorKind::UnterminatedMap);
+        assert_eq!(error("vault a\nmap\n###\n##\nend").kind(), &VaultErrorKind::RaggedMap);
+        assert_eq!(error("vault a\nmap\n#x#\nend").to_string(), "In vault 'a': character 'x' is not in the legend");
+    }
+
+    #[test]
+    fn orientations() {
+        let vault = Vault::new("corner", &["+.", "##", "##"]);
+        let tile = |grid: &Grid<Glyph>, x, y| grid[Point::new(x, y)].tile;
+        let turned = vault.grid(1, false);
+        assert_eq!((turned.width(), turned.height()), (3, 2));
+        assert_eq!(tile(&turned, 2, 0), Some(Tile::Door));
+        assert_eq!(tile(&vault.grid(2, false), 1, 2), Some(Tile::Door));
+        assert_eq!(tile(&vault.grid(3, false), 0, 1), Some(Tile::Door));
+        assert_eq!(tile(&vault.grid(0, true), 1, 0), Some(Tile::Door));
+        assert_eq!(vault.orientations().len(), 8);
+        let square = Vault::new("square", &["...", "...", "..."]);
+        assert_eq!(square.orientations().len(), 1);
+    }
+
+    #[test]
+    fn stamps_into_rock() {
+        let library = VaultLibrary::parse(LIBRARY).unwrap();
+        let mut map = GeneratedMap::new(40, 30);
+        map.tiles.fill_rect(Rect::new(2, 2, 8, 8), Tile::Floor);
+        let mut rng = GameRng::from_seed(2);
+        assert!(library.choose(0, &mut rng).is_some_and(|vault| vault.name == "shrine"));
+        VaultStamp::new(library).count(3).apply(&mut map, &mut rng);
+        assert_eq!(map.rooms.len(), 3);
+        for room in &map.rooms {
+            assert_eq!(map.tiles[room.center()], Tile::Floor);
+        }
+        assert!(connected(&map.tiles));
+    }
+
+    #[test]
+    fn doors_open_onto_the_map() {
+        let mut library = VaultLibrary::parse(LIBRARY).unwrap();
+        library.vaults.truncate(1);
+        let mut map = GeneratedMap::new(30, 20);
+        map.tiles.fill_rect(Rect::new(1, 10, 28, 1), Tile::Corridor);
+        VaultStamp::new(library).depth(7).count(2).apply(&mut map, &mut GameRng::from_seed(5));
+        assert_eq!(map.rooms.len(), 2);
+        assert_eq!(map.items.len(), 2);
+        assert_eq!(map.spawns.iter().filter(|(_, table)| table == "orcs").count(), 2);
+        for room in &map.rooms {
+            let openings = room
+                .perimeter()
+                .filter(|&point| map.tiles.neighbors4(point).any(|next| !room.contains(next) && map.tiles[next].is_walkable()))
+                .filter(|&point| map.tiles[point].is_walkable());
+            let doors: Vec<Point> = openings.collect();
+            assert_eq!(doors.len(), 1);
+            assert_eq!(map.tiles[doors[0]], Tile::Door);
+        }
+        assert!(connected(&map.tiles));
+    }
+}
@@ -0,0 +1,589 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Wave Function Collapse
+//!
+
+use super::connect::{connect, place_spawn_and_stairs};
+use super::meta::tidy;
+use super::{Connectivity, GeneratedMap, MapGenerator, MetaBuilder, Tile};
+use map::{Grid, Point, Rect};
+use rand::Rng;
+use random::GameRng;
+use std::collections::HashMap;
+use std::{mem, slice};
+
+/// Neighbouring cells, indexed so that the opposite of direction `d` is `(d + 2) % 4`
+const DIRECTIONS: [Point; 4] = [Point::EAST, Point::SOUTH, Point::WEST, Point::NORTH];
+
+/// How constraints are learned from a sample
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Model {
+    /// Single tiles, which may sit next to each other only if they do somewhere in the sample.
+    Tiled,
+    /// Square windows of the given size, which may overlap only if they agree on every shared
+    /// tile. Every window of the output appears somewhere in the sample.
+    Overlapping(i32),
+}
+
+/// Patterns and adjacency rules learned from a sample map
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraints {
+    model: Model,
+    patterns: Vec<Vec<Tile>>,
+    weights: Vec<f64>,
+    allowed: Vec<[Vec<usize>; 4]>,
+}
+
+impl Constraints {
+    /// Learn from the sample as drawn.
+    pub fn learn(sample: &Grid<Tile>, model: Model) -> Constraints {
+        Constraints::from_samples(slice::from_ref(sample), model)
+    }
+    /// Learn from the sample and its seven other rotations and reflections.
+    pub fn learn_rotated(sample: &Grid<Tile>, model: Model) -> Constraints {
+        let mut samples = Vec::with_capacity(8);
+        let mut current = sample.clone();
+        for _ in 0..4 {
+            let mirrored = Grid::from_fn(current.width(), current.height(), |p| current[Point::new(current.width() - 1 - p.x, p.y)]);
+            let rotated = Grid::from_fn(current.height(), current.width(), |p| current[Point::new(p.y, current.height() - 1 - p.x)]);
+            samples.push(current);
+            samples.push(mirrored);
+            current = rotated;
+        }
+        Constraints::from_samples(&samples, model)
+    }
+    pub fn model(&self) -> Model {
+        self.model
+    }
+    /// Number of distinct patterns
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+    /// Tile placed on the map by a pattern, its top left corner.
+    pub fn tile(&self, pattern: usize) -> Tile {
+        self.patterns[pattern][0]
+    }
+    /// Number of times a pattern occurs in the sample
+    pub fn weight(&self, pattern: usize) -> f64 {
+        self.weights[pattern]
+    }
+    /// Returns true if pattern `b` may sit one step from pattern `a` in an orthogonal `direction`.
+    pub fn allows(&self, a: usize, direction: Point, b: usize) -> bool {
+        DIRECTIONS
+            .iter()
+            .position(|&d| d == direction)
+            .is_some_and(|d| self.allowed[a][d].contains(&b))
+    }
+    /// Returns true if a map could have been produced from these constraints.
+    pub fn is_satisfied(&self, tiles: &Grid<Tile>) -> bool {
+        let size = self.size();
+        let index: HashMap<&[Tile], usize> = self.patterns.iter().enumerate().map(|(i, pattern)| (pattern.as_slice(), i)).collect();
+        let windows = Rect::new(0, 0, tiles.width() - size + 1, tiles.height() - size + 1);
+        let found: Option<Vec<usize>> = windows
+            .points()
+            .map(|p| {
+                let window: Vec<Tile> = Rect::new(p.x, p.y, size, size).points().map(|q| tiles[q]).collect();
+                index.get(window.as_slice()).cloned()
+            })
+            .collect();
+        let found = match found.and_then(|found| Grid::from_vec(windows.width.max(0), windows.height.max(0), found)) {
+            Some(found) => found,
+            None => return false,
+        };
+        match self.model {
+            Model::Overlapping(_) => true,
+            Model::Tiled => found.iter().all(|(point, &a)| {
+                DIRECTIONS
+                    .iter()
+                    .enumerate()
+                    .all(|(d, &direction)| found.get(point + direction).is_none_or(|b| self.allowed[a][d].contains(b)))
+            }),
+        }
+    }
+
+    fn has_tile(&self, tile: Tile) -> bool {
+        self.patterns.iter().any(|pattern| pattern[0] == tile)
+    }
+
+    fn size(&self) -> i32 {
+        match self.model {
+            Model::Tiled => 1,
+            Model::Overlapping(size) => size.max(1),
+        }
+    }
+
+    fn from_samples(samples: &[Grid<Tile>], model: Model) -> Constraints {
+        let mut constraints = Constraints {
+            model,
+            patterns: Vec::new(),
+            weights: Vec::new(),
+            allowed: Vec::new(),
+        };
+        let size = constraints.size();
+        let mut index: HashMap<Vec<Tile>, usize> = HashMap::new();
+        let mut found = Vec::with_capacity(samples.len());
+        for sample in samples {
+            let windows = Rect::new(0, 0, sample.width() - size + 1, sample.height() - size + 1);
+            found.push(Grid::from_fn(windows.width.max(0), windows.height.max(0), |p| {
+                let window: Vec<Tile> = Rect::new(p.x, p.y, size, size).points().map(|q| sample[q]).collect();
+                let next = constraints.patterns.len();
+                let pattern = *index.entry(window.clone()).or_insert(next);
+                if pattern == next {
+                    constraints.patterns.push(window);
+                    constraints.weights.push(0.0);
+                }
+                constraints.weights[pattern] += 1.0;
+                pattern
+            }));
+        }
+        constraints.allowed = vec![Default::default(); constraints.patterns.len()];
+        match model {
+            Model::Tiled => {
+                for grid in &found {
+                    for (point, &a) in grid.iter() {
+                        for (d, &direction) in DIRECTIONS.iter().enumerate() {
+                            if let Some(&b) = grid.get(point + direction) {
+                                if !constraints.allowed[a][d].contains(&b) {
+                                    constraints.allowed[a][d].push(b);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            Model::Overlapping(_) => {
+                for a in 0..constraints.patterns.len() {
+                    for b in 0..constraints.patterns.len() {
+                        for (d, &direction) in DIRECTIONS.iter().enumerate() {
+                            if constraints.overlaps(a, direction, b) {
+                                constraints.allowed[a][d].push(b);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        constraints
+    }
+
+    /// Returns true if pattern `b`, shifted by `offset`, agrees with pattern `a` where they meet.
+    fn overlaps(&self, a: usize, offset: Point, b: usize) -> bool {
+        let size = self.size();
+        let shared = Rect::new(0, 0, size, size).intersection(&Rect::new(offset.x, offset.y, size, size));
+        shared.points().all(|p| {
+            let q = p - offset;
+            self.patterns[a][(p.y * size + p.x) as usize] == self.patterns[b][(q.y * size + q.x) as usize]
+        })
+    }
+}
+
+/// Maps that locally resemble a hand-drawn sample
+///
+/// Each cell starts out able to hold any pattern. The cell with the fewest remaining choices is
+/// collapsed to a single pattern, weighted by how often it appears in the sample, and the choice
+/// is propagated to its neighbours. When a cell runs out of choices the most recent choice is
+/// undone and ruled out, backtracking as far as needed. A run that backtracks too often is
+/// abandoned and retried.
+///
+/// As a generator, the edge of the map is pinned to wall. As a meta-builder, the inside of every
+/// room is collapsed again around the tiles already surrounding it, which suits samples of
+/// furnished rooms.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WaveFunctionCollapse {
+    constraints: Constraints,
+    attempts: u32,
+    backtracks: u32,
+    connectivity: Connectivity,
+}
+
+impl WaveFunctionCollapse {
+    pub fn new(constraints: Constraints) -> WaveFunctionCollapse {
+        WaveFunctionCollapse {
+            constraints,
+            attempts: 10,
+            backtracks: 1000,
+            connectivity: Connectivity::RemovePockets,
+        }
+    }
+    /// Number of runs to abandon before giving up.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+    /// Number of choices a run may undo before it is abandoned.
+    pub fn backtracks(mut self, backtracks: u32) -> Self {
+        self.backtracks = backtracks;
+        self
+    }
+    pub fn connectivity(mut self, connectivity: Connectivity) -> Self {
+        self.connectivity = connectivity;
+        self
+    }
+    pub fn constraints(&self) -> &Constraints {
+        &self.constraints
+    }
+    /// Fill a grid the size of `pinned`, keeping every tile it sets, or `None` if no run
+    /// succeeded.
+    pub fn collapse(&self, pinned: &Grid<Option<Tile>>, rng: &mut GameRng) -> Option<Grid<Tile>> {
+        if self.constraints.is_empty() {
+            return None;
+        }
+        let mut initial = Wave::new(&self.constraints, pinned);
+        if !initial.propagate() {
+            return None;
+        }
+        initial.trail.clear();
+        for _ in 0..self.attempts {
+            let mut wave = initial.clone();
+            if wave.run(rng, self.backtracks) {
+                return Some(wave.tiles());
+            }
+        }
+        None
+    }
+}
+
+impl MapGenerator for WaveFunctionCollapse {
+    /// Produces a map of solid wall if every run fails.
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> GeneratedMap {
+        let mut map = GeneratedMap::new(width, height);
+        let inner = map.tiles.bounds().inflate(-1);
+        let pinned = Grid::from_fn(width, height, |point| if inner.contains(point) { None } else { Some(Tile::Wall) });
+        if let Some(tiles) = self.collapse(&pinned, rng) {
+            map.tiles = tiles;
+            connect(&mut map.tiles, self.connectivity);
+            place_spawn_and_stairs(&mut map);
+        }
+        map
+    }
+}
+
+impl MetaBuilder for WaveFunctionCollapse {
+    /// Tiles around each room that the sample does not contain, such as corridors, are not
+    /// pinned. Rooms that cannot be collapsed are left as they were.
+    fn apply(&self, map: &mut GeneratedMap, rng: &mut GameRng) {
+        for room in map.rooms.clone() {
+            let area = room.inflate(1).intersection(&map.tiles.bounds());
+            let pinned = Grid::from_fn(area.width, area.height, |offset| {
+                let tile = map.tiles[area.min() + offset];
+                if room.contains(area.min() + offset) || !self.constraints.has_tile(tile) {
+                    None
+                } else {
+                    Some(tile)
+                }
+            });
+            if let Some(tiles) = self.collapse(&pinned, rng) {
+                for point in room.points() {
+                    map.tiles.set(point, tiles[point - area.min()]);
+                }
+            }
+        }
+        connect(&mut map.tiles, Connectivity::Tunnel);
+        tidy(map);
+    }
+}
+
+/// Patterns still possible in every cell during a single run
+#[derive(Clone)]
+struct Wave<'a> {
+    constraints: &'a Constraints,
+    cells: Grid<()>,
+    possible: Vec<bool>,
+    /// Patterns in the neighbour in each direction still supporting a pattern in a cell
+    support: Vec<[u32; 4]>,
+    counts: Vec<usize>,
+    sum_weights: Vec<f64>,
+    sum_weight_logs: Vec<f64>,
+    /// Every pattern ruled out so far, in order
+    trail: Vec<(usize, usize)>,
+    pending: Vec<(usize, usize)>,
+    contradiction: bool,
+}
+
+impl<'a> Wave<'a> {
+    fn new(constraints: &'a Constraints, pinned: &Grid<Option<Tile>>) -> Wave<'a> {
+        let patterns = constraints.len();
+        let cells = pinned.map(|_| ());
+        let count = cells.cells().len();
+        let support = constraints.allowed.iter().map(|allowed| {
+            let mut support = [0; 4];
+            for (d, allowed) in allowed.iter().enumerate() {
+                support[d] = allowed.len() as u32;
+            }
+            support
+        });
+        let support: Vec<[u32; 4]> = support.collect();
+        let sum_weight: f64 = constraints.weights.iter().sum();
+        let sum_weight_log: f64 = constraints.weights.iter().map(|w| w * w.ln()).sum();
+        let mut wave = Wave {
+            constraints,
+            cells,
+            possible: vec![true; count * patterns],
+            support: (0..count).flat_map(|_| support.iter().cloned()).collect(),
+            counts: vec![patterns; count],
+            sum_weights: vec![sum_weight; count],
+            sum_weight_logs: vec![sum_weight_log; count],
+            trail: Vec::new(),
+            pending: Vec::new(),
+            contradiction: false,
+        };
+        for cell in 0..count {
+            let point = wave.cells.point(cell);
+            for pattern in 0..patterns {
+                let pinned_out = pinned[point].is_some_and(|tile| tile != constraints.tile(pattern));
+                let unsupported = DIRECTIONS
+                    .iter()
+                    .enumerate()
+                    .any(|(d, &direction)| wave.cells.in_bounds(point + direction) && constraints.allowed[pattern][d].is_empty());
+                if pinned_out || unsupported {
+                    wave.ban(cell, pattern);
+                }
+            }
+        }
+        wave
+    }
+
+    /// Collapse cells until every one holds a single pattern, returning false on failure.
+    fn run(&mut self, rng: &mut GameRng, max_backtracks: u32) -> bool {
+        let mut decisions: Vec<(usize, usize, usize)> = Vec::new();
+        let mut backtracks = 0;
+        while let Some(cell) = self.observe(rng) {
+            let pattern = self.choose(cell, rng);
+            decisions.push((cell, pattern, self.trail.len()));
+            let patterns = self.constraints.len();
+            for other in (0..patterns).filter(|&other| other != pattern) {
+                self.ban(cell, other);
+            }
+            let mut consistent = self.propagate();
+            while !consistent {
+                let (cell, pattern, mark) = match decisions.pop() {
+                    Some(decision) => decision,
+                    None => return false,
+                };
+                backtracks += 1;
+                if backtracks > max_backtracks {
+                    return false;
+                }
+                self.undo(mark);
+                self.ban(cell, pattern);
+                consistent = self.propagate();
+            }
+        }
+        true
+    }
+
+    /// Undecided cell with the least entropy, ties broken at random
+    fn observe(&self, rng: &mut GameRng) -> Option<usize> {
+        let mut best = None;
+        let mut lowest = f64::INFINITY;
+        for cell in 0..self.counts.len() {
+            if self.counts[cell] > 1 {
+                let sum = self.sum_weights[cell];
+                let entropy = sum.ln() - self.sum_weight_logs[cell] / sum + rng.next_f64() * 1e-6;
+                if entropy < lowest {
+                    lowest = entropy;
+                    best = Some(cell);
+                }
+            }
+        }
+        best
+    }
+
+    fn choose(&self, cell: usize, rng: &mut GameRng) -> usize {
+        let patterns = self.constraints.len();
+        let candidates = (0..patterns).filter(|&pattern| self.possible[cell * patterns + pattern]);
+        let total: f64 = candidates.clone().map(|pattern| self.constraints.weights[pattern]).sum();
+        let mut remaining = rng.next_f64() * total;
+        let mut chosen = None;
+        for pattern in candidates {
+            chosen = Some(pattern);
+            remaining -= self.constraints.weights[pattern];
+            if remaining < 0.0 {
+                break;
+            }
+        }
+        chosen.unwrap_or(0)
+    }
+
+    /// Rule out a pattern, deferring the effect on its neighbours until `propagate`.
+    fn ban(&mut self, cell: usize, pattern: usize) {
+        let index = cell * self.constraints.len() + pattern;
+        if !self.possible[index] {
+            return;
+        }
+        let weight = self.constraints.weights[pattern];
+        self.possible[index] = false;
+        self.counts[cell] -= 1;
+        self.sum_weights[cell] -= weight;
+        self.sum_weight_logs[cell] -= weight * weight.ln();
+        if self.counts[cell] == 0 {
+            self.contradiction = true;
+        }
+        self.trail.push((cell, pattern));
+        self.pending.push((cell, pattern));
+    }
+
+    /// Remove support for everything ruled out since the last call, returning false if a cell
+    /// has run out of patterns.
+    ///
+    /// Propagation always runs to completion so that the trail can be undone exactly.
+    fn propagate(&mut self) -> bool {
+        let patterns = self.constraints.len();
+        while let Some((cell, pattern)) = self.pending.pop() {
+            let point = self.cells.point(cell);
+            for (d, &direction) in DIRECTIONS.iter().enumerate() {
+                let neighbor = match self.cells.index(point + direction) {
+                    Some(neighbor) => neighbor,
+                    None => continue,
+                };
+                for &other in &self.constraints.allowed[pattern][d] {
+                    let support = &mut self.support[neighbor * patterns + other][(d + 2) % 4];
+                    *support -= 1;
+                    if *support == 0 {
+                        self.ban(neighbor, other);
+                    }
+                }
+            }
+        }
+        !mem::replace(&mut self.contradiction, false)
+    }
+
+    /// Restore every pattern ruled out since the trail was `mark` long.
+    fn undo(&mut self, mark: usize) {
+        let patterns = self.constraints.len();
+        while self.trail.len() > mark {
+            let (cell, pattern) = match self.trail.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            let weight = self.constraints.weights[pattern];
+            self.possible[cell * patterns + pattern] = true;
+            self.counts[cell] += 1;
+            self.sum_weights[cell] += weight;
+            self.sum_weight_logs[cell] += weight * weight.ln();
+            let point = self.cells.point(cell);
+            for (d, &direction) in DIRECTIONS.iter().enumerate() {
+                if let Some(neighbor) = self.cells.index(point + direction) {
+                    for &other in &self.constraints.allowed[pattern][d] {
+                        self.support[neighbor * patterns + other][(d + 2) % 4] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn tiles(&self) -> Grid<Tile> {
+        let patterns = self.constraints.len();
+        Grid::from_fn(self.cells.width(), self.cells.height(), |point| {
+            let cell = self.cells.index(point).unwrap_or(0);
+            let pattern = (0..patterns).find(|&pattern| self.possible[cell * patterns + pattern]).unwrap_or(0);
+            self.constraints.tile(pattern)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mapgen::connect::walkable_regions;
+    use mapgen::{BspGenerator, Pipeline};
+    use rand::SeedableRng;
+
+    fn parse(rows: &[&str]) -> Grid<Tile> {
+        let cells = rows
+            .iter()
+            .flat_map(|row| {
+                row.chars().map(|c| match c {
+                    '#' => Tile::Wall,
+                    '+' => Tile::Door,
+                    _ => Tile::Floor,
+                })
+            })
+            .collect();
+        Grid::from_vec(rows[0].len() as i32, rows.len() as i32, cells).unwrap()
+    }
+
+    fn sample() -> Grid<Tile> {
+        parse(&[
+            "############",
+            "#....#.....#",
+            "#....#.....#",
+            "#....+.....#",
+            "#....#.....#",
+            "###+####.###",
+            "#......#.###",
+            "#......#...#",
+            "#..........#",
+            "############",
+        ])
+    }
+
+    #[test]
+    fn learns_constraints() {
+        let tiled = Constraints::learn(&sample(), Model::Tiled);
+        assert_eq!(tiled.len(), 3);
+        let (wall, floor, door) = (0, 1, 2);
+        assert_eq!((tiled.tile(wall), tiled.tile(floor), tiled.tile(door)), (Tile::Wall, Tile::Floor, Tile::Door));
+        assert!(tiled.allows(floor, Point::EAST, door) && tiled.allows(door, Point::WEST, floor));
+        assert!(!tiled.allows(door, Point::EAST, door));
+        assert!(tiled.is_satisfied(&sample()));
+        let overlapping = Constraints::learn(&sample(), Model::Overlapping(3));
+        assert!(overlapping.is_satisfied(&sample()));
+        assert!(!overlapping.is_satisfied(&parse(&["#.#", "...", "#.#"])));
+        assert!(Constraints::learn_rotated(&sample(), Model::Overlapping(3)).len() > overlapping.len());
+    }
+
+    #[test]
+    fn collapsed_maps_follow_the_sample() {
+        for &model in [Model::Tiled, Model::Overlapping(2), Model::Overlapping(3)].iter() {
+            let generator = WaveFunctionCollapse::new(Constraints::learn_rotated(&sample(), model));
+            let mut pinned = Grid::new(30, 20, None);
+            pinned.set(Point::new(0, 0), Some(Tile::Wall));
+            pinned.set(Point::new(10, 10), Some(Tile::Floor));
+            let tiles = generator.collapse(&pinned, &mut GameRng::from_seed(4)).expect("collapse");
+            assert!(generator.constraints().is_satisfied(&tiles), "{:?}", model);
+            assert_eq!(tiles[Point::new(10, 10)], Tile::Floor);
+            let map = generator.generate(30, 20, &mut GameRng::from_seed(4));
+            assert!(map.tiles.cells().iter().filter(|tile| tile.is_walkable()).count() > 30, "{:?}", model);
+            assert_eq!(map, generator.generate(30, 20, &mut GameRng::from_seed(4)));
+        }
+    }
+
+    #[test]
+    fn furnishes_rooms_in_a_pipeline() {
+        let pillars = parse(&["########", "#......#", "#.#....#", "#......#", "#....#.#", "#......#", "########"]);
+        let furnish = WaveFunctionCollapse::new(Constraints::learn(&pillars, Model::Overlapping(2)));
+        let pipeline = Pipeline::new(BspGenerator::new().door_chance(0.0)).with(furnish);
+        let (map, snapshots) = pipeline.generate_with_snapshots(50, 30, &mut GameRng::from_seed(6));
+        assert!(walkable_regions(&map.tiles).is_connected());
+        assert_ne!(snapshots[0].map.tiles, map.tiles);
+    }
+
+    #[test]
+    fn impossible_pins_fail() {
+        let generator = WaveFunctionCollapse::new(Constraints::learn(&sample(), Model::Tiled)).attempts(2);
+        let mut pinned = Grid::new(5, 5, None);
+        pinned.set(Point::new(2, 2), Some(Tile::Corridor));
+        assert!(generator.collapse(&pinned, &mut GameRng::from_seed(1)).is_none());
+        pinned.set(Point::new(2, 2), Some(Tile::Door));
+        pinned.set(Point::new(3, 2), Some(Tile::Door));
+        assert!(generator.collapse(&pinned, &mut GameRng::from_seed(1)).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Fractal Combinators
+//!
+
+use super::Noise;
+
+/// How octaves are shaped before being summed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FractalKind {
+    /// Fractional Brownian motion, plain sums of octaves for rolling terrain
+    Fbm,
+    /// Inverted absolute values, forming sharp ridges for mountain ranges
+    Ridged,
+    /// Absolute values, forming rounded billows for hills and clouds
+    Billow,
+}
+
+/// Octaves of a noise function at rising frequencies and falling amplitudes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fractal<N> {
+    source: N,
+    kind: FractalKind,
+    octaves: u32,
+    lacunarity: f64,
+    persistence: f64,
+}
+
+impl<N: Noise> Fractal<N> {
+    pub fn new(source: N) -> Fractal<N> {
+        Fractal {
+            source,
+            kind: FractalKind::Fbm,
+            octaves: 4,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+    pub fn kind(mut self, kind: FractalKind) -> Self {
+        self.kind = kind;
+        self
+    }
+    /// Number of octaves, at least one.
+    pub fn octaves(mut self, octaves: u32) -> Self {
+        self.octaves = octaves.max(1);
+        self
+    }
+    /// Frequency multiplier from one octave to the next.
+    pub fn lacunarity(mut self, lacunarity: f64) -> Self {
+        self.lacunarity = lacunarity;
+        self
+    }
+    /// Amplitude multiplier from one octave to the next.
+    pub fn persistence(mut self, persistence: f64) -> Self {
+        self.persistence = persistence;
+        self
+    }
+}
+
+impl<N: Noise> Noise for Fractal<N> {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let (mut frequency, mut amplitude) = (1.0, 1.0);
+        let (mut total, mut scale) = (0.0, 0.0);
+        for octave in 0..self.octaves {
+            // Offset each octave so their lattices do not line up at the origin.
+            let offset = f64::from(octave) * 17.31;
+            let value = self.source.get(x * frequency + offset, y * frequency + offset);
+            let shaped = match self.kind {
+                FractalKind::Fbm => value,
+                FractalKind::Ridged => (1.0 - value.abs()).powi(2) * 2.0 - 1.0,
+                FractalKind::Billow => value.abs() * 2.0 - 1.0,
+            };
+            total += shaped * amplitude;
+            scale += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        (total / scale).clamp(-1.0, 1.0)
+    }
+}
+
+/// Noise sampled at coordinates displaced by a second noise function, twisting its features
+#[derive(Clone, Debug, PartialEq)]
+pub struct DomainWarp<N, W> {
+    source: N,
+    warp: W,
+    strength: f64,
+}
+
+impl<N: Noise, W: Noise> DomainWarp<N, W> {
+    pub fn new(source: N, warp: W) -> DomainWarp<N, W> {
+        DomainWarp { source, warp, strength: 1.0 }
+    }
+    /// Greatest displacement, in the source's coordinates.
+    pub fn strength(mut self, strength: f64) -> Self {
+        self.strength = strength;
+        self
+    }
+}
+
+impl<N: Noise, W: Noise> Noise for DomainWarp<N, W> {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let dx = self.warp.get(x, y);
+        let dy = self.warp.get(x + 31.7, y + 47.3);
+        self.source.get(x + dx * self.strength, y + dy * self.strength)
+    }
+}
@@ -0,0 +1,106 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Gradient Noise
+//!
+
+use super::{fade, lerp, Noise, Permutation};
+use random::GameRng;
+
+/// Dot product of the offset from a lattice point with one of eight gradients chosen by `hash`
+fn gradient(hash: u8, x: f64, y: f64) -> f64 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+/// Ken Perlin's improved gradient noise
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Perlin {
+    permutation: Permutation,
+}
+
+impl Perlin {
+    pub fn new(rng: &mut GameRng) -> Perlin {
+        Perlin {
+            permutation: Permutation::new(rng),
+        }
+    }
+}
+
+impl Noise for Perlin {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (i, j) = (x0 as i64, y0 as i64);
+        let (fx, fy) = (x - x0, y - y0);
+        let hash = |di, dj| self.permutation.hash(i + di, j + dj);
+        let top = lerp(gradient(hash(0, 0), fx, fy), gradient(hash(1, 0), fx - 1.0, fy), fade(fx));
+        let bottom = lerp(gradient(hash(0, 1), fx, fy - 1.0), gradient(hash(1, 1), fx - 1.0, fy - 1.0), fade(fx));
+        lerp(top, bottom, fade(fy)).clamp(-1.0, 1.0)
+    }
+}
+
+/// Simplex noise, gradient noise on a triangular lattice with fewer directional artifacts than
+/// Perlin noise
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Simplex {
+    permutation: Permutation,
+}
+
+impl Simplex {
+    pub fn new(rng: &mut GameRng) -> Simplex {
+        Simplex {
+            permutation: Permutation::new(rng),
+        }
+    }
+}
+
+impl Noise for Simplex {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let skew = 0.5 * (3f64.sqrt() - 1.0);
+        let unskew = (3.0 - 3f64.sqrt()) / 6.0;
+        let s = (x + y) * skew;
+        let (i, j) = ((x + s).floor(), (y + s).floor());
+        let t = (i + j) * unskew;
+        let (x0, y0) = (x - (i - t), y - (j - t));
+        let (di, dj) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (0, 0, x0, y0),
+            (di, dj, x0 - f64::from(di) + unskew, y0 - f64::from(dj) + unskew),
+            (1, 1, x0 - 1.0 + 2.0 * unskew, y0 - 1.0 + 2.0 * unskew),
+        ];
+        let (i, j) = (i as i64, j as i64);
+        let total: f64 = corners
+            .iter()
+            .map(|&(ci, cj, cx, cy)| {
+                let falloff = 0.5 - cx * cx - cy * cy;
+                if falloff <= 0.0 {
+                    0.0
+                } else {
+                    falloff.powi(4) * gradient(self.permutation.hash(i + i64::from(ci), j + i64::from(cj)), cx, cy)
+                }
+            })
+            .sum();
+        (total * 70.0).clamp(-1.0, 1.0)
+    }
+}
@@ -0,0 +1,169 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Coherent Noise
+//!
+//! Smoothly varying random values for overworld and terrain generation. Every noise function is
+//! seeded from a [`GameRng`], usually the Engine's `Random::MAP` stream, returns values of
+//! roughly -1 to 1, and can be combined with [`Fractal`] and [`DomainWarp`] before being sampled
+//! into a [`Grid`] and thresholded into biomes.
+//!
+//! [`GameRng`]: struct.GameRng.html
+//! [`Fractal`]: struct.Fractal.html
+//! [`DomainWarp`]: struct.DomainWarp.html
+//! [`Grid`]: struct.Grid.html
+//!
+
+mod fractal;
+mod gradient;
+mod value;
+mod worley;
+
+pub use self::fractal::{DomainWarp, Fractal, FractalKind};
+pub use self::gradient::{Perlin, Simplex};
+pub use self::value::ValueNoise;
+pub use self::worley::{Worley, WorleyDistance, WorleyOutput};
+
+use map::Grid;
+use rand::Rng;
+use random::GameRng;
+
+/// Two dimensional noise function
+pub trait Noise {
+    /// Value at a point, roughly between -1 and 1.
+    fn get(&self, x: f64, y: f64) -> f64;
+
+    /// Sample a `width` x `height` grid, scaling cell coordinates by `frequency`.
+    ///
+    /// Lattice noise is zero at whole coordinates, so use a frequency below one.
+    fn grid(&self, width: i32, height: i32, frequency: f64) -> Grid<f32> {
+        Grid::from_fn(width, height, |point| self.get(f64::from(point.x) * frequency, f64::from(point.y) * frequency) as f32)
+    }
+}
+
+impl<N: Noise + ?Sized> Noise for &N {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        (**self).get(x, y)
+    }
+}
+
+impl<N: Noise + ?Sized> Noise for Box<N> {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        (**self).get(x, y)
+    }
+}
+
+/// Map every value to the first band whose upper limit it does not exceed, or to `above` if it
+/// exceeds them all.
+///
+/// Bands are `(limit, value)` pairs in increasing order of limit, such as
+/// `&[(-0.2, Water), (0.0, Sand), (0.5, Grass)]` with `above` set to `Mountain`.
+pub fn threshold<T: Clone>(values: &Grid<f32>, bands: &[(f32, T)], above: T) -> Grid<T> {
+    values.map(|&value| bands.iter().find(|&&(limit, _)| value <= limit).map_or_else(|| above.clone(), |(_, band)| band.clone()))
+}
+
+/// Stretch values linearly to fill 0 to 1.
+///
+/// A grid of a single value becomes all zeroes.
+pub fn normalize(values: &mut Grid<f32>) {
+    let min = values.cells().iter().cloned().fold(f32::INFINITY, f32::min);
+    let max = values.cells().iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+    let range = max - min;
+    for value in values.cells_mut() {
+        *value = if range > 0.0 { (*value - min) / range } else { 0.0 };
+    }
+}
+
+/// Shuffled lattice hash shared by the noise functions
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Permutation {
+    table: Vec<u8>,
+}
+
+impl Permutation {
+    fn new(rng: &mut GameRng) -> Permutation {
+        let mut table: Vec<u8> = (0..=255).collect();
+        rng.shuffle(&mut table);
+        let repeated = table.clone();
+        table.extend(repeated);
+        Permutation { table }
+    }
+    /// Pseudo random byte for a lattice point
+    fn hash(&self, x: i64, y: i64) -> u8 {
+        let x = (x & 255) as usize;
+        let y = (y & 255) as usize;
+        self.table[self.table[x] as usize + y]
+    }
+}
+
+/// Smootherstep, easing lattice interpolation so the noise has no visible seams.
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use map::Point;
+    use rand::SeedableRng;
+
+    fn sources(seed: u64) -> Vec<Box<dyn Noise>> {
+        let mut rng = GameRng::from_seed(seed);
+        vec![
+            Box::new(Perlin::new(&mut rng)),
+            Box::new(Simplex::new(&mut rng)),
+            Box::new(ValueNoise::new(&mut rng)),
+            Box::new(Worley::new(&mut rng)),
+            Box::new(Worley::new(&mut rng).distance(WorleyDistance::Manhattan).output(WorleyOutput::Difference)),
+            Box::new(Fractal::new(Perlin::new(&mut rng)).octaves(5)),
+            Box::new(Fractal::new(Simplex::new(&mut rng)).kind(FractalKind::Ridged)),
+            Box::new(Fractal::new(ValueNoise::new(&mut rng)).kind(FractalKind::Billow).lacunarity(1.8).persistence(0.6)),
+            Box::new(DomainWarp::new(Perlin::new(&mut rng), Simplex::new(&mut rng)).strength(2.0)),
+        ]
+    }
+
+    #[test]
+    fn noise_is_bounded_smooth_and_seeded() {
+        for (index, (noise, again)) in sources(1).iter().zip(sources(1).iter()).enumerate() {
+            let grid = noise.grid(64, 64, 0.061);
+            assert_eq!(grid, again.grid(64, 64, 0.061), "{}", index);
+            assert!(grid.cells().iter().all(|value| *value >= -1.0 && *value <= 1.0), "{}", index);
+            let min = grid.cells().iter().cloned().fold(f32::INFINITY, f32::min);
+            let max = grid.cells().iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+            assert!(max - min > 0.5, "{} spans {} to {}", index, min, max);
+            // Fractals add detail at up to eight times the frequency, so only check single octaves.
+            for point in grid.points().filter(|point| point.x > 0 && index < 5) {
+                let step = (grid[point] - grid[point + Point::WEST]).abs();
+                assert!(step < 0.5, "{} jumps by {} at {}", index, step, point);
+            }
+        }
+        let other = sources(2);
+        assert_ne!(sources(1)[0].grid(16, 16, 0.1), other[0].grid(16, 16, 0.1));
+    }
+
+    #[test]
+    fn thresholds_into_bands() {
+        let mut heights = Grid::from_fn(5, 1, |point| point.x as f32 * 10.0);
+        normalize(&mut heights);
+        assert_eq!(heights.cells(), &[0.0, 0.25, 0.5, 0.75, 1.0]);
+        let biomes = threshold(&heights, &[(0.2, 'w'), (0.5, 's')], 'm');
+        assert_eq!(biomes.cells(), &['w', 's', 's', 'm', 'm']);
+    }
+}
@@ -0,0 +1,51 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Value Noise
+//!
+
+use super::{fade, lerp, Noise, Permutation};
+use random::GameRng;
+
+/// Random values at lattice points, smoothly interpolated between them
+///
+/// Blockier than gradient noise, which suits some terrain styles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValueNoise {
+    permutation: Permutation,
+}
+
+impl ValueNoise {
+    pub fn new(rng: &mut GameRng) -> ValueNoise {
+        ValueNoise {
+            permutation: Permutation::new(rng),
+        }
+    }
+    fn lattice(&self, i: i64, j: i64) -> f64 {
+        f64::from(self.permutation.hash(i, j)) / 127.5 - 1.0
+    }
+}
+
+impl Noise for ValueNoise {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (i, j) = (x0 as i64, y0 as i64);
+        let (u, v) = (fade(x - x0), fade(y - y0));
+        let top = lerp(self.lattice(i, j), self.lattice(i + 1, j), u);
+        let bottom = lerp(self.lattice(i, j + 1), self.lattice(i + 1, j + 1), u);
+        lerp(top, bottom, v)
+    }
+}
@@ -0,0 +1,102 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Worley Noise
+//!
+
+use super::{Noise, Permutation};
+use random::GameRng;
+
+/// How distances to feature points are measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WorleyDistance {
+    /// Round cells
+    Euclidean,
+    /// Diamond shaped cells
+    Manhattan,
+    /// Square cells
+    Chebyshev,
+}
+
+/// Which distances make up the value of Worley noise
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WorleyOutput {
+    /// Distance to the nearest feature point, low at the heart of every cell
+    Nearest,
+    /// Distance to the second nearest feature point
+    SecondNearest,
+    /// Difference between the two, low along the borders between cells
+    Difference,
+}
+
+/// Cellular noise, measuring the distance to a random feature point in every lattice cell
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Worley {
+    xs: Permutation,
+    ys: Permutation,
+    distance: WorleyDistance,
+    output: WorleyOutput,
+}
+
+impl Worley {
+    pub fn new(rng: &mut GameRng) -> Worley {
+        Worley {
+            xs: Permutation::new(rng),
+            ys: Permutation::new(rng),
+            distance: WorleyDistance::Euclidean,
+            output: WorleyOutput::Nearest,
+        }
+    }
+    pub fn distance(mut self, distance: WorleyDistance) -> Self {
+        self.distance = distance;
+        self
+    }
+    pub fn output(mut self, output: WorleyOutput) -> Self {
+        self.output = output;
+        self
+    }
+}
+
+impl Noise for Worley {
+    fn get(&self, x: f64, y: f64) -> f64 {
+        let (i, j) = (x.floor() as i64, y.floor() as i64);
+        let (mut nearest, mut second) = (f64::INFINITY, f64::INFINITY);
+        for cj in j - 2..=j + 2 {
+            for ci in i - 2..=i + 2 {
+                let fx = ci as f64 + f64::from(self.xs.hash(ci, cj)) / 256.0;
+                let fy = cj as f64 + f64::from(self.ys.hash(ci, cj)) / 256.0;
+                let (dx, dy) = ((fx - x).abs(), (fy - y).abs());
+                let distance = match self.distance {
+                    WorleyDistance::Euclidean => (dx * dx + dy * dy).sqrt(),
+                    WorleyDistance::Manhattan => dx + dy,
+                    WorleyDistance::Chebyshev => dx.max(dy),
+                };
+                if distance < nearest {
+                    second = nearest;
+                    nearest = distance;
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+        }
+        let value = match self.output {
+            WorleyOutput::Nearest => nearest,
+            WorleyOutput::SecondNearest => second,
+            WorleyOutput::Difference => second - nearest,
+        };
+        (value * 2.0 - 1.0).clamp(-1.0, 1.0)
+    }
+}
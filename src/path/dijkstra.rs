@@ -0,0 +1,311 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Dijkstra Maps
+//!
+
+use super::{Pathable, ORTHOGONAL_COST};
+use map::{Grid, Point};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Point waiting to spread its value to its neighbours
+#[derive(Clone, Copy, Debug)]
+struct Pending {
+    value: f32,
+    point: Point,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Pending) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Pending) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lowest value first
+impl Ord for Pending {
+    fn cmp(&self, other: &Pending) -> Ordering {
+        other.value.total_cmp(&self.value)
+    }
+}
+
+/// Distance from every tile to the nearest of a set of weighted goals
+///
+/// Each goal starts at its own value, so a goal of `-10` is as attractive as a goal of `0` ten
+/// steps further away. Values are measured in orthogonal steps over tiles of cost 1, and
+/// unreachable tiles hold `f32::INFINITY`. Walking downhill approaches the goals; combining
+/// maps blends several desires into one, and inverting a map produces one that flees.
+///
+/// The same graph must be passed to every method of one map.
+#[derive(Clone, Debug)]
+pub struct DijkstraMap {
+    values: Grid<f32>,
+    sources: Grid<Option<usize>>,
+    goals: Vec<(Point, f32)>,
+    open: BinaryHeap<Pending>,
+    edges: Vec<(Point, u32)>,
+}
+
+impl DijkstraMap {
+    /// Multiplier Brogue uses when inverting an approach map into a flee map
+    pub const FLEE_COEFFICIENT: f32 = 1.2;
+
+    /// Create a map where every tile is unreachable.
+    pub fn new(width: i32, height: i32) -> DijkstraMap {
+        DijkstraMap {
+            values: Grid::new(width, height, f32::INFINITY),
+            sources: Grid::new(width, height, None),
+            goals: Vec::new(),
+            open: BinaryHeap::new(),
+            edges: Vec::new(),
+        }
+    }
+    pub fn width(&self) -> i32 {
+        self.values.width()
+    }
+    pub fn height(&self) -> i32 {
+        self.values.height()
+    }
+    /// Goals of the last computation
+    pub fn goals(&self) -> &[(Point, f32)] {
+        &self.goals
+    }
+    /// Value at a point, or `None` if it is out of bounds or unreachable.
+    pub fn value(&self, point: Point) -> Option<f32> {
+        self.values.get(point).cloned().filter(|value| value.is_finite())
+    }
+    pub fn values(&self) -> &Grid<f32> {
+        &self.values
+    }
+    /// Recompute the whole map from a new set of goals.
+    pub fn compute<G, I>(&mut self, graph: &G, goals: I)
+    where
+        G: Pathable<Node = Point>,
+        I: IntoIterator<Item = (Point, f32)>,
+    {
+        self.values.fill(f32::INFINITY);
+        self.sources.fill(None);
+        self.goals.clear();
+        self.goals.extend(goals);
+        for index in 0..self.goals.len() {
+            self.seed(index);
+        }
+        self.spread(graph);
+    }
+    /// Move or revalue some goals, given as `(index, point, value)`, recomputing only the tiles
+    /// that were closest to them.
+    ///
+    /// # Panics
+    /// If an index is not a goal of the last computation.
+    pub fn update_goals<G: Pathable<Node = Point>>(&mut self, graph: &G, moved: &[(usize, Point, f32)]) {
+        for &(index, point, value) in moved {
+            self.goals[index] = (point, value);
+        }
+        let mut stale = Vec::new();
+        for (index, source) in self.sources.cells_mut().iter_mut().enumerate() {
+            if let Some(goal) = *source {
+                if moved.iter().any(|&(moved, _, _)| moved == goal) {
+                    *source = None;
+                    stale.push(index);
+                }
+            }
+        }
+        for &index in &stale {
+            self.values.cells_mut()[index] = f32::INFINITY;
+        }
+        for index in stale {
+            self.edges.clear();
+            graph.successors(self.values.point(index), &mut self.edges);
+            for &(neighbor, _) in &self.edges {
+                if let Some(value) = self.value(neighbor) {
+                    self.open.push(Pending { value, point: neighbor });
+                }
+            }
+        }
+        for index in 0..self.goals.len() {
+            self.seed(index);
+        }
+        self.spread(graph);
+    }
+    /// Add `other`, multiplied by `weight`, to every tile reachable in both maps.
+    ///
+    /// The result no longer tracks goals and cannot be updated incrementally.
+    pub fn combine(&mut self, other: &DijkstraMap, weight: f32) {
+        for (point, value) in self.values.iter_mut() {
+            if let Some(other) = other.value(point) {
+                if value.is_finite() {
+                    *value += other * weight;
+                }
+            }
+        }
+        self.forget_goals();
+    }
+    /// Multiply every reachable tile by `factor`.
+    ///
+    /// The result no longer tracks goals and cannot be updated incrementally.
+    pub fn scale(&mut self, factor: f32) {
+        for value in self.values.cells_mut() {
+            if value.is_finite() {
+                *value *= factor;
+            }
+        }
+        self.forget_goals();
+    }
+    /// Turn an approach map into a flee map.
+    ///
+    /// Values are multiplied by `-coefficient` and the map rescanned, so walking downhill leads
+    /// away from the goals while still preferring open space over dead ends. Coefficients above
+    /// 1, such as `FLEE_COEFFICIENT`, make fleeing past the goal towards safety worthwhile.
+    pub fn invert<G: Pathable<Node = Point>>(&mut self, graph: &G, coefficient: f32) {
+        self.scale(-coefficient);
+        self.rescan(graph);
+    }
+    /// Lower every tile to at most its neighbours' values plus the cost of reaching them.
+    pub fn rescan<G: Pathable<Node = Point>>(&mut self, graph: &G) {
+        for index in 0..self.values.cells().len() {
+            let value = self.values.cells()[index];
+            if value.is_finite() {
+                let point = self.values.point(index);
+                self.open.push(Pending { value, point });
+            }
+        }
+        self.spread(graph);
+    }
+    /// Lowest neighbour reachable from `from` with a lower value than it, if any.
+    pub fn downhill<G: Pathable<Node = Point>>(&self, graph: &G, from: Point) -> Option<Point> {
+        let mut edges = Vec::new();
+        graph.successors(from, &mut edges);
+        let mut best = (from, self.value(from).unwrap_or(f32::INFINITY));
+        for (neighbor, _) in edges {
+            if let Some(value) = self.value(neighbor) {
+                if value < best.1 {
+                    best = (neighbor, value);
+                }
+            }
+        }
+        if best.0 == from {
+            None
+        } else {
+            Some(best.0)
+        }
+    }
+
+    fn forget_goals(&mut self) {
+        self.goals.clear();
+        self.sources.fill(None);
+    }
+    fn seed(&mut self, index: usize) {
+        let (point, value) = self.goals[index];
+        if let Some(current) = self.values.get_mut(point) {
+            if value < *current {
+                *current = value;
+                self.sources[point] = Some(index);
+                self.open.push(Pending { value, point });
+            }
+        }
+    }
+    fn spread<G: Pathable<Node = Point>>(&mut self, graph: &G) {
+        while let Some(Pending { value, point }) = self.open.pop() {
+            if value != self.values[point] {
+                continue;
+            }
+            let source = self.sources[point];
+            self.edges.clear();
+            graph.predecessors(point, &mut self.edges);
+            for &(neighbor, step) in &self.edges {
+                let total = value + step as f32 / ORTHOGONAL_COST as f32;
+                if let Some(current) = self.values.get_mut(neighbor) {
+                    if total < *current {
+                        *current = total;
+                        self.sources[neighbor] = source;
+                        self.open.push(Pending { value: total, point: neighbor });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::GridGraph;
+    use super::*;
+    use map::Neighborhood;
+    use rand::{Rng, SeedableRng};
+    use random::GameRng;
+
+    fn corridor(length: i32) -> GridGraph<'static, fn(Point) -> Option<u32>> {
+        GridGraph::new(length, 1, (|_| Some(1)) as fn(Point) -> Option<u32>)
+    }
+
+    #[test]
+    fn weighted_goals_attract() {
+        let graph = corridor(10);
+        let mut map = DijkstraMap::new(10, 1);
+        map.compute(&graph, vec![(Point::new(0, 0), 0.0), (Point::new(9, 0), -4.0)]);
+        assert_eq!(map.value(Point::new(2, 0)), Some(2.0));
+        assert_eq!(map.value(Point::new(3, 0)), Some(2.0));
+        assert_eq!(map.value(Point::new(4, 0)), Some(1.0));
+        assert_eq!(map.downhill(&graph, Point::new(2, 0)), Some(Point::new(1, 0)));
+        assert_eq!(map.downhill(&graph, Point::new(4, 0)), Some(Point::new(5, 0)));
+        assert_eq!(map.downhill(&graph, Point::new(9, 0)), None);
+    }
+
+    #[test]
+    fn inverted_maps_flee() {
+        let graph = corridor(10);
+        let mut map = DijkstraMap::new(10, 1);
+        map.compute(&graph, vec![(Point::new(3, 0), 0.0)]);
+        let approach = map.clone();
+        map.invert(&graph, DijkstraMap::FLEE_COEFFICIENT);
+        assert_eq!(map.downhill(&graph, Point::new(4, 0)), Some(Point::new(5, 0)));
+        assert_eq!(map.downhill(&graph, Point::new(2, 0)), Some(Point::new(1, 0)));
+        assert!(map.goals().is_empty());
+
+        map.combine(&approach, 2.0);
+        map.scale(0.5);
+        let expected = (-1.2 * 6.0 + 2.0 * 6.0) * 0.5;
+        assert!((map.value(Point::new(9, 0)).unwrap() - expected).abs() < 1e-4);
+    }
+
+    #[test]
+    fn incremental_updates_match_full_recompute() {
+        let mut rng = GameRng::from_seed(38);
+        let open = Grid::from_fn(25, 25, |_| rng.gen_range(0, 4) != 0);
+        let cost = |point: Point| if open[point] { Some(1) } else { None };
+        let graph = GridGraph::new(25, 25, &cost).with_neighborhood(Neighborhood::Four);
+        let random_goal = |rng: &mut GameRng| (Point::new(rng.gen_range(0, 25), rng.gen_range(0, 25)), -(rng.gen_range(0, 5) as f32));
+        let goals: Vec<(Point, f32)> = (0..4).map(|_| random_goal(&mut rng)).collect();
+        let mut incremental = DijkstraMap::new(25, 25);
+        incremental.compute(&graph, goals);
+        for _ in 0..10 {
+            let moved = [(rng.gen_range(0, 4), random_goal(&mut rng)), (rng.gen_range(0, 4), random_goal(&mut rng))];
+            let moved: Vec<(usize, Point, f32)> = moved.iter().map(|&(index, (point, value))| (index, point, value)).collect();
+            incremental.update_goals(&graph, &moved);
+            let mut full = DijkstraMap::new(25, 25);
+            full.compute(&graph, incremental.goals().to_vec());
+            assert_eq!(incremental.values(), full.values());
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Path Finder
+//!
+
+use super::Pathable;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+use std::mem;
+
+/// Node waiting to be expanded
+struct Open<N> {
+    priority: u32,
+    cost: u32,
+    node: N,
+}
+
+impl<N> PartialEq for Open<N> {
+    fn eq(&self, other: &Open<N>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N> Eq for Open<N> {}
+
+impl<N> PartialOrd for Open<N> {
+    fn partial_cmp(&self, other: &Open<N>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Lowest priority first, breaking ties towards the node furthest along its path.
+impl<N> Ord for Open<N> {
+    fn cmp(&self, other: &Open<N>) -> Ordering {
+        other.priority.cmp(&self.priority).then(self.cost.cmp(&other.cost))
+    }
+}
+
+/// Cheapest known way of reaching a node
+struct Visited<N> {
+    cost: u32,
+    parent: N,
+    closed: bool,
+}
+
+/// Open and visited sets of one search direction
+struct Frontier<N> {
+    open: BinaryHeap<Open<N>>,
+    visited: HashMap<N, Visited<N>>,
+}
+
+impl<N: Copy + Eq + Hash> Frontier<N> {
+    fn new() -> Frontier<N> {
+        Frontier {
+            open: BinaryHeap::new(),
+            visited: HashMap::new(),
+        }
+    }
+    fn reset(&mut self, start: N, priority: u32) {
+        self.open.clear();
+        self.visited.clear();
+        self.visited.insert(start, Visited { cost: 0, parent: start, closed: false });
+        self.open.push(Open { priority, cost: 0, node: start });
+    }
+    /// Pop the next node worth expanding and close it.
+    fn pop(&mut self) -> Option<(N, u32)> {
+        while let Some(Open { cost, node, .. }) = self.open.pop() {
+            let visited = self.visited.get_mut(&node).expect("Open node was never visited");
+            if !visited.closed && cost == visited.cost {
+                visited.closed = true;
+                return Some((node, cost));
+            }
+        }
+        None
+    }
+    /// Record a step to `node`, returning true if it is the cheapest way there so far.
+    fn relax(&mut self, node: N, parent: N, cost: u32, estimate: u32) -> bool {
+        match self.visited.entry(node) {
+            Entry::Occupied(mut entry) => {
+                let visited = entry.get_mut();
+                if visited.closed || cost >= visited.cost {
+                    return false;
+                }
+                visited.cost = cost;
+                visited.parent = parent;
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(Visited { cost, parent, closed: false });
+            }
+        }
+        self.open.push(Open {
+            priority: cost.saturating_add(estimate),
+            cost,
+            node,
+        });
+        true
+    }
+    /// Lowest priority still waiting to be expanded
+    fn peek(&self) -> Option<u32> {
+        self.open.peek().map(|open| open.priority)
+    }
+    fn cost(&self, node: N) -> Option<u32> {
+        self.visited.get(&node).map(|visited| visited.cost)
+    }
+    /// Append the nodes from `node` back to the start of the search.
+    fn trace(&self, mut node: N, path: &mut Vec<N>) {
+        loop {
+            path.push(node);
+            let parent = self.visited[&node].parent;
+            if parent == node {
+                return;
+            }
+            node = parent;
+        }
+    }
+}
+
+/// Reusable pathfinding buffers
+///
+/// Every search clears and refills the same buffers, so a long lived `PathFinder` stops
+/// allocating once they have grown to fit the largest search. Paths are written into a
+/// caller supplied `Vec` from start to goal, inclusive, and the total cost is returned.
+pub struct PathFinder<N> {
+    forward: Frontier<N>,
+    backward: Frontier<N>,
+    edges: Vec<(N, u32)>,
+    limit: Option<usize>,
+}
+
+impl<N: Copy + Eq + Hash> PathFinder<N> {
+    pub fn new() -> PathFinder<N> {
+        PathFinder {
+            forward: Frontier::new(),
+            backward: Frontier::new(),
+            edges: Vec::new(),
+            limit: None,
+        }
+    }
+    /// Give up after expanding `limit` nodes, keeping hopeless searches cheap.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+    /// Cheapest path from `start` to `goal` using A* and the graph's heuristic.
+    ///
+    /// Returns `None` if `goal` is unreachable or the search limit was reached.
+    pub fn astar<G>(&mut self, graph: &G, start: N, goal: N, path: &mut Vec<N>) -> Option<u32>
+    where
+        G: Pathable<Node = N>,
+    {
+        self.search(graph, start, |node| node == goal, |node| graph.heuristic(node, goal), path)
+    }
+    /// Cheapest path from `start` to the nearest node for which `is_goal` returns true.
+    ///
+    /// Returns `None` if no goal is reachable or the search limit was reached.
+    pub fn dijkstra<G, F>(&mut self, graph: &G, start: N, is_goal: F, path: &mut Vec<N>) -> Option<u32>
+    where
+        G: Pathable<Node = N>,
+        F: FnMut(N) -> bool,
+    {
+        self.search(graph, start, is_goal, |_| 0, path)
+    }
+    /// Cheapest path from `start` to `goal`, searching from both ends at once.
+    ///
+    /// Uses [`Pathable::predecessors`] for the backward search. Usually expands fewer nodes than
+    /// Dijkstra's algorithm when no good heuristic is available. Returns `None` if `goal` is
+    /// unreachable or the search limit was reached.
+    ///
+    /// [`Pathable::predecessors`]: trait.Pathable.html#method.predecessors
+    pub fn bidirectional<G>(&mut self, graph: &G, start: N, goal: N, path: &mut Vec<N>) -> Option<u32>
+    where
+        G: Pathable<Node = N>,
+    {
+        path.clear();
+        self.forward.reset(start, 0);
+        self.backward.reset(goal, 0);
+        let mut best = if start == goal { Some((0, start)) } else { None };
+        let mut expanded = 0;
+        while let (Some(forward), Some(backward)) = (self.forward.peek(), self.backward.peek()) {
+            if let Some((cost, _)) = best {
+                if forward.saturating_add(backward) >= cost {
+                    break;
+                }
+            }
+            if self.limit.is_some_and(|limit| expanded >= limit) {
+                return None;
+            }
+            expanded += 1;
+            let forwards = forward <= backward;
+            let (frontier, other) = if forwards {
+                (&mut self.forward, &self.backward)
+            } else {
+                (&mut self.backward, &self.forward)
+            };
+            let (node, cost) = match frontier.pop() {
+                Some(next) => next,
+                None => continue,
+            };
+            self.edges.clear();
+            if forwards {
+                graph.successors(node, &mut self.edges);
+            } else {
+                graph.predecessors(node, &mut self.edges);
+            }
+            for &(next, step) in &self.edges {
+                let total = cost.saturating_add(step);
+                if frontier.relax(next, node, total, 0) {
+                    if let Some(remaining) = other.cost(next) {
+                        let through = total.saturating_add(remaining);
+                        if best.is_none_or(|(cost, _)| through < cost) {
+                            best = Some((through, next));
+                        }
+                    }
+                }
+            }
+        }
+        let (cost, meeting) = best?;
+        self.forward.trace(meeting, path);
+        path.reverse();
+        path.pop();
+        self.backward.trace(meeting, path);
+        Some(cost)
+    }
+
+    fn search<G, F, H>(&mut self, graph: &G, start: N, mut is_goal: F, mut estimate: H, path: &mut Vec<N>) -> Option<u32>
+    where
+        G: Pathable<Node = N>,
+        F: FnMut(N) -> bool,
+        H: FnMut(N) -> u32,
+    {
+        path.clear();
+        self.forward.reset(start, estimate(start));
+        let mut edges = mem::take(&mut self.edges);
+        let mut expanded = 0;
+        let mut result = None;
+        while let Some((node, cost)) = self.forward.pop() {
+            if is_goal(node) {
+                self.forward.trace(node, path);
+                path.reverse();
+                result = Some(cost);
+                break;
+            }
+            if self.limit.is_some_and(|limit| expanded >= limit) {
+                break;
+            }
+            expanded += 1;
+            edges.clear();
+            graph.successors(node, &mut edges);
+            for &(next, step) in &edges {
+                let total = cost.saturating_add(step);
+                if self.forward.visited.get(&next).is_none_or(|visited| total < visited.cost) {
+                    let estimate = estimate(next);
+                    self.forward.relax(next, node, total, estimate);
+                }
+            }
+        }
+        self.edges = edges;
+        result
+    }
+}
+
+impl<N: Copy + Eq + Hash> Default for PathFinder<N> {
+    fn default() -> PathFinder<N> {
+        PathFinder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{GridGraph, Heuristic, HexGraph, DIAGONAL_COST, ORTHOGONAL_COST};
+    use super::*;
+    use hex::HexLayout;
+    use map::{Grid, Neighborhood, Point, Topology};
+    use rand::{Rng, SeedableRng};
+    use random::GameRng;
+
+    fn parse(rows: &[&str]) -> Grid<bool> {
+        let cells = rows.iter().flat_map(|row| row.chars().map(|c| c != '#')).collect();
+        Grid::from_vec(rows[0].len() as i32, rows.len() as i32, cells).unwrap()
+    }
+
+    fn open(map: &Grid<bool>) -> impl Fn(Point) -> Option<u32> + '_ {
+        move |point| if map[point] { Some(1) } else { None }
+    }
+
+    /// Weighted directed graph of named nodes
+    struct Roads(Vec<(&'static str, &'static str, u32)>);
+
+    impl Pathable for Roads {
+        type Node = &'static str;
+        fn successors(&self, node: &'static str, out: &mut Vec<(&'static str, u32)>) {
+            out.extend(self.0.iter().filter(|road| road.0 == node).map(|road| (road.1, road.2)));
+        }
+        fn predecessors(&self, node: &'static str, out: &mut Vec<(&'static str, u32)>) {
+            out.extend(self.0.iter().filter(|road| road.1 == node).map(|road| (road.0, road.2)));
+        }
+    }
+
+    #[test]
+    fn paths_around_walls() {
+        let map = parse(&[
+            ".....",
+            ".###.",
+            "...#.",
+        ]);
+        let graph = GridGraph::new(5, 3, open(&map));
+        let mut finder = PathFinder::new();
+        let mut path = Vec::new();
+        let cost = finder.astar(&graph, Point::new(0, 2), Point::new(4, 2), &mut path).unwrap();
+        assert_eq!(cost, 4 * ORTHOGONAL_COST + 2 * DIAGONAL_COST);
+        assert_eq!(path.first(), Some(&Point::new(0, 2)));
+        assert_eq!(path.last(), Some(&Point::new(4, 2)));
+        assert_eq!(path.len(), 7);
+
+        let four = GridGraph::new(5, 3, open(&map)).with_neighborhood(Neighborhood::Four);
+        assert_eq!(finder.astar(&four, Point::new(0, 2), Point::new(4, 2), &mut path), Some(8 * ORTHOGONAL_COST));
+        assert!(path.windows(2).all(|step| step[0].manhattan(step[1]) == 1));
+        assert_eq!(finder.astar(&four, Point::new(0, 2), Point::new(2, 1), &mut path), None);
+        assert!(path.is_empty());
+    }
+
+    #[test]
+    fn blocked_overlay_and_limits() {
+        let map = Grid::new(5, 1, true);
+        let monster = |point: Point| point == Point::new(2, 0);
+        let graph = GridGraph::new(5, 1, open(&map)).with_blocked(&monster);
+        let mut finder = PathFinder::new();
+        let mut path = Vec::new();
+        assert_eq!(finder.astar(&graph, Point::new(0, 0), Point::new(4, 0), &mut path), None);
+
+        let graph = GridGraph::new(5, 1, open(&map));
+        finder.set_limit(Some(2));
+        assert_eq!(finder.astar(&graph, Point::new(0, 0), Point::new(4, 0), &mut path), None);
+        assert_eq!(finder.bidirectional(&graph, Point::new(0, 0), Point::new(4, 0), &mut path), None);
+        finder.set_limit(None);
+        assert_eq!(finder.astar(&graph, Point::new(0, 0), Point::new(4, 0), &mut path), Some(4 * ORTHOGONAL_COST));
+    }
+
+    #[test]
+    fn searches_agree_on_cost() {
+        let mut rng = GameRng::from_seed(37);
+        let map = Grid::from_fn(30, 30, |_| rng.gen_range(0, 3) != 0);
+        let costs = Grid::from_fn(30, 30, |_| rng.gen_range(1, 4));
+        let cost = |point: Point| if map[point] { Some(costs[point]) } else { None };
+        let mut finder = PathFinder::new();
+        let mut path = Vec::new();
+        for &heuristic in [Heuristic::Octile, Heuristic::Chebyshev, Heuristic::Manhattan].iter() {
+            let neighborhood = if heuristic == Heuristic::Manhattan { Neighborhood::Four } else { Neighborhood::Eight };
+            let graph = GridGraph::new(30, 30, &cost).with_neighborhood(neighborhood).with_heuristic(heuristic);
+            for _ in 0..20 {
+                let start = Point::new(rng.gen_range(0, 30), rng.gen_range(0, 30));
+                let goal = Point::new(rng.gen_range(0, 30), rng.gen_range(0, 30));
+                if !map[start] {
+                    continue;
+                }
+                let expected = finder.dijkstra(&graph, start, |node| node == goal, &mut path);
+                assert_eq!(finder.astar(&graph, start, goal, &mut path), expected);
+                let walked: u32 = path.windows(2).map(|step| {
+                    let diagonal = step[0].x != step[1].x && step[0].y != step[1].y;
+                    cost(step[1]).unwrap() * if diagonal { DIAGONAL_COST } else { ORTHOGONAL_COST }
+                }).sum();
+                assert_eq!(expected.map(|_| walked), expected);
+                assert_eq!(finder.bidirectional(&graph, start, goal, &mut path), expected);
+                assert_eq!(expected.is_some(), path.first() == Some(&start) && path.last() == Some(&goal));
+            }
+        }
+    }
+
+    #[test]
+    fn searches_arbitrary_graphs() {
+        let roads = Roads(vec![("town", "forest", 5), ("forest", "cave", 5), ("town", "river", 2), ("river", "cave", 4), ("cave", "town", 1)]);
+        let mut finder = PathFinder::new();
+        let mut path = Vec::new();
+        assert_eq!(finder.astar(&roads, "town", "cave", &mut path), Some(6));
+        assert_eq!(path, vec!["town", "river", "cave"]);
+        assert_eq!(finder.bidirectional(&roads, "forest", "river", &mut path), Some(8));
+        assert_eq!(path, vec!["forest", "cave", "town", "river"]);
+        assert_eq!(finder.dijkstra(&roads, "cave", |node| node.starts_with('f'), &mut path), Some(6));
+    }
+
+    #[test]
+    fn paths_across_hexes() {
+        let map = parse(&[
+            "......",
+            ".####.",
+            "......",
+            "......",
+        ]);
+        let layout = HexLayout::OddRows;
+        let graph = HexGraph::new(6, 4, layout, open(&map));
+        let mut finder = PathFinder::new();
+        let mut path = Vec::new();
+        let cost = finder.astar(&graph, Point::new(0, 0), Point::new(5, 3), &mut path).unwrap();
+        assert_eq!(cost, layout.distance(Point::new(0, 0), Point::new(5, 3)) as u32 * ORTHOGONAL_COST);
+        assert!(path.windows(2).all(|step| layout.distance(step[0], step[1]) == 1));
+        assert!(path.iter().all(|&point| map[point]));
+        assert_eq!(finder.bidirectional(&graph, Point::new(0, 0), Point::new(5, 3), &mut path), Some(cost));
+
+        let cost = finder.astar(&graph, Point::new(2, 0), Point::new(2, 2), &mut path).unwrap();
+        assert_eq!(cost, 4 * ORTHOGONAL_COST);
+    }
+}
@@ -0,0 +1,118 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Grid Graph
+//!
+
+use super::{Heuristic, Pathable, DIAGONAL_COST, ORTHOGONAL_COST};
+use map::{Neighborhood, Point};
+
+/// Grid map viewed as a graph for pathfinding
+///
+/// `cost` returns the cost of entering a tile, or `None` if it cannot be entered. Step costs are
+/// scaled by `ORTHOGONAL_COST` and `DIAGONAL_COST`. Points outside of `width` x `height` are never
+/// entered. Tiles should cost at least 1, or the heuristics may overestimate.
+pub struct GridGraph<'a, F> {
+    width: i32,
+    height: i32,
+    cost: F,
+    neighborhood: Neighborhood,
+    heuristic: Heuristic,
+    blocked: Option<&'a dyn Fn(Point) -> bool>,
+}
+
+impl<'a, F: Fn(Point) -> Option<u32>> GridGraph<'a, F> {
+    /// Create a graph with eight way movement and the octile heuristic.
+    pub fn new(width: i32, height: i32, cost: F) -> GridGraph<'a, F> {
+        GridGraph {
+            width,
+            height,
+            cost,
+            neighborhood: Neighborhood::Eight,
+            heuristic: Heuristic::Octile,
+            blocked: None,
+        }
+    }
+    /// Set the movement directions. Four way movement also switches to the Manhattan heuristic.
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        if neighborhood == Neighborhood::Four {
+            self.heuristic = Heuristic::Manhattan;
+        }
+        self
+    }
+    pub fn with_heuristic(mut self, heuristic: Heuristic) -> Self {
+        self.heuristic = heuristic;
+        self
+    }
+    /// Treat tiles for which `blocked` returns true as impassable, such as tiles holding monsters.
+    ///
+    /// Exempt the goal from the overlay when pathing towards another Entity.
+    pub fn with_blocked(mut self, blocked: &'a dyn Fn(Point) -> bool) -> Self {
+        self.blocked = Some(blocked);
+        self
+    }
+    /// Cost of entering `point`, or `None` if it cannot be entered.
+    pub fn cost(&self, point: Point) -> Option<u32> {
+        if !self.contains(point) {
+            return None;
+        }
+        if let Some(blocked) = self.blocked {
+            if blocked(point) {
+                return None;
+            }
+        }
+        (self.cost)(point)
+    }
+    fn contains(&self, point: Point) -> bool {
+        point.x >= 0 && point.y >= 0 && point.x < self.width && point.y < self.height
+    }
+    fn step(offset: Point) -> u32 {
+        if offset.x != 0 && offset.y != 0 {
+            DIAGONAL_COST
+        } else {
+            ORTHOGONAL_COST
+        }
+    }
+}
+
+impl<'a, F: Fn(Point) -> Option<u32>> Pathable for GridGraph<'a, F> {
+    type Node = Point;
+
+    fn successors(&self, node: Point, out: &mut Vec<(Point, u32)>) {
+        for &offset in self.neighborhood.offsets() {
+            if let Some(cost) = self.cost(node + offset) {
+                out.push((node + offset, cost.saturating_mul(Self::step(offset))));
+            }
+        }
+    }
+
+    fn predecessors(&self, node: Point, out: &mut Vec<(Point, u32)>) {
+        let cost = match self.cost(node) {
+            Some(cost) => cost,
+            None => return,
+        };
+        for &offset in self.neighborhood.offsets() {
+            if self.contains(node + offset) {
+                out.push((node + offset, cost.saturating_mul(Self::step(offset))));
+            }
+        }
+    }
+
+    fn heuristic(&self, from: Point, to: Point) -> u32 {
+        self.heuristic.estimate(from, to)
+    }
+}
@@ -0,0 +1,100 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Hex Graph
+//!
+
+use super::{Pathable, ORTHOGONAL_COST};
+use hex::HexLayout;
+use map::{Point, Topology};
+
+/// Hex map stored in offset coordinates viewed as a graph for pathfinding
+///
+/// Works like a [`GridGraph`], with every step onto a tile of cost 1 costing `ORTHOGONAL_COST`
+/// and the hex distance as the heuristic.
+///
+/// [`GridGraph`]: struct.GridGraph.html
+pub struct HexGraph<'a, F> {
+    width: i32,
+    height: i32,
+    cost: F,
+    layout: HexLayout,
+    blocked: Option<&'a dyn Fn(Point) -> bool>,
+}
+
+impl<'a, F: Fn(Point) -> Option<u32>> HexGraph<'a, F> {
+    pub fn new(width: i32, height: i32, layout: HexLayout, cost: F) -> HexGraph<'a, F> {
+        HexGraph {
+            width,
+            height,
+            cost,
+            layout,
+            blocked: None,
+        }
+    }
+    /// Treat tiles for which `blocked` returns true as impassable, such as tiles holding monsters.
+    ///
+    /// Exempt the goal from the overlay when pathing towards another Entity.
+    pub fn with_blocked(mut self, blocked: &'a dyn Fn(Point) -> bool) -> Self {
+        self.blocked = Some(blocked);
+        self
+    }
+    /// Cost of entering `point`, or `None` if it cannot be entered.
+    pub fn cost(&self, point: Point) -> Option<u32> {
+        if !self.contains(point) {
+            return None;
+        }
+        if let Some(blocked) = self.blocked {
+            if blocked(point) {
+                return None;
+            }
+        }
+        (self.cost)(point)
+    }
+    fn contains(&self, point: Point) -> bool {
+        point.x >= 0 && point.y >= 0 && point.x < self.width && point.y < self.height
+    }
+}
+
+impl<'a, F: Fn(Point) -> Option<u32>> Pathable for HexGraph<'a, F> {
+    type Node = Point;
+
+    fn successors(&self, node: Point, out: &mut Vec<(Point, u32)>) {
+        for direction in 0..self.layout.directions() {
+            let next = self.layout.neighbor(node, direction);
+            if let Some(cost) = self.cost(next) {
+                out.push((next, cost.saturating_mul(ORTHOGONAL_COST)));
+            }
+        }
+    }
+
+    fn predecessors(&self, node: Point, out: &mut Vec<(Point, u32)>) {
+        let cost = match self.cost(node) {
+            Some(cost) => cost,
+            None => return,
+        };
+        for direction in 0..self.layout.directions() {
+            let previous = self.layout.neighbor(node, direction);
+            if self.contains(previous) {
+                out.push((previous, cost.saturating_mul(ORTHOGONAL_COST)));
+            }
+        }
+    }
+
+    fn heuristic(&self, from: Point, to: Point) -> u32 {
+        self.layout.distance(from, to) as u32 * ORTHOGONAL_COST
+    }
+}
@@ -0,0 +1,88 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Pathfinding
+//!
+//! Searches run over any graph implementing [`Pathable`], with [`GridGraph`] adapting grid maps
+//! and [`HexGraph`] adapting hex maps.
+//! A [`PathFinder`] owns every buffer a search needs, so keeping one around and reusing it avoids
+//! allocating once its buffers have grown to fit the map.
+//!
+//! [`Pathable`]: trait.Pathable.html
+//! [`GridGraph`]: struct.GridGraph.html
+//! [`HexGraph`]: struct.HexGraph.html
+//! [`PathFinder`]: struct.PathFinder.html
+//!
+
+mod dijkstra;
+mod finder;
+mod grid;
+mod hex;
+
+pub use self::dijkstra::DijkstraMap;
+pub use self::finder::PathFinder;
+pub use self::grid::GridGraph;
+pub use self::hex::HexGraph;
+
+use map::Point;
+use std::hash::Hash;
+
+/// Cost of an orthogonal step onto a tile of cost 1
+pub const ORTHOGONAL_COST: u32 = 100;
+/// Cost of a diagonal step onto a tile of cost 1, approximately `ORTHOGONAL_COST * sqrt(2)`
+pub const DIAGONAL_COST: u32 = 141;
+
+/// Graph that can be searched for paths
+pub trait Pathable {
+    type Node: Copy + Eq + Hash;
+    /// Append every node reachable in one step from `node`, along with the cost of the step.
+    fn successors(&self, node: Self::Node, out: &mut Vec<(Self::Node, u32)>);
+    /// Append every node that can reach `node` in one step, along with the cost of the step.
+    ///
+    /// Only used by bidirectional search. The default assumes every edge can be travelled both
+    /// ways at the same cost.
+    fn predecessors(&self, node: Self::Node, out: &mut Vec<(Self::Node, u32)>) {
+        self.successors(node, out)
+    }
+    /// Estimated cost from `from` to `to`. Must never overestimate for A* to find the cheapest path.
+    fn heuristic(&self, _from: Self::Node, _to: Self::Node) -> u32 {
+        0
+    }
+}
+
+/// Distance estimate between two grid points
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Heuristic {
+    /// Exact for four way movement
+    Manhattan,
+    /// Exact for eight way movement where diagonal steps cost the same as orthogonal ones
+    Chebyshev,
+    /// Exact for eight way movement with diagonal steps costing `DIAGONAL_COST`
+    Octile,
+}
+
+impl Heuristic {
+    /// Estimated cost between two points over tiles of cost 1.
+    pub fn estimate(self, from: Point, to: Point) -> u32 {
+        let dx = (from.x - to.x).unsigned_abs();
+        let dy = (from.y - to.y).unsigned_abs();
+        match self {
+            Heuristic::Manhattan => (dx + dy) * ORTHOGONAL_COST,
+            Heuristic::Chebyshev => dx.max(dy) * ORTHOGONAL_COST,
+            Heuristic::Octile => (dx.max(dy) - dx.min(dy)) * ORTHOGONAL_COST + dx.min(dy) * DIAGONAL_COST,
+        }
+    }
+}
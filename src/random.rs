@@ -0,0 +1,191 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Random Number Service
+//!
+
+use rand::{self, Rng, SeedableRng};
+use std::collections::BTreeMap;
+
+/// Seedable, serializable random number generator
+///
+/// A PCG-XSH-RR generator with 64 bits of state producing 32 bit outputs. It implements
+/// [`rand::Rng`], so all of `rand`'s sampling helpers (`gen_range`, `choose`, `shuffle`, ...)
+/// are available, and its complete state is written into saves.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRng {
+    state: u64,
+    increment: u64,
+}
+
+const MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+impl GameRng {
+    /// Create a generator from a seed and a stream selector. Generators with the same seed but
+    /// different streams produce unrelated sequences.
+    pub fn new(seed: u64, stream: u64) -> GameRng {
+        let mut rng = GameRng {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+    fn step(&mut self) {
+        self.state = self.state.wrapping_mul(MULTIPLIER).wrapping_add(self.increment);
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.step();
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+}
+
+impl SeedableRng<u64> for GameRng {
+    fn reseed(&mut self, seed: u64) {
+        *self = GameRng::from_seed(seed);
+    }
+    fn from_seed(seed: u64) -> GameRng {
+        GameRng::new(seed, 0)
+    }
+}
+
+/// Random Number Service
+///
+/// Owns a master seed and any number of named streams derived from it. Each stream depends only
+/// on the master seed and its name, so drawing from one stream never disturbs another: the same
+/// seed produces the same dungeon from the [`MAP`] stream no matter how many [`COMBAT`] rolls
+/// were made in between.
+///
+/// [`MAP`]: #associatedconstant.MAP
+/// [`COMBAT`]: #associatedconstant.COMBAT
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Random {
+    seed: u64,
+    master: GameRng,
+    streams: BTreeMap<String, GameRng>,
+}
+
+impl Random {
+    /// Stream for map generation
+    pub const MAP: &'static str = "map";
+    /// Stream for combat resolution
+    pub const COMBAT: &'static str = "combat";
+    /// Stream for loot and spawn tables
+    pub const LOOT: &'static str = "loot";
+
+    /// Create the service from a master seed.
+    pub fn new(seed: u64) -> Random {
+        Random {
+            seed,
+            master: GameRng::new(seed, 0),
+            streams: BTreeMap::new(),
+        }
+    }
+    /// Create the service from a master seed chosen by the operating system.
+    pub fn from_entropy() -> Random {
+        Random::new(rand::thread_rng().next_u64())
+    }
+    /// Master seed
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+    /// Restart the master generator and every stream from a new seed.
+    pub fn reseed(&mut self, seed: u64) {
+        *self = Random::new(seed);
+    }
+    /// General purpose generator for draws which do not belong to a named stream.
+    pub fn master(&mut self) -> &mut GameRng {
+        &mut self.master
+    }
+    /// Named stream, created from the master seed on first use.
+    pub fn stream(&mut self, name: &str) -> &mut GameRng {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                let hash = fnv1a(name.as_bytes());
+                GameRng::new(mix(seed ^ hash), mix(hash))
+            })
+    }
+    /// Derive an independent seed, for example to seed a level generator.
+    pub fn derive_seed(&self, name: &str) -> u64 {
+        mix(self.seed ^ mix(fnv1a(name.as_bytes())))
+    }
+}
+
+/// 64 bit FNV-1a hash of a stream name
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// SplitMix64 finalizer
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+
+    fn draw(rng: &mut GameRng) -> Vec<u32> {
+        (0..8).map(|_| rng.gen_range(0, 1000)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut first = Random::new(1234);
+        let mut second = Random::new(1234);
+        assert_eq!(draw(first.master()), draw(second.master()));
+        assert_eq!(draw(first.stream(Random::MAP)), draw(second.stream(Random::MAP)));
+        assert!(draw(Random::new(1235).master()) != draw(Random::new(1234).master()));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut quiet = Random::new(99);
+        let mut busy = Random::new(99);
+        for _ in 0..100 {
+            busy.stream(Random::COMBAT).next_u32();
+            busy.master().next_u32();
+        }
+        assert_eq!(draw(quiet.stream(Random::MAP)), draw(busy.stream(Random::MAP)));
+        assert!(draw(quiet.stream(Random::LOOT)) != draw(quiet.stream(Random::MAP)));
+        assert!(quiet.derive_seed("level-1") != quiet.derive_seed("level-2"));
+    }
+
+    #[test]
+    fn serialized_state_continues() {
+        let mut random = Random::new(7);
+        draw(random.stream(Random::COMBAT));
+        let json = serde_json::to_string(&random).unwrap();
+        let mut restored: Random = serde_json::from_str(&json).unwrap();
+        assert_eq!(draw(random.stream(Random::COMBAT)), draw(restored.stream(Random::COMBAT)));
+        assert_eq!(restored.seed(), 7);
+    }
+}
@@ -0,0 +1,298 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Autosave
+//!
+
+use super::{SaveError, SaveSlots};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Safe points at which an autosave may be taken
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AutosaveTrigger {
+    /// The active State was suspended by a pushed State.
+    Suspend,
+    /// A turn ended, carrying the number of turns taken so far.
+    Turn(u64),
+    /// The player moved to another level.
+    LevelChange,
+    /// The State stack is being halted.
+    Halt,
+}
+
+/// Autosave Policy
+///
+/// Selects which [`AutosaveTrigger`]s write an autosave and which slot it is written to.
+///
+/// [`AutosaveTrigger`]: enum.AutosaveTrigger.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AutosavePolicy {
+    slot: String,
+    on_suspend: bool,
+    every_turns: Option<u64>,
+    on_level_change: bool,
+    on_halt: bool,
+}
+
+impl AutosavePolicy {
+    /// Policy writing to the given slot, with every trigger disabled.
+    pub fn new(slot: &str) -> AutosavePolicy {
+        AutosavePolicy {
+            slot: slot.to_string(),
+            on_suspend: false,
+            every_turns: None,
+            on_level_change: false,
+            on_halt: false,
+        }
+    }
+    /// Autosave whenever a State is suspended.
+    pub fn on_suspend(mut self, enabled: bool) -> AutosavePolicy {
+        self.on_suspend = enabled;
+        self
+    }
+    /// Autosave every `turns` turns, or never if `turns` is zero.
+    pub fn every_turns(mut self, turns: u64) -> AutosavePolicy {
+        self.every_turns = if turns > 0 { Some(turns) } else { None };
+        self
+    }
+    /// Autosave on level transitions.
+    pub fn on_level_change(mut self, enabled: bool) -> AutosavePolicy {
+        self.on_level_change = enabled;
+        self
+    }
+    /// Autosave when the State stack is halted.
+    pub fn on_halt(mut self, enabled: bool) -> AutosavePolicy {
+        self.on_halt = enabled;
+        self
+    }
+    pub fn slot(&self) -> &str {
+        &self.slot
+    }
+    /// Check if the policy autosaves on a trigger.
+    pub fn wants(&self, trigger: AutosaveTrigger) -> bool {
+        match trigger {
+            AutosaveTrigger::Suspend => self.on_suspend,
+            AutosaveTrigger::Turn(turn) => self.every_turns.map(|every| turn % every == 0).unwrap_or(false),
+            AutosaveTrigger::LevelChange => self.on_level_change,
+            AutosaveTrigger::Halt => self.on_halt,
+        }
+    }
+}
+
+/// Deferred serialization of a captured save
+pub(crate) type Job = Box<dyn FnOnce() -> Result<Vec<u8>, SaveError> + Send>;
+
+enum Request {
+    Write(SaveSlots, String, Job),
+    Flush(Sender<()>),
+}
+
+/// Background writer for autosaves
+///
+/// The game is captured on the game thread; serializing the capture and writing the slot happen
+/// here. Writes are processed in order by a single worker so two autosaves never race on the
+/// same slot.
+pub(crate) struct Autosaver {
+    policy: AutosavePolicy,
+    requests: Option<Sender<Request>>,
+    errors: Receiver<SaveError>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Autosaver {
+    pub(crate) fn new(policy: AutosavePolicy) -> Autosaver {
+        let (requests, pending) = channel();
+        let (failures, errors) = channel();
+        let worker = thread::Builder::new()
+            .name("oxide-autosave".to_string())
+            .spawn(move || {
+                for request in pending {
+                    match request {
+                        Request::Write(slots, slot, job) => {
+                            if let Err(error) = job().and_then(|bytes| slots.write(&slot, &bytes)) {
+                                let _ = failures.send(error);
+                            }
+                        }
+                        Request::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("Unable to spawn autosave thread");
+        Autosaver {
+            policy,
+            requests: Some(requests),
+            errors,
+            worker: Some(worker),
+        }
+    }
+    pub(crate) fn policy(&self) -> &AutosavePolicy {
+        &self.policy
+    }
+    /// Queue a captured save for serializing and writing.
+    pub(crate) fn submit(&self, slots: SaveSlots, job: Job) {
+        if let Some(ref requests) = self.requests {
+            let _ = requests.send(Request::Write(slots, self.policy.slot.clone(), job));
+        }
+    }
+    /// Block until every queued autosave has been written.
+    pub(crate) fn flush(&self) {
+        if let Some(ref requests) = self.requests {
+            let (done, wait) = channel();
+            if requests.send(Request::Flush(done)).is_ok() {
+                let _ = wait.recv();
+            }
+        }
+    }
+    /// Errors from autosaves written since the last call.
+    pub(crate) fn errors(&self) -> Vec<SaveError> {
+        self.errors.try_iter().collect()
+    }
+}
+
+impl Drop for Autosaver {
+    fn drop(&mut self) {
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::state::{State, StateManager};
+    use super::super::super::{Data, Engine, Event, Transition, Version};
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Depth(u32);
+
+    impl Data for Depth {}
+
+    struct Explore(bool);
+
+    struct Inventory;
+
+    impl State<Depth> for Explore {
+        fn initialize(&mut self, _engine: &mut Engine<Depth>) {}
+        fn cleanup(&mut self, _engine: &mut Engine<Depth>) {}
+        fn suspend(&mut self, engine: &mut Engine<Depth>) {
+            engine.data().0 = 3;
+        }
+        fn resume(&mut self, _engine: &mut Engine<Depth>) {}
+        fn handle(&mut self, _engine: &mut Engine<Depth>, _event: Event) -> Transition<Depth> {
+            Transition::Continue
+        }
+        fn update(&mut self, _engine: &mut Engine<Depth>, _delta: f64) -> Transition<Depth> {
+            if self.0 {
+                self.0 = false;
+                Transition::Push(Box::new(Inventory))
+            } else {
+                Transition::Halt
+            }
+        }
+        fn render(&mut self, _engine: &mut Engine<Depth>) {}
+    }
+
+    impl State<Depth> for Inventory {
+        fn initialize(&mut self, _engine: &mut Engine<Depth>) {}
+        fn cleanup(&mut self, _engine: &mut Engine<Depth>) {}
+        fn suspend(&mut self, _engine: &mut Engine<Depth>) {}
+        fn resume(&mut self, _engine: &mut Engine<Depth>) {}
+        fn handle(&mut self, _engine: &mut Engine<Depth>, _event: Event) -> Transition<Depth> {
+            Transition::Continue
+        }
+        fn update(&mut self, engine: &mut Engine<Depth>, _delta: f64) -> Transition<Depth> {
+            engine.data().0 = 7;
+            Transition::Halt
+        }
+        fn render(&mut self, _engine: &mut Engine<Depth>) {}
+    }
+
+    fn engine(name: &str, policy: AutosavePolicy) -> Engine<Depth> {
+        let directory = env::temp_dir().join(format!("oxide-autosave-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let mut engine = Engine::new(Depth(1));
+        engine.set_game_version(Version::new(1, 0, 0));
+        engine.set_save_slots(SaveSlots::new(directory));
+        engine.set_autosave(policy);
+        engine
+    }
+
+    fn saved_depth(engine: &Engine<Depth>) -> u32 {
+        let mut loaded = Engine::new(Depth(0));
+        loaded.set_game_version(Version::new(1, 0, 0));
+        loaded.set_save_slots(engine.save_slots().clone());
+        loaded.load_slot("auto").unwrap();
+        let depth = loaded.data().0;
+        depth
+    }
+
+    #[test]
+    fn policy_triggers() {
+        let policy = AutosavePolicy::new("auto").every_turns(10).on_halt(true);
+        assert!(!policy.wants(AutosaveTrigger::Suspend));
+        assert!(!policy.wants(AutosaveTrigger::Turn(9)));
+        assert!(policy.wants(AutosaveTrigger::Turn(20)));
+        assert!(!policy.wants(AutosaveTrigger::LevelChange));
+        assert!(policy.wants(AutosaveTrigger::Halt));
+        assert!(!policy.every_turns(0).wants(AutosaveTrigger::Turn(0)));
+    }
+
+    #[test]
+    fn saves_on_suspend() {
+        let mut engine = engine("suspend", AutosavePolicy::new("auto").on_suspend(true));
+        let mut states = StateManager::new(Explore(true));
+        states.start(&mut engine);
+        states.update(&mut engine, 0.1);
+        engine.flush_autosaves();
+        assert_eq!(saved_depth(&engine), 3);
+        states.update(&mut engine, 0.1);
+        assert!(!states.active());
+        engine.flush_autosaves();
+        assert_eq!(saved_depth(&engine), 3);
+        assert!(engine.autosave_errors().is_empty());
+        fs::remove_dir_all(engine.save_slots().directory()).unwrap();
+    }
+
+    #[test]
+    fn saves_on_halt_and_turns() {
+        let policy = AutosavePolicy::new("auto").on_halt(true).every_turns(2);
+        let mut engine = engine("halt", policy);
+        engine.end_turn();
+        engine.flush_autosaves();
+        assert!(!engine.save_slots().exists("auto"));
+        engine.data().0 = 2;
+        engine.end_turn();
+        engine.flush_autosaves();
+        assert_eq!(saved_depth(&engine), 2);
+
+        let mut states = StateManager::new(Explore(true));
+        states.start(&mut engine);
+        states.update(&mut engine, 0.1);
+        states.update(&mut engine, 0.1);
+        assert!(!states.active());
+        engine.flush_autosaves();
+        assert_eq!(saved_depth(&engine), 7);
+        fs::remove_dir_all(engine.save_slots().directory()).unwrap();
+    }
+}
@@ -0,0 +1,514 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Save Game Subsystem
+//!
+//! A save file is a single line JSON [`SaveHeader`] followed by the JSON payload holding the
+//! game's [`Data`], the [`Random`] service state, a snapshot of the ECS [`World`] and one snapshot
+//! per frozen level. Saves are usually kept in named slots managed by [`SaveSlots`].
+//!
+//! [`SaveHeader`]: struct.SaveHeader.html
+//! [`Data`]: trait.Data.html
+//! [`Random`]: struct.Random.html
+//! [`World`]: ../oxide_ecs/struct.World.html
+//! [`SaveSlots`]: struct.SaveSlots.html
+//!
+
+mod autosave;
+mod slots;
+
+pub(crate) use self::autosave::{Autosaver, Job};
+pub use self::autosave::{AutosavePolicy, AutosaveTrigger};
+pub use self::slots::SaveSlots;
+
+use super::level::LevelManager;
+use super::random::Random;
+use super::{Version, VERSION};
+use oxide_ecs::{SnapshotError, World, WorldSnapshot};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{self, Value};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Save Migration
+///
+/// Rewrites the payload of a save written by an older game version in place.
+pub type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Save File Header
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveHeader {
+    crate_version: Version,
+    game_version: Version,
+    length: u64,
+    checksum: u64,
+    timestamp: u64,
+}
+
+impl SaveHeader {
+    /// Version of Oxide which wrote the save
+    pub fn crate_version(&self) -> &Version {
+        &self.crate_version
+    }
+    /// Version of the game which wrote the save
+    pub fn game_version(&self) -> &Version {
+        &self.game_version
+    }
+    /// Length of the payload in bytes
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+    /// Checksum of the payload
+    pub fn checksum(&self) -> u64 {
+        self.checksum
+    }
+    /// Seconds since the Unix epoch at which the save was written
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+}
+
+/// Save and Load Errors
+#[derive(Debug)]
+pub enum SaveError {
+    /// Underlying I/O failure.
+    Io(io::Error),
+    /// The header or payload is not well formed.
+    Format(String),
+    /// The World could not be captured or restored.
+    Snapshot(SnapshotError),
+    /// The payload is shorter than the length recorded in the header.
+    Truncated { expected: u64, found: u64 },
+    /// The payload does not match the checksum recorded in the header.
+    ChecksumMismatch { expected: u64, found: u64 },
+    /// The save was written by a newer version than the one loading it.
+    NewerVersion { found: String, supported: String },
+    /// The save was written by an incompatible version of Oxide.
+    IncompatibleVersion { found: String, supported: String },
+    /// A registered migration failed.
+    Migration { version: Version, message: String },
+    /// The slot name is empty or contains characters other than letters, digits, `-` and `_`.
+    InvalidSlot(String),
+    /// There is no save in the slot.
+    NoSuchSlot(String),
+}
+
+impl SaveError {
+    /// Check if the error indicates a damaged save rather than an environmental problem.
+    pub fn is_corrupt(&self) -> bool {
+        matches!(*self, SaveError::Format(_) | SaveError::Truncated { .. } | SaveError::ChecksumMismatch { .. })
+    }
+}
+
+impl Display for SaveError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            SaveError::Io(ref error) => write!(f, "I/O error: {}", error),
+            SaveError::Format(ref message) => write!(f, "Malformed save: {}", message),
+            SaveError::Snapshot(ref error) => write!(f, "Snapshot error: {}", error),
+            SaveError::Truncated { expected, found } => {
+                write!(f, "Truncated save: expected {} bytes, found {}", expected, found)
+            }
+            SaveError::ChecksumMismatch { expected, found } => {
+                write!(f, "Checksum mismatch: expected {:016x}, found {:016x}", expected, found)
+            }
+            SaveError::NewerVersion { ref found, ref supported } => {
+                write!(f, "Save was written by version {} but only {} or older is supported", found, supported)
+            }
+            SaveError::IncompatibleVersion { ref found, ref supported } => {
+                write!(f, "Save was written by version {} which is incompatible with {}", found, supported)
+            }
+            SaveError::Migration { ref version, ref message } => {
+                write!(f, "Migration to {} failed: {}", version, message)
+            }
+            SaveError::InvalidSlot(ref slot) => write!(f, "Invalid save slot name '{}'", slot),
+            SaveError::NoSuchSlot(ref slot) => write!(f, "No save in slot '{}'", slot),
+        }
+    }
+}
+
+impl Error for SaveError {}
+
+impl From<io::Error> for SaveError {
+    fn from(error: io::Error) -> SaveError {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> SaveError {
+        SaveError::Format(error.to_string())
+    }
+}
+
+impl From<SnapshotError> for SaveError {
+    fn from(error: SnapshotError) -> SaveError {
+        SaveError::Snapshot(error)
+    }
+}
+
+/// Registered Migrations, ordered by the version they migrate to.
+#[derive(Default)]
+pub(crate) struct Migrations {
+    entries: Vec<(Version, Migration)>,
+}
+
+impl Migrations {
+    pub(crate) fn add(&mut self, version: Version, migration: Migration) {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.0.cmp_precedence(&version) == Ordering::Greater)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, (version, migration));
+    }
+    fn apply(&self, from: &Version, to: &Version, payload: &mut Value) -> Result<(), SaveError> {
+        for &(ref version, migration) in &self.entries {
+            if version.cmp_precedence(from) == Ordering::Greater
+                && version.cmp_precedence(to) != Ordering::Greater
+            {
+                migration(payload).map_err(|message| SaveError::Migration {
+                    version: version.clone(),
+                    message,
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct PayloadRef<'a, D: 'a> {
+    data: &'a D,
+    random: &'a Random,
+    world: &'a WorldSnapshot,
+    levels: &'a LevelManager,
+    turn: u64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Payload<D> {
+    pub(crate) data: D,
+    pub(crate) random: Random,
+    pub(crate) world: WorldSnapshot,
+    #[serde(default)]
+    pub(crate) levels: LevelManager,
+    #[serde(default)]
+    pub(crate) turn: u64,
+}
+
+/// Write the header and payload for a save.
+pub(crate) fn write<D, W>(writer: W, data: &D, random: &Random, world: &World, levels: &LevelManager, turn: u64, game_version: &Version) -> Result<SaveHeader, SaveError>
+    where
+        D: Serialize,
+        W: Write,
+{
+    let world = world.snapshot()?;
+    let payload = PayloadRef {
+        data,
+        random,
+        world: &world,
+        levels,
+        turn,
+    };
+    finish(writer, &payload, game_version)
+}
+
+/// Owned copy of everything a save holds, so it can be serialized away from the game thread.
+pub(crate) struct Capture<D> {
+    data: D,
+    random: Random,
+    world: WorldSnapshot,
+    levels: LevelManager,
+    turn: u64,
+    game_version: Version,
+}
+
+impl<D: Serialize> Capture<D> {
+    pub(crate) fn new(data: D, random: Random, world: &World, levels: LevelManager, turn: u64, game_version: Version) -> Result<Capture<D>, SaveError> {
+        Ok(Capture {
+            data,
+            random,
+            world: world.snapshot()?,
+            levels,
+            turn,
+            game_version,
+        })
+    }
+    /// Serialize the save into a buffer.
+    pub(crate) fn into_bytes(self) -> Result<Vec<u8>, SaveError> {
+        let mut buffer = Vec::new();
+        let payload = PayloadRef {
+            data: &self.data,
+            random: &self.random,
+            world: &self.world,
+            levels: &self.levels,
+            turn: self.turn,
+        };
+        finish(&mut buffer, &payload, &self.game_version)?;
+        Ok(buffer)
+    }
+}
+
+fn finish<D: Serialize, W: Write>(mut writer: W, payload: &PayloadRef<D>, game_version: &Version) -> Result<SaveHeader, SaveError> {
+    let payload = serde_json::to_vec(payload)?;
+    let header = SaveHeader {
+        crate_version: VERSION,
+        game_version: game_version.clone(),
+        length: payload.len() as u64,
+        checksum: checksum(&payload),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0),
+    };
+    serde_json::to_writer(&mut writer, &header)?;
+    writer.write_all(b"\n")?;
+    writer.write_all(&payload)?;
+    writer.flush()?;
+    Ok(header)
+}
+
+/// Read, verify and migrate a save.
+pub(crate) fn read<D, R>(reader: R, game_version: &Version, migrations: &Migrations) -> Result<(SaveHeader, Payload<D>), SaveError>
+    where
+        D: DeserializeOwned,
+        R: Read,
+{
+    let mut reader = BufReader::new(reader);
+    let header = read_header(&mut reader)?;
+    let mut payload = Vec::new();
+    reader.read_to_end(&mut payload)?;
+
+    if (payload.len() as u64) < header.length {
+        return Err(SaveError::Truncated {
+            expected: header.length,
+            found: payload.len() as u64,
+        });
+    }
+    let found = checksum(&payload);
+    if found != header.checksum {
+        return Err(SaveError::ChecksumMismatch {
+            expected: header.checksum,
+            found,
+        });
+    }
+    if !header.crate_version.is_compatible_with(&VERSION) {
+        return Err(SaveError::IncompatibleVersion {
+            found: header.crate_version.to_string(),
+            supported: VERSION.to_string(),
+        });
+    }
+    check_not_newer(&header.crate_version, &VERSION)?;
+    check_not_newer(&header.game_version, game_version)?;
+
+    let mut value: Value = serde_json::from_slice(&payload)?;
+    migrations.apply(&header.game_version, game_version, &mut value)?;
+    let payload: Payload<D> = serde_json::from_value(value)?;
+    Ok((header, payload))
+}
+
+/// Read the header line of a save.
+pub(crate) fn read_header<R: BufRead>(reader: &mut R) -> Result<SaveHeader, SaveError> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if !line.ends_with('\n') {
+        return Err(SaveError::Format("Incomplete header".to_string()));
+    }
+    Ok(serde_json::from_str(&line)?)
+}
+
+fn check_not_newer(found: &Version, supported: &Version) -> Result<(), SaveError> {
+    if found.cmp_precedence(supported) == Ordering::Greater {
+        Err(SaveError::NewerVersion {
+            found: found.to_string(),
+            supported: supported.to_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// 64 bit FNV-1a checksum
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Data, Engine};
+    use super::*;
+    use oxide_ecs::{Component, Persistent};
+    use rand::Rng;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Progress {
+        depth: u32,
+        #[serde(default)]
+        gold: u32,
+    }
+
+    impl Data for Progress {}
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Health(i32);
+
+    impl Component for Health {}
+
+    impl Persistent for Health {}
+
+    fn new_engine(version: Version) -> Engine<Progress> {
+        let mut engine = Engine::new(Progress { depth: 1, gold: 0 });
+        engine.set_game_version(version);
+        engine.world_mut().register_persistent::<Health>("health");
+        engine
+    }
+
+    fn add_gold(payload: &mut Value) -> Result<(), String> {
+        payload["data"]["gold"] = Value::from(100);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut engine = new_engine(Version::new(1, 0, 0));
+        engine.data().depth = 4;
+        let player = engine.world_mut().create_entity();
+        engine.world_mut().insert(player, Health(12));
+        engine.random().reseed(5);
+        engine.rng(Random::COMBAT).next_u32();
+        engine.end_turn();
+        engine.end_turn();
+        let mut buffer = Vec::new();
+        let header = engine.save(&mut buffer).unwrap();
+        assert_eq!(header.game_version(), &Version::new(1, 0, 0));
+
+        let mut loaded = new_engine(Version::new(1, 0, 0));
+        loaded.world_mut().create_entity();
+        assert_eq!(loaded.load(&buffer[..]).unwrap(), header);
+        assert_eq!(loaded.data().depth, 4);
+        assert_eq!(loaded.world().get::<Health>(player), Some(&Health(12)));
+        assert_eq!(loaded.random().seed(), 5);
+        assert_eq!(loaded.turn(), 2);
+        assert_eq!(loaded.rng(Random::COMBAT).next_u32(), engine.rng(Random::COMBAT).next_u32());
+    }
+
+    #[test]
+    fn migrates_older_saves() {
+        let mut buffer = Vec::new();
+        new_engine(Version::new(1, 0, 0)).save(&mut buffer).unwrap();
+
+        let mut loaded = new_engine(Version::new(1, 2, 0));
+        loaded.add_migration(Version::new(1, 1, 0), add_gold);
+        loaded.add_migration(Version::new(1, 0, 0), |_| Err("Already applied".to_string()));
+        loaded.load(&buffer[..]).unwrap();
+        assert_eq!(loaded.data().gold, 100);
+    }
+
+    #[test]
+    fn build_metadata_does_not_select_migrations() {
+        let mut buffer = Vec::new();
+        new_engine("1.1.0+a".parse().unwrap()).save(&mut buffer).unwrap();
+
+        let mut loaded = new_engine("1.1.0+c".parse().unwrap());
+        loaded.add_migration("1.1.0+b".parse().unwrap(), add_gold);
+        loaded.load(&buffer[..]).unwrap();
+        assert_eq!(loaded.data().gold, 0);
+    }
+
+    #[test]
+    fn refuses_newer_saves() {
+        let mut buffer = Vec::new();
+        new_engine(Version::new(2, 0, 0)).save(&mut buffer).unwrap();
+        match new_engine(Version::new(1, 9, 9)).load(&buffer[..]) {
+            Err(SaveError::NewerVersion { ref found, .. }) if found == "v2.0.0" => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn permadeath_deletes_loaded_slot() {
+        let directory = ::std::env::temp_dir().join(format!("oxide-permadeath-{}", ::std::process::id()));
+        let slots = SaveSlots::new(&directory).with_permadeath(true);
+        let mut engine = new_engine(Version::new(1, 0, 0));
+        engine.set_save_slots(slots.clone());
+        engine.data().depth = 9;
+        engine.save_slot("run").unwrap();
+        engine.save_slot("run").unwrap();
+
+        let mut loaded = new_engine(Version::new(1, 0, 0));
+        loaded.set_save_slots(slots.clone());
+        loaded.load_slot("run").unwrap();
+        assert_eq!(loaded.data().depth, 9);
+        assert!(!slots.exists("run"));
+        assert!(!slots.backup_path("run", 1).unwrap().exists());
+        match loaded.load_slot("run") {
+            Err(SaveError::NoSuchSlot(_)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        ::std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn failed_load_keeps_game() {
+        let mut buffer = Vec::new();
+        new_engine(Version::new(1, 0, 0)).save(&mut buffer).unwrap();
+
+        let mut loaded = Engine::new(Progress { depth: 3, gold: 0 });
+        loaded.set_game_version(Version::new(1, 0, 0));
+        let player = loaded.world_mut().create_entity();
+        loaded.random().reseed(8);
+        match loaded.load(&buffer[..]) {
+            Err(SaveError::Snapshot(SnapshotError::UnknownComponent(ref name))) if name == "health" => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(loaded.world().is_alive(player));
+        assert_eq!(loaded.data().depth, 3);
+        assert_eq!(loaded.random().seed(), 8);
+    }
+
+    #[test]
+    fn detects_truncation() {
+        let mut buffer = Vec::new();
+        new_engine(Version::new(1, 0, 0)).save(&mut buffer).unwrap();
+        let length = buffer.len();
+        match new_engine(Version::new(1, 0, 0)).load(&buffer[..length - 5]) {
+            Err(SaveError::Truncated { expected, found }) => assert_eq!(expected, found + 5),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        let newline = buffer.iter().position(|b| *b == b'\n').unwrap();
+        let error = new_engine(Version::new(1, 0, 0)).load(&buffer[..newline]).unwrap_err();
+        assert!(error.is_corrupt());
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let mut buffer = Vec::new();
+        new_engine(Version::new(1, 0, 0)).save(&mut buffer).unwrap();
+        let last = buffer.len() - 2;
+        buffer[last] ^= 0x01;
+        match new_engine(Version::new(1, 0, 0)).load(&buffer[..]) {
+            Err(SaveError::ChecksumMismatch { .. }) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Save Slots
+//!
+
+use super::{read_header, SaveError, SaveHeader};
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+const EXTENSION: &str = "sav";
+
+/// Distinguishes the temporary files of concurrent writes, such as a manual save racing the
+/// autosave worker.
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+/// Serializes backup rotation so concurrent writes cannot interleave their renames.
+static ROTATION: Mutex<()> = Mutex::new(());
+
+/// Named Save Slots in a directory
+///
+/// Each slot is stored as `<slot>.sav`. Saves are written to a uniquely named temporary file,
+/// synced to disk and renamed over the slot, so a crash mid-save leaves the previous save intact. Before a slot
+/// is replaced its previous save is kept as `<slot>.sav.1`, with older backups shifted up to the
+/// configured count.
+#[derive(Clone, Debug)]
+pub struct SaveSlots {
+    directory: PathBuf,
+    backups: usize,
+    permadeath: bool,
+}
+
+impl Default for SaveSlots {
+    fn default() -> SaveSlots {
+        SaveSlots::new("saves")
+    }
+}
+
+impl SaveSlots {
+    /// Save Slots in a directory, keeping two backups per slot.
+    pub fn new<P: Into<PathBuf>>(directory: P) -> SaveSlots {
+        SaveSlots {
+            directory: directory.into(),
+            backups: 2,
+            permadeath: false,
+        }
+    }
+    /// Set the number of backups kept per slot.
+    pub fn with_backups(mut self, backups: usize) -> SaveSlots {
+        self.backups = backups;
+        self
+    }
+    /// Delete a slot and its backups as soon as it has been loaded.
+    pub fn with_permadeath(mut self, permadeath: bool) -> SaveSlots {
+        self.permadeath = permadeath;
+        self
+    }
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+    pub fn backups(&self) -> usize {
+        self.backups
+    }
+    pub fn permadeath(&self) -> bool {
+        self.permadeath
+    }
+    /// Path of a slot's save file.
+    pub fn path(&self, slot: &str) -> Result<PathBuf, SaveError> {
+        let valid = !slot.is_empty() && slot.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if valid {
+            Ok(self.directory.join(format!("{}.{}", slot, EXTENSION)))
+        } else {
+            Err(SaveError::InvalidSlot(slot.to_string()))
+        }
+    }
+    /// Path of a slot's backup, where generation 1 is the most recent.
+    pub fn backup_path(&self, slot: &str, generation: usize) -> Result<PathBuf, SaveError> {
+        self.path(slot).map(|path| suffixed(&path, &generation.to_string()))
+    }
+    /// Check if a slot holds a save.
+    pub fn exists(&self, slot: &str) -> bool {
+        self.path(slot).map(|path| path.is_file()).unwrap_or(false)
+    }
+    /// Names of all slots holding a save, sorted.
+    pub fn list(&self) -> Result<Vec<String>, SaveError> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into()),
+        };
+        let mut slots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) == Some(EXTENSION) {
+                if let Some(slot) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    if self.path(slot).is_ok() {
+                        slots.push(slot.to_string());
+                    }
+                }
+            }
+        }
+        slots.sort();
+        Ok(slots)
+    }
+    /// Read only the header of a slot's save.
+    pub fn header(&self, slot: &str) -> Result<SaveHeader, SaveError> {
+        let file = open(&self.path(slot)?, slot)?;
+        read_header(&mut BufReader::new(file))
+    }
+    /// Read a slot's save.
+    pub fn read(&self, slot: &str) -> Result<Vec<u8>, SaveError> {
+        read_all(&self.path(slot)?, slot)
+    }
+    /// Read one of a slot's backups, where generation 1 is the most recent.
+    pub fn read_backup(&self, slot: &str, generation: usize) -> Result<Vec<u8>, SaveError> {
+        read_all(&self.backup_path(slot, generation)?, slot)
+    }
+    /// Atomically replace a slot's save, rotating the previous save into the backups.
+    pub fn write(&self, slot: &str, bytes: &[u8]) -> Result<(), SaveError> {
+        let path = self.path(slot)?;
+        let temp = suffixed(
+            &path,
+            &format!("{}-{}.tmp", process::id(), TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)),
+        );
+        fs::create_dir_all(&self.directory)?;
+        let written = File::create(&temp).and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        });
+        if let Err(error) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(error.into());
+        }
+        let _rotation = ROTATION.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if self.backups > 0 && path.is_file() {
+            for generation in (1..self.backups).rev() {
+                let older = self.backup_path(slot, generation)?;
+                if older.is_file() {
+                    fs::rename(&older, self.backup_path(slot, generation + 1)?)?;
+                }
+            }
+            fs::copy(&path, self.backup_path(slot, 1)?)?;
+        }
+        fs::rename(&temp, &path)?;
+        sync_directory(&self.directory);
+        Ok(())
+    }
+    /// Delete a slot's save, all of its backups and any interrupted writes.
+    ///
+    /// Backups beyond the configured count, left over from a larger count, are deleted too.
+    pub fn delete(&self, slot: &str) -> Result<(), SaveError> {
+        let path = self.path(slot)?;
+        remove(&path)?;
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error.into()),
+        };
+        let prefix = format!("{}.{}.", slot, EXTENSION);
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let suffix = match name.to_str().and_then(|name| name.strip_prefix(prefix.as_str())) {
+                Some(suffix) => suffix,
+                None => continue,
+            };
+            let backup = !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit());
+            if backup || suffix.ends_with("tmp") {
+                remove(&entry.path())?;
+            }
+        }
+        sync_directory(&self.directory);
+        Ok(())
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn open(path: &Path, slot: &str) -> Result<File, SaveError> {
+    File::open(path).map_err(|error| match error.kind() {
+        ErrorKind::NotFound => SaveError::NoSuchSlot(slot.to_string()),
+        _ => SaveError::Io(error),
+    })
+}
+
+fn read_all(path: &Path, slot: &str) -> Result<Vec<u8>, SaveError> {
+    let mut bytes = Vec::new();
+    open(path, slot)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn remove(path: &Path) -> Result<(), SaveError> {
+    match fs::remove_file(path) {
+        Err(ref error) if error.kind() == ErrorKind::NotFound => Ok(()),
+        result => result.map_err(SaveError::from),
+    }
+}
+
+/// Persist renames on platforms where directories can be synced. Failure only weakens the
+/// durability guarantee, so it is ignored.
+fn sync_directory(directory: &Path) {
+    if cfg!(unix) {
+        if let Ok(directory) = File::open(directory) {
+            let _ = directory.sync_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn slots(name: &str) -> SaveSlots {
+        let directory = env::temp_dir().join(format!("oxide-slots-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        SaveSlots::new(directory)
+    }
+
+    #[test]
+    fn rotates_backups() {
+        let slots = slots("rotate").with_backups(2);
+        for run in 1..5u8 {
+            slots.write("hero", &[run]).unwrap();
+        }
+        assert_eq!(slots.read("hero").unwrap(), vec![4]);
+        assert_eq!(slots.read_backup("hero", 1).unwrap(), vec![3]);
+        assert_eq!(slots.read_backup("hero", 2).unwrap(), vec![2]);
+        assert!(!slots.backup_path("hero", 3).unwrap().exists());
+        assert_eq!(fs::read_dir(slots.directory()).unwrap().count(), 3);
+        assert_eq!(slots.list().unwrap(), vec!["hero".to_string()]);
+
+        slots.delete("hero").unwrap();
+        assert!(!slots.exists("hero"));
+        assert!(!slots.backup_path("hero", 1).unwrap().exists());
+        fs::remove_dir_all(slots.directory()).unwrap();
+    }
+
+    #[test]
+    fn delete_removes_every_backup_and_interrupted_write() {
+        let slots = slots("delete").with_backups(4);
+        for run in 1..7u8 {
+            slots.write("hero", &[run]).unwrap();
+        }
+        let path = slots.path("hero").unwrap();
+        fs::write(suffixed(&path, "tmp"), [0]).unwrap();
+        fs::write(suffixed(&path, "1234-0.tmp"), [0]).unwrap();
+        slots.write("other", &[1]).unwrap();
+
+        let slots = slots.with_backups(1);
+        slots.delete("hero").unwrap();
+        let remaining: Vec<_> = fs::read_dir(slots.directory())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(remaining, vec!["other.sav".to_string()]);
+        fs::remove_dir_all(slots.directory()).unwrap();
+    }
+
+    #[test]
+    fn concurrent_writes_use_separate_temporary_files() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let slots = Arc::new(slots("concurrent").with_backups(1));
+        let writers: Vec<_> = (0..4u8)
+            .map(|writer| {
+                let slots = slots.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        slots.write("hero", &[writer; 64]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let saved = slots.read("hero").unwrap();
+        assert_eq!(saved.len(), 64);
+        assert!(saved.iter().all(|&byte| byte == saved[0]));
+        assert_eq!(fs::read_dir(slots.directory()).unwrap().count(), 2);
+        fs::remove_dir_all(slots.directory()).unwrap();
+    }
+
+    #[test]
+    fn reports_missing_and_invalid_slots() {
+        let slots = slots("missing");
+        assert_eq!(slots.list().unwrap(), Vec::<String>::new());
+        match slots.read("nobody") {
+            Err(SaveError::NoSuchSlot(ref slot)) if slot == "nobody" => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        match slots.write("../escape", &[]) {
+            Err(SaveError::InvalidSlot(_)) => (),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -20,6 +20,7 @@
 use super::data::Data;
 use super::engine::Engine;
 use super::event::Event;
+use super::save::AutosaveTrigger;
 
 /// State Transition commands
 pub enum Transition<D: Data> {
@@ -29,9 +30,9 @@ pub enum Transition<D: Data> {
     /// exist.
     Pop,
     /// Call Suspend the current state, push a new State onto the stack, call initialize.
-    Push(Box<State<D>>),
+    Push(Box<dyn State<D>>),
     /// Call cleanup on the current state, pop it off the stackSwitch to a new State by Popping off the old one and Pushing on a new one.
-    Switch(Box<State<D>>),
+    Switch(Box<dyn State<D>>),
     /// Pop All States and shut down.
     Halt,
 }
@@ -59,7 +60,7 @@ pub trait State<D: Data> {
 
 /// Simple Stack based State Machine
 pub(crate) struct StateManager<'a, D: Data> {
-    states: Vec<Box<State<D> + 'a>>,
+    states: Vec<Box<dyn State<D> + 'a>>,
     active: bool,
 }
 
@@ -118,15 +119,19 @@ impl<'a, D: Data> StateManager<'a, D> {
                 Transition::Pop => self.pop(engine),
                 Transition::Push(state) => self.push(engine, state),
                 Transition::Switch(state) => self.switch(engine, state),
-                Transition::Halt => self.stop(engine),
+                Transition::Halt => {
+                    engine.autosave(AutosaveTrigger::Halt);
+                    self.stop(engine)
+                }
             }
         }
     }
-    fn push(&mut self, engine: &mut Engine<D>, state: Box<State<D>>) {
+    fn push(&mut self, engine: &mut Engine<D>, state: Box<dyn State<D>>) {
         if self.active {
             // Suspend currently active state.
             if let Some(state) = self.states.last_mut() {
                 state.suspend(engine);
+                engine.autosave(AutosaveTrigger::Suspend);
             }
 
             self.states.push(state);
@@ -146,7 +151,7 @@ impl<'a, D: Data> StateManager<'a, D> {
             }
         }
     }
-    fn switch(&mut self, engine: &mut Engine<D>, state: Box<State<D>>) {
+    fn switch(&mut self, engine: &mut Engine<D>, state: Box<dyn State<D>>) {
         if self.active {
             if let Some(mut state) = self.states.pop() {
                 state.cleanup(engine);
@@ -0,0 +1,363 @@
+//
+// Copyright 2017 Hans W. Uhlig.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+//!
+//! Weighted Random Tables
+//!
+//! Tables are plain serde data so loot and spawn tables can live in data files:
+//!
+//! ```json
+//! {
+//!     "rolls": "1d3",
+//!     "guaranteed": [{ "item": "gold" }],
+//!     "entries": [
+//!         { "outcome": { "item": "potion" }, "weight": 10 },
+//!         { "outcome": { "item": "wand" }, "weight": 1, "weight_per_level": 2, "min_level": 3 },
+//!         { "outcome": "nothing", "weight": 5 }
+//!     ]
+//! }
+//! ```
+//!
+//! A single roll, including the rolls of every nested table, makes at most [`MAX_TABLE_DRAWS`] weighted
+//! draws, so a table whose `rolls` expression can produce huge counts fails instead of stalling.
+//!
+//! [`MAX_TABLE_DRAWS`]: constant.MAX_TABLE_DRAWS.html
+//!
+
+use super::dice::Dice;
+use rand::Rng;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Most weighted draws a single roll may make, counting those of nested tables
+pub const MAX_TABLE_DRAWS: u64 = 10_000;
+
+/// Random Table Errors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TableError {
+    /// The roll would make more than [`MAX_TABLE_DRAWS`] draws.
+    ///
+    /// [`MAX_TABLE_DRAWS`]: constant.MAX_TABLE_DRAWS.html
+    TooManyDraws(u64),
+}
+
+impl Display for TableError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            TableError::TooManyDraws(draws) => {
+                write!(f, "Table roll needs at least {} draws but at most {} are allowed", draws, MAX_TABLE_DRAWS)
+            }
+        }
+    }
+}
+
+impl Error for TableError {}
+
+/// Result of drawing a table entry
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome<T> {
+    /// A single result
+    Item(T),
+    /// A nested table, rolled with its own rolls and guaranteed outcomes
+    Table(Box<RandomTable<T>>),
+    /// No result, for tables which sometimes produce nothing
+    Nothing,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_rolls() -> Dice {
+    Dice::constant(1)
+}
+
+/// Weighted table entry
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableEntry<T> {
+    pub outcome: Outcome<T>,
+    /// Weight at `min_level`
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Weight gained, or lost if negative, for every level above `min_level`
+    #[serde(default)]
+    pub weight_per_level: i32,
+    /// First level at which the entry can be drawn
+    #[serde(default)]
+    pub min_level: u32,
+    /// Last level at which the entry can be drawn
+    #[serde(default)]
+    pub max_level: Option<u32>,
+}
+
+impl<T> TableEntry<T> {
+    /// Entry with a fixed weight available at every level.
+    pub fn new(outcome: Outcome<T>, weight: u32) -> TableEntry<T> {
+        TableEntry {
+            outcome,
+            weight,
+            weight_per_level: 0,
+            min_level: 0,
+            max_level: None,
+        }
+    }
+    /// Restrict the entry to a range of levels.
+    pub fn levels(mut self, min_level: u32, max_level: Option<u32>) -> TableEntry<T> {
+        self.min_level = min_level;
+        self.max_level = max_level;
+        self
+    }
+    /// Change the entry's weight by `weight_per_level` for every level above `min_level`.
+    pub fn scaled(mut self, weight_per_level: i32) -> TableEntry<T> {
+        self.weight_per_level = weight_per_level;
+        self
+    }
+    /// Weight of the entry at a level, zero where it cannot be drawn.
+    pub fn weight_at(&self, level: u32) -> u32 {
+        if level < self.min_level || self.max_level.is_some_and(|max| level > max) {
+            return 0;
+        }
+        let scaled = i64::from(self.weight) + i64::from(self.weight_per_level) * i64::from(level - self.min_level);
+        scaled.clamp(0, i64::from(u32::MAX)) as u32
+    }
+}
+
+/// Weighted Random Table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+pub struct RandomTable<T> {
+    /// Number of draws from `entries` per roll
+    #[serde(default = "default_rolls")]
+    pub rolls: Dice,
+    /// Draw without replacement, so no entry is drawn twice in one roll
+    #[serde(default)]
+    pub unique: bool,
+    /// Outcomes produced by every roll, before any draws
+    #[serde(default)]
+    pub guaranteed: Vec<Outcome<T>>,
+    #[serde(default)]
+    pub entries: Vec<TableEntry<T>>,
+}
+
+impl<T> Default for RandomTable<T> {
+    fn default() -> RandomTable<T> {
+        RandomTable {
+            rolls: default_rolls(),
+            unique: false,
+            guaranteed: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> RandomTable<T> {
+    /// Empty table drawing once per roll
+    pub fn new() -> RandomTable<T> {
+        RandomTable::default()
+    }
+    /// Add an item with a fixed weight.
+    pub fn item(self, item: T, weight: u32) -> RandomTable<T> {
+        self.entry(TableEntry::new(Outcome::Item(item), weight))
+    }
+    /// Add a nested table with a fixed weight.
+    pub fn table(self, table: RandomTable<T>, weight: u32) -> RandomTable<T> {
+        self.entry(TableEntry::new(Outcome::Table(Box::new(table)), weight))
+    }
+    /// Add a chance of producing nothing.
+    pub fn nothing(self, weight: u32) -> RandomTable<T> {
+        self.entry(TableEntry::new(Outcome::Nothing, weight))
+    }
+    /// Add an entry.
+    pub fn entry(mut self, entry: TableEntry<T>) -> RandomTable<T> {
+        self.entries.push(entry);
+        self
+    }
+    /// Add an outcome produced by every roll.
+    pub fn guarantee(mut self, outcome: Outcome<T>) -> RandomTable<T> {
+        self.guaranteed.push(outcome);
+        self
+    }
+    /// Set the number of draws per roll.
+    pub fn rolls(mut self, rolls: Dice) -> RandomTable<T> {
+        self.rolls = rolls;
+        self
+    }
+    /// Draw without replacement.
+    pub fn unique(mut self, unique: bool) -> RandomTable<T> {
+        self.unique = unique;
+        self
+    }
+    /// Total weight of all entries at a level.
+    pub fn total_weight(&self, level: u32) -> u64 {
+        self.entries.iter().map(|entry| u64::from(entry.weight_at(level))).sum()
+    }
+    /// Draw a single entry, resolving nested tables.
+    pub fn draw<R: Rng>(&self, rng: &mut R, level: u32) -> Result<Vec<T>, TableError> {
+        let mut results = Vec::new();
+        let mut budget = MAX_TABLE_DRAWS - 1;
+        let weights: Vec<u64> = self.entries.iter().map(|entry| u64::from(entry.weight_at(level))).collect();
+        if let Some(index) = pick(rng, &weights) {
+            resolve(&self.entries[index].outcome, rng, level, &mut budget, &mut results)?;
+        }
+        Ok(results)
+    }
+    /// Roll the table: every guaranteed outcome followed by `rolls` weighted draws.
+    ///
+    /// Fails without drawing further once the roll and its nested tables need more than
+    /// [`MAX_TABLE_DRAWS`] draws.
+    ///
+    /// [`MAX_TABLE_DRAWS`]: constant.MAX_TABLE_DRAWS.html
+    pub fn roll<R: Rng>(&self, rng: &mut R, level: u32) -> Result<Vec<T>, TableError> {
+        let mut results = Vec::new();
+        let mut budget = MAX_TABLE_DRAWS;
+        self.roll_into(rng, level, &mut budget, &mut results)?;
+        Ok(results)
+    }
+    fn roll_into<R: Rng>(&self, rng: &mut R, level: u32, budget: &mut u64, results: &mut Vec<T>) -> Result<(), TableError> {
+        for outcome in &self.guaranteed {
+            resolve(outcome, rng, level, budget, results)?;
+        }
+        let mut weights: Vec<u64> = self.entries.iter().map(|entry| u64::from(entry.weight_at(level))).collect();
+        let draws = self.rolls.roll_total(rng).max(0) as u64;
+        if draws > *budget {
+            return Err(TableError::TooManyDraws(MAX_TABLE_DRAWS - *budget + draws));
+        }
+        *budget -= draws;
+        for _ in 0..draws {
+            match pick(rng, &weights) {
+                Some(index) => {
+                    resolve(&self.entries[index].outcome, rng, level, budget, results)?;
+                    if self.unique {
+                        weights[index] = 0;
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn pick<R: Rng>(rng: &mut R, weights: &[u64]) -> Option<usize> {
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = rng.gen_range(0, total);
+    for (index, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return Some(index);
+        }
+        target -= weight;
+    }
+    None
+}
+
+fn resolve<T: Clone, R: Rng>(outcome: &Outcome<T>, rng: &mut R, level: u32, budget: &mut u64, results: &mut Vec<T>) -> Result<(), TableError> {
+    match *outcome {
+        Outcome::Item(ref item) => results.push(item.clone()),
+        Outcome::Table(ref table) => table.roll_into(rng, level, budget, results)?,
+        Outcome::Nothing => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use random::GameRng;
+    use rand::SeedableRng;
+    use serde_json;
+
+    #[test]
+    fn level_scaling() {
+        let entry = TableEntry::new(Outcome::Item("wand"), 2).levels(3, Some(10)).scaled(-1);
+        assert_eq!(entry.weight_at(2), 0);
+        assert_eq!(entry.weight_at(3), 2);
+        assert_eq!(entry.weight_at(4), 1);
+        assert_eq!(entry.weight_at(6), 0);
+        assert_eq!(entry.weight_at(11), 0);
+        let table = RandomTable::new().item("rat", 5).entry(entry);
+        assert_eq!(table.total_weight(3), 7);
+    }
+
+    #[test]
+    fn guaranteed_nested_and_unique() {
+        let gems = RandomTable::new().item("ruby", 1).item("opal", 1);
+        let table = RandomTable::new()
+            .guarantee(Outcome::Item("gold"))
+            .table(gems, 1)
+            .item("potion", 1)
+            .item("scroll", 1)
+            .rolls(Dice::constant(10))
+            .unique(true);
+        let mut rng = GameRng::from_seed(11);
+        for _ in 0..50 {
+            let mut loot = table.roll(&mut rng, 1).unwrap();
+            assert_eq!(loot.len(), 4);
+            assert_eq!(loot.remove(0), "gold");
+            assert!(loot.contains(&"potion") && loot.contains(&"scroll"));
+            assert!(loot.contains(&"ruby") || loot.contains(&"opal"));
+        }
+    }
+
+    #[test]
+    fn weights_are_respected() {
+        let table = RandomTable::new().item('a', 3).item('b', 1).nothing(0);
+        let mut rng = GameRng::from_seed(5);
+        let draws: Vec<char> = (0..4000).flat_map(|_| table.draw(&mut rng, 0).unwrap()).collect();
+        let a = draws.iter().filter(|c| **c == 'a').count();
+        assert_eq!(draws.len(), 4000);
+        assert!(a > 2850 && a < 3150, "{}", a);
+        assert!(RandomTable::<char>::new().nothing(1).roll(&mut rng, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn loads_from_data() {
+        let json = r#"{
+            "rolls": "2",
+            "guaranteed": [{ "item": "gold" }],
+            "entries": [
+                { "outcome": { "item": "potion" }, "weight": 10 },
+                { "outcome": { "item": "wand" }, "weight": 1, "weight_per_level": 2, "min_level": 3 },
+                { "outcome": "nothing" }
+            ]
+        }"#;
+        let table: RandomTable<String> = serde_json::from_str(json).unwrap();
+        assert_eq!(table.rolls, Dice::constant(2));
+        assert_eq!(table.total_weight(0), 11);
+        assert_eq!(table.total_weight(5), 16);
+        let mut rng = GameRng::from_seed(1);
+        let loot = table.roll(&mut rng, 5).unwrap();
+        assert_eq!(loot[0], "gold");
+        assert!(loot.len() <= 3);
+        let round_trip: RandomTable<String> = serde_json::from_str(&serde_json::to_string(&table).unwrap()).unwrap();
+        assert_eq!(round_trip, table);
+    }
+
+    #[test]
+    fn draws_are_limited() {
+        let mut rng = GameRng::from_seed(3);
+        let huge: RandomTable<u8> = RandomTable::new().item(1, 1).rolls("1000d1000000".parse().unwrap());
+        assert!(matches!(huge.roll(&mut rng, 0), Err(TableError::TooManyDraws(draws)) if draws > MAX_TABLE_DRAWS));
+
+        let inner = RandomTable::new().item(1u8, 1).rolls(Dice::constant(200));
+        let outer = RandomTable::new().table(inner, 1).rolls(Dice::constant(100));
+        assert!(outer.roll(&mut rng, 0).is_err());
+        assert_eq!(outer.draw(&mut rng, 0).unwrap().len(), 200);
+    }
+}
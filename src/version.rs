@@ -13,29 +13,334 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 //
+//!
+//! Semantic Versioning
+//!
 
-use std::fmt::{Display, Debug, Formatter, Result};
+use serde::de::{Deserialize, Deserializer, Error as DeError};
+use serde::ser::{Serialize, Serializer};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::str::FromStr;
 
+/// Semantic Version
+///
+/// Versions are ordered by [Semantic Versioning](https://semver.org) precedence. Build metadata
+/// does not affect precedence and is only compared, as plain text, to break ties between
+/// otherwise equal versions, so the ordering agrees with equality.
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Version {
-    major: &'static str,
-    minor: &'static str,
-    patch: &'static str,
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Cow<'static, str>,
+    build: Cow<'static, str>,
+}
+
+impl Version {
+    /// Create a release Version from its major, minor and patch components
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Version {
+        Version {
+            major,
+            minor,
+            patch,
+            pre: Cow::Borrowed(""),
+            build: Cow::Borrowed(""),
+        }
+    }
+    pub fn major(&self) -> u64 {
+        self.major
+    }
+    pub fn minor(&self) -> u64 {
+        self.minor
+    }
+    pub fn patch(&self) -> u64 {
+        self.patch
+    }
+    /// Dot separated pre-release identifiers, empty for releases.
+    pub fn pre_release(&self) -> &str {
+        &self.pre
+    }
+    /// Dot separated build metadata identifiers, empty if there is none.
+    pub fn build(&self) -> &str {
+        &self.build
+    }
+    /// Check if this is a pre-release Version
+    pub fn is_pre_release(&self) -> bool {
+        !self.pre.is_empty()
+    }
+    /// Check if two Versions are expected to interoperate.
+    ///
+    /// Versions are compatible when they agree on their leftmost non-zero component, following
+    /// Cargo's caret requirements: `1.2.0` and `1.9.3` are compatible, `0.2.0` and `0.3.0` are
+    /// not. Pre-releases are only compatible with Versions of the same major, minor and patch.
+    pub fn is_compatible_with(&self, other: &Version) -> bool {
+        if self.is_pre_release() || other.is_pre_release() {
+            return (self.major, self.minor, self.patch) == (other.major, other.minor, other.patch);
+        }
+        match (self.major, self.minor) {
+            (0, 0) => other.major == 0 && other.minor == 0 && self.patch == other.patch,
+            (0, minor) => other.major == 0 && other.minor == minor,
+            (major, _) => other.major == major,
+        }
+    }
+    /// Compare two Versions by precedence alone, ignoring build metadata.
+    pub fn cmp_precedence(&self, other: &Version) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (self.pre.is_empty(), other.pre.is_empty()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) => cmp_identifiers(&self.pre, &other.pre),
+            })
+    }
+    fn fmt_plain(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre)?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build)?;
+        }
+        Ok(())
+    }
+}
+
+fn cmp_identifiers(left: &str, right: &str) -> Ordering {
+    let mut left = left.split('.');
+    let mut right = right.split('.');
+    loop {
+        let ordering = match (left.next(), right.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(l), Some(r)) => match (l.parse::<u64>(), r.parse::<u64>()) {
+                (Ok(l), Ok(r)) => l.cmp(&r),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => l.cmp(r),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        self.cmp_precedence(other)
+            .then_with(|| self.build.cmp(&other.build))
+    }
 }
 
 impl Display for Version {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "v{}.{}.{})", self.major, self.minor, self.patch)
+        write!(f, "v")?;
+        self.fmt_plain(f)
     }
 }
 
 impl Debug for Version {
     fn fmt(&self, f: &mut Formatter) -> Result {
-        write!(f, "v{}.{}.{})", self.major, self.minor, self.patch)
+        write!(f, "v")?;
+        self.fmt_plain(f)
+    }
+}
+
+/// Version Parsing Errors
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VersionError {
+    /// The major, minor or patch component is missing.
+    MissingComponent(&'static str),
+    /// A numeric component is not a valid number.
+    InvalidNumber(String),
+    /// A numeric component or identifier has a leading zero.
+    LeadingZero(String),
+    /// A pre-release or build identifier is empty or contains invalid characters.
+    InvalidIdentifier(String),
+}
+
+impl Display for VersionError {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        match *self {
+            VersionError::MissingComponent(name) => write!(f, "Missing {} version", name),
+            VersionError::InvalidNumber(ref value) => write!(f, "Invalid version number '{}'", value),
+            VersionError::LeadingZero(ref value) => write!(f, "Leading zero in '{}'", value),
+            VersionError::InvalidIdentifier(ref value) => write!(f, "Invalid identifier '{}'", value),
+        }
+    }
+}
+
+impl Error for VersionError {}
+
+fn parse_number(value: Option<&str>, name: &'static str) -> ::std::result::Result<u64, VersionError> {
+    let value = value.ok_or(VersionError::MissingComponent(name))?;
+    if value.len() > 1 && value.starts_with('0') {
+        return Err(VersionError::LeadingZero(value.to_string()));
+    }
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(VersionError::InvalidNumber(value.to_string()));
+    }
+    value.parse().map_err(|_| VersionError::InvalidNumber(value.to_string()))
+}
+
+fn parse_identifiers(value: &str, pre_release: bool) -> ::std::result::Result<(), VersionError> {
+    for identifier in value.split('.') {
+        if identifier.is_empty() || !identifier.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+            return Err(VersionError::InvalidIdentifier(identifier.to_string()));
+        }
+        let numeric = identifier.bytes().all(|b| b.is_ascii_digit());
+        if pre_release && numeric && identifier.len() > 1 && identifier.starts_with('0') {
+            return Err(VersionError::LeadingZero(identifier.to_string()));
+        }
+    }
+    Ok(())
+}
+
+impl FromStr for Version {
+    type Err = VersionError;
+
+    /// Parse `major.minor.patch[-pre][+build]`, optionally prefixed with `v`.
+    fn from_str(value: &str) -> ::std::result::Result<Version, VersionError> {
+        let value = value.trim();
+        let value = value.strip_prefix('v').unwrap_or(value);
+        let (value, build) = match value.find('+') {
+            Some(index) => (&value[..index], Some(&value[index + 1..])),
+            None => (value, None),
+        };
+        let (value, pre) = match value.find('-') {
+            Some(index) => (&value[..index], Some(&value[index + 1..])),
+            None => (value, None),
+        };
+        let mut parts = value.split('.');
+        let major = parse_number(parts.next(), "major")?;
+        let minor = parse_number(parts.next(), "minor")?;
+        let patch = parse_number(parts.next(), "patch")?;
+        if let Some(extra) = parts.next() {
+            return Err(VersionError::InvalidNumber(extra.to_string()));
+        }
+        if let Some(pre) = pre {
+            parse_identifiers(pre, true)?;
+        }
+        if let Some(build) = build {
+            parse_identifiers(build, false)?;
+        }
+        Ok(Version {
+            major,
+            minor,
+            patch,
+            pre: Cow::Owned(pre.unwrap_or("").to_string()),
+            build: Cow::Owned(build.unwrap_or("").to_string()),
+        })
+    }
+}
+
+impl Serialize for Version {
+    fn serialize<S: Serializer>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error> {
+        struct Plain<'a>(&'a Version);
+        impl<'a> Display for Plain<'a> {
+            fn fmt(&self, f: &mut Formatter) -> Result {
+                self.0.fmt_plain(f)
+            }
+        }
+        serializer.collect_str(&Plain(self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> ::std::result::Result<Version, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(D::Error::custom)
     }
 }
 
+const fn parse_env(value: &str) -> u64 {
+    match u64::from_str_radix(value, 10) {
+        Ok(value) => value,
+        Err(_) => panic!("Package version components must be numeric"),
+    }
+}
+
+/// Version of this Library
 pub const VERSION: Version = Version {
-    major: env!("CARGO_PKG_VERSION_MAJOR"),
-    minor: env!("CARGO_PKG_VERSION_MINOR"),
-    patch: env!("CARGO_PKG_VERSION_PATCH"),
-};
\ No newline at end of file
+    major: parse_env(env!("CARGO_PKG_VERSION_MAJOR")),
+    minor: parse_env(env!("CARGO_PKG_VERSION_MINOR")),
+    patch: parse_env(env!("CARGO_PKG_VERSION_PATCH")),
+    pre: Cow::Borrowed(env!("CARGO_PKG_VERSION_PRE")),
+    build: Cow::Borrowed(""),
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(value: &str) -> Version {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let version = v("1.2.3-beta.2+build.7");
+        assert_eq!((version.major(), version.minor(), version.patch()), (1, 2, 3));
+        assert_eq!(version.pre_release(), "beta.2");
+        assert_eq!(version.build(), "build.7");
+        assert_eq!(version.to_string(), "v1.2.3-beta.2+build.7");
+        assert_eq!(v("v0.1.0"), Version::new(0, 1, 0));
+        assert_eq!(VERSION.to_string(), format!("v{}", env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("1.2".parse::<Version>(), Err(VersionError::MissingComponent("patch")));
+        assert_eq!("1.02.3".parse::<Version>(), Err(VersionError::LeadingZero("02".to_string())));
+        assert_eq!("1.x.3".parse::<Version>(), Err(VersionError::InvalidNumber("x".to_string())));
+        assert_eq!("1.2.3-".parse::<Version>(), Err(VersionError::InvalidIdentifier("".to_string())));
+        assert_eq!("1.2.3-01".parse::<Version>(), Err(VersionError::LeadingZero("01".to_string())));
+        assert!("1.2.3+01".parse::<Version>().is_ok());
+    }
+
+    #[test]
+    fn precedence() {
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.10.0",
+            "2.0.0",
+        ];
+        for pair in ordered.windows(2) {
+            assert!(v(pair[0]) < v(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(v("1.0.0+a").cmp_precedence(&v("1.0.0+b")), Ordering::Equal);
+        assert!(v("1.0.0+a") != v("1.0.0+b"));
+        assert!(v("1.0.0+01") != v("1.0.0+1"));
+        assert_ne!(v("1.0.0+01").cmp(&v("1.0.0+1")), Ordering::Equal);
+    }
+
+    #[test]
+    fn compatibility() {
+        assert!(v("1.2.0").is_compatible_with(&v("1.9.3")));
+        assert!(!v("1.2.0").is_compatible_with(&v("2.0.0")));
+        assert!(v("0.2.1").is_compatible_with(&v("0.2.7")));
+        assert!(!v("0.2.0").is_compatible_with(&v("0.3.0")));
+        assert!(!v("0.0.1").is_compatible_with(&v("0.0.2")));
+        assert!(!v("1.2.0-rc.1").is_compatible_with(&v("1.2.1")));
+        assert!(v("1.2.0-rc.1").is_compatible_with(&v("1.2.0")));
+    }
+}